@@ -15,8 +15,210 @@ export interface BaseWatermarkConfig {
   x_offset?: number;
   /** Y轴偏移 (像素) */
   y_offset?: number;
+  /** X轴偏移占底图宽度的百分比 (-100 到 100)，设置后取代 x_offset */
+  x_offset_pct?: number;
+  /** Y轴偏移占底图高度的百分比 (-100 到 100)，设置后取代 y_offset */
+  y_offset_pct?: number;
+  /** 亚像素偏移 [x, y]，每项在 [0, 1) 内，叠加在整数偏移之上 */
+  subpixel_offset?: [number, number];
+  /** 亚像素偏移的采样方式，默认 'bilinear' */
+  overlay_sampling?: 'bilinear' | 'nearest';
+  /**
+   * 未设置 position 时负偏移量的含义：
+   * 'start'（默认）水印左上角距图片右/下边缘 |offset|；'css' 水印右/下边缘距图片右/下边缘 |offset|（同 CSS right/bottom）
+   */
+  negative_offset_mode?: 'start' | 'css';
+  /** 单个水印超出图片时移回图片内（贴近最近的边缘），默认 false：部分超出的被裁切，完全在图片外的抛出错误 */
+  clamp_to_bounds?: boolean;
+  /** 锚点位置，设置后 x_offset/y_offset 为相对锚点的增量（默认 0） */
+  position?: 'top-left' | 'top' | 'top-right' | 'left' | 'center' | 'right' | 'bottom-left' | 'bottom' | 'bottom-right';
+  /** 通过 preload_watermark 预加载的水印名称，设置后无需再传 image_data */
+  watermark_key?: string;
   /** 是否平铺 */
   tile?: boolean;
+  /** 平铺水印的目标覆盖率 (0-100)，按水印实际不透明像素计算间距，设置后自动平铺 */
+  coverage_percent?: number;
+  /** 只在底图 alpha 非透明像素的外接矩形（主体范围）内平铺水印，设置后自动平铺 */
+  subject_tile?: boolean;
+  /** 边框水印：沿四周边框带重复绘制水印，内部保持干净 */
+  border_watermark?: BorderConfig;
+  /** 自水印：以底图自身缩小、去色后的副本平铺回原图，设置后无需 image_data */
+  self_watermark?: SelfWatermarkConfig;
+  /** 输出前将 RGB 预乘 alpha（用于要求预乘 alpha 的 WebGL 纹理） */
+  premultiply_output?: boolean;
+  /** 分块输出的块宽高 [宽, 高]，配合 add_watermark_tiles 使用 */
+  tile_output?: [number, number];
+  /** add_watermark_sweep 依次使用的不透明度列表 (0-1) */
+  transparency_sweep?: number[];
+  /** 输出格式，默认沿用输入图片的格式（无法识别时为 'png'）；'jpeg' 以 background_color 拍平透明度，'webp' 为无损编码 */
+  output_format?: OutputFormat;
+  /** JPEG 输出的压缩质量 (0-100)，默认 85；WebP 为无损编码，不受影响 */
+  quality?: number;
+  /** JPEG 底图输出为 JPEG 时保留原图的 EXIF（方向重置为 1），默认 false */
+  preserve_exif?: boolean;
+  /** 动画 GIF 逐帧添加水印并输出动画 GIF，默认 false（只处理第一帧）；不能与 autocrop 或非 GIF 的 output_format 同时使用 */
+  animated?: boolean;
+  /** 动画中添加水印的帧：'all'（默认）、'first' 或 'every:N'（第 0、N、2N... 帧），其余帧原样保留；需要 animated 为 true */
+  frame_selector?: 'all' | 'first' | `every:${number}`;
+  /** add_watermark_formats 一次输出的格式列表 */
+  output_formats?: OutputFormat[];
+  /** PNG 输出是否使用 Adam7 隔行扫描，默认 false；max_output_bytes 触发的量化输出不隔行 */
+  png_interlace?: boolean;
+  /** PNG 输出的字节数上限，超出时逐步减少调色板颜色数量化压缩（需启用 quantize 特性编译，否则超出时报错） */
+  max_output_bytes?: number;
+  /** 水印与背景亮度对比不足时自动提高不透明度，保证水印可见（边框模式不生效） */
+  ensure_visible?: boolean;
+  /** ensure_visible 自动提高不透明度的上限 (0-1]，默认 1.0 */
+  ensure_visible_max?: number;
+  /** 按水印下方的局部背景亮度把水印逐区域绘制为黑色或白色（保留 alpha），不能与 border_watermark 同时使用 */
+  auto_contrast?: boolean;
+  /** 翻转水印 */
+  flip?: 'horizontal' | 'vertical' | 'both';
+  /** 水印变换顺序，默认 ['resize', 'flip', 'rotate']，未列出的步骤按默认顺序追加 */
+  transform_order?: Array<'resize' | 'flip' | 'rotate'>;
+  /** 混合模式，默认 'normal'；'multiply'（正片叠底）适合阴影层，'screen'（滤色）只提亮，'overlay'（叠加）保留底图明暗 */
+  blend_mode?: 'normal' | 'multiply' | 'screen' | 'overlay';
+  /** add_watermarks 多水印叠加时的层级，数值小的先绘制（位于下层），默认 0，相同层级保持数组顺序 */
+  z_index?: number;
+  /** 平铺时保证至少有一个完整可见的水印，所有平铺水印都被边缘裁切时在中心额外放置一个 */
+  tile_ensure_full?: boolean;
+  /** 平铺网格的旋转角度（度），非 0 时沿斜向网格平铺，网格延伸到图片外，边缘水印被裁切，四角不会留空 */
+  tile_angle?: number;
+  /** 平铺网格的横向间距（像素，相邻水印左上角之间的距离），默认水印宽度 + |x_offset|，不能与 coverage_percent 同时使用 */
+  tile_spacing_x?: number;
+  /** 平铺网格的纵向间距（像素），默认水印高度 + |y_offset|，不能与 coverage_percent 同时使用 */
+  tile_spacing_y?: number;
+  /** 相邻平铺水印之间的横向空隙（像素），设置后间距为水印宽度 + 空隙，x_offset 只决定起始位置；不能与 tile_spacing_x、coverage_percent 同时使用 */
+  tile_margin_x?: number;
+  /** 相邻平铺水印之间的纵向空隙（像素），设置后间距为水印高度 + 空隙，y_offset 只决定起始位置 */
+  tile_margin_y?: number;
+  /** 平铺的行数上限（最大 1000），设置后只绘制 行数×列数 的水印块，按 position 定位（默认居中）；不能与 tile_angle 同时使用 */
+  tile_rows?: number;
+  /** 平铺的列数上限（最大 1000），未设置的一方按图片尺寸铺满 */
+  tile_cols?: number;
+  /** 平铺位置随机抖动幅度 (0-1)，每个水印在两个方向上最多偏移 jitter × 间距，使水印网格难以被整体抹除 */
+  jitter?: number;
+  /** 随机抖动（jitter、rotation_jitter）的种子，相同种子输出完全一致，默认 0 */
+  seed?: number;
+  /** 平铺时每个水印的随机旋转幅度（度），实际角度为 rotate ± rotation_jitter 内的随机值；要求 transform_order 以 'rotate' 结尾 */
+  rotation_jitter?: number;
+  /** 平铺时与主水印按棋盘格交替使用的第二个水印（base64），缩放、旋转等参数与主水印相同 */
+  tile_alternate_image_data?: string;
+  /** 不透明度热力图（base64 灰度图，自动缩放到底图尺寸），白色保持原不透明度，黑色完全不显示水印 */
+  opacity_map_data?: string;
+  /** 裁剪多边形（底图像素坐标，至少 3 个点），水印只作用于多边形内部，边缘抗锯齿 */
+  clip_polygon?: [number, number][];
+  /** 快速预览模式：缩放和旋转使用最近邻采样并跳过耗时效果，最终导出时应关闭 */
+  fast?: boolean;
+  /** 旋转质量：'fast'（默认）双线性插值；'high' 以 2 倍分辨率旋转后 Lanczos 缩小，斜边抗锯齿更好但更慢；fast 模式下忽略 */
+  rotation_quality?: 'fast' | 'high';
+  /** 缩放滤波器，默认 'lanczos3'；像素风 Logo 使用 'nearest' 保持锐利，大幅缩小时 'triangle' 更快；fast 模式下忽略 */
+  resize_filter?: 'nearest' | 'triangle' | 'catmullrom' | 'gaussian' | 'lanczos3';
+  /** 平铺时按底图局部亮度方差（亮度归一化到 0-1）筛选位置的阈值 */
+  tile_variance_threshold?: number;
+  /** 方差筛选方向：'above' 只放在细节丰富区域（默认），'below' 只放在平坦区域 */
+  tile_variance_polarity?: 'above' | 'below';
+  /** 视为透明的背景色 [R, G, B]，用于没有 alpha 通道的水印 */
+  color_key?: [number, number, number];
+  /** 颜色键容差（各通道最大差值，0-255），默认 0 */
+  color_key_tolerance?: number;
+  /** 形状遮罩：'circle'（内切圆，非正方形水印为椭圆）或 'rounded'（圆角矩形），在缩放之后、旋转之前应用，边缘羽化 */
+  mask?: 'circle' | 'rounded';
+  /** mask 为 'rounded' 时的圆角半径（像素），默认短边的 1/8 */
+  corner_radius?: number;
+  /** 四角不透明度 [左上, 右上, 左下, 右下] (0-1)，在水印内双线性插值，可实现方向性渐隐 */
+  corner_opacities?: [number, number, number, number];
+  /** 着色（十六进制，如 '#FFFFFF'），把水印所有非透明像素的颜色替换为该颜色并保留 alpha，同一个黑色 Logo 可用于深色图片 */
+  tint_color?: string;
+  /** 去色：按亮度 (0.299R + 0.587G + 0.114B) 转为灰度并保留 alpha；与 tint_color 同时设置时按亮度着色为单色 */
+  grayscale?: boolean;
+  /** 不透明度渐隐：'horizontal' 从左到右、'vertical' 从上到下、'radial' 从中心到四角由不透明渐变为透明，默认 'none'；transparency 仍为整体上限 */
+  gradient?: 'horizontal' | 'vertical' | 'radial' | 'none';
+  /** 投影：由水印的 alpha 生成阴影，偏移、模糊后垫在水印下方，提高在复杂背景上的辨识度；水印本身保持在配置的位置，只有阴影超出 */
+  shadow?: boolean;
+  /** 阴影水平偏移 (像素，可为负，-1000 到 1000)，默认 4 */
+  shadow_offset_x?: number;
+  /** 阴影垂直偏移 (像素，可为负，-1000 到 1000)，默认 4 */
+  shadow_offset_y?: number;
+  /** 阴影高斯模糊的 sigma (像素，0-100)，默认 2，0 为硬边阴影；fast 模式下不模糊 */
+  shadow_blur?: number;
+  /** 阴影颜色 (十六进制)，默认 '#000000' */
+  shadow_color?: string;
+  /** 源文件名，用于生成建议的下载文件名 */
+  source_name?: string;
+  /** 按 JPEG 底图的 EXIF 方向旋转/翻转后再添加水印，默认 true */
+  respect_exif_orientation?: boolean;
+  /** 底图像素数（宽 x 高）上限，超出时报错，默认 50000000 */
+  max_pixels?: number;
+  /** 合成后按整张图片的 alpha 外接矩形裁掉透明边框 */
+  autocrop?: boolean;
+  /** 丢弃 alpha 通道（以 background_color 拍平），输出 RGB 图片 */
+  force_rgb?: boolean;
+  /** 拍平透明度时使用的背景色（十六进制，用于 JPEG 输出和 force_rgb），默认 '#FFFFFF' */
+  background_color?: string;
+  /** JPEG 色度抽样，默认使用编码器默认值 */
+  jpeg_chroma_subsampling?: '4:4:4' | '4:2:2' | '4:2:0';
+  /** 输出原图与水印结果的差异图（放大显示），用于检查水印强度 */
+  diff_output?: boolean;
+  /** 差异放大倍数，默认 10 */
+  diff_amplification?: number;
+  /** 边缘去晕颜色 (十六进制)，设置后半透明边缘像素会向该颜色轻度混合 */
+  edge_matte_color?: string;
+  /** 边缘去晕强度 (0-1)，默认 0.5 */
+  edge_matte_strength?: number;
+}
+
+/**
+ * 边框水印配置
+ */
+export interface BorderConfig {
+  /** 边框带宽度 (像素) */
+  thickness: number;
+  /** 边框带底色 (十六进制)，不设置时保持原图 */
+  color?: string;
+  /** 相邻水印之间的间距 (像素)，默认为边框宽度的一半 */
+  spacing?: number;
+}
+
+/**
+ * 输出图片格式
+ */
+export type OutputFormat = 'png' | 'jpeg' | 'webp' | 'gif' | 'bmp';
+
+/**
+ * 渐变配置
+ */
+export interface GradientConfig {
+  /** 十六进制颜色，至少两个，沿方向均匀分布 */
+  colors: string[];
+  /** 渐变方向，默认 'vertical'（从上到下） */
+  direction?: 'vertical' | 'horizontal';
+}
+
+/**
+ * 标签底板配置
+ */
+export interface LabelBgConfig {
+  /** 十六进制底色，默认 '#000000' */
+  color?: string;
+  /** 渐变底色，设置后取代 color */
+  gradient?: GradientConfig;
+  /** 文字范围四周的内边距 (像素)，默认 8 */
+  padding?: number;
+  /** 圆角半径 (像素)，默认 0 */
+  corner_radius?: number;
+  /** 底板不透明度 (0-1)，默认 1.0 */
+  opacity?: number;
+}
+
+/**
+ * 自水印配置
+ */
+export interface SelfWatermarkConfig {
+  /** 叠加不透明度 (0-1)，默认 0.1 */
+  strength?: number;
+  /** 副本相对底图的缩放比例 (0-1]，默认 0.25 */
+  scale?: number;
 }
 
 /**
@@ -25,14 +227,24 @@ export interface BaseWatermarkConfig {
 export interface TextWatermarkConfig extends BaseWatermarkConfig {
   /** 水印类型 */
   type: 'text';
-  /** 水印文字 */
+  /** 水印文字，未提供 image_data 时由 WASM 用内置字体渲染（需启用 text-render 特性），支持 \n 换行 */
   text: string;
-  /** 字体名称 */
+  /** 字体名称（仅客户端渲染时生效，原生渲染固定使用 DejaVu Sans） */
   font?: string;
-  /** 字体大小 */
+  /** 字体大小 (像素)，默认 30 */
   font_size?: number;
-  /** 字体颜色 (十六进制) */
+  /** 字体颜色 (十六进制)，默认 '#FFFFFF' */
   font_color?: string;
+  /** 渐变填充，保留字形轮廓，取代 font_color 的纯色 */
+  text_gradient?: GradientConfig;
+  /** 文字后方的标签底板，按文字实际范围加内边距绘制 */
+  label_background?: LabelBgConfig;
+  /** 使用 SDF（有向距离场）按 width/height 缩放文字水印，任意尺寸保持锐利（需启用 sdf-text 特性） */
+  sdf_text?: boolean;
+  /** 文字水印目标宽度（仅 sdf_text 时生效，未设置时按比例计算） */
+  width?: number;
+  /** 文字水印目标高度（仅 sdf_text 时生效，未设置时按比例计算） */
+  height?: number;
 }
 
 /**
@@ -41,18 +253,35 @@ export interface TextWatermarkConfig extends BaseWatermarkConfig {
 export interface ImageWatermarkConfig extends BaseWatermarkConfig {
   /** 水印类型 */
   type: 'image';
-  /** base64编码的图片数据 */
-  image_data: string;
-  /** 水印图片宽度 */
+  /** base64编码的图片数据（使用 watermark_key 时可省略） */
+  image_data?: string;
+  /** 水印图片宽度，只设置 width 时高度按原始宽高比推算 */
   width?: number;
-  /** 水印图片高度 */
+  /** 水印图片高度，只设置 height 时宽度按原始宽高比推算 */
   height?: number;
+  /** 水印宽度占底图宽度的百分比 (0-100]，设置后取代 width */
+  width_pct?: number;
+  /** 水印高度占底图高度的百分比 (0-100]，设置后取代 height（与 height 一样需要同时设置宽度） */
+  height_pct?: number;
+  /** 备用水印（base64），image_data 解码失败时使用 */
+  fallback_image_data?: string;
+}
+
+/**
+ * 不可见水印配置：payload 写入蓝色通道最低位，只能输出 PNG，通过 extract_watermark 读取；
+ * 在 add_watermarks 中必须是第一个配置，且总是最后嵌入（忽略 z_index）
+ */
+export interface InvisibleWatermarkConfig extends BaseWatermarkConfig {
+  /** 水印类型 */
+  type: 'invisible';
+  /** 嵌入的字符串（UTF-8），每个字节占用 8 个像素，另有 64 个像素用于标记和长度 */
+  payload: string;
 }
 
 /**
  * 水印配置联合类型
  */
-export type WatermarkConfig = TextWatermarkConfig | ImageWatermarkConfig;
+export type WatermarkConfig = TextWatermarkConfig | ImageWatermarkConfig | InvisibleWatermarkConfig;
 
 /**
  * 文字水印配置选项
@@ -84,9 +313,9 @@ export interface TextWatermarkOptions {
 export interface ImageWatermarkOptions {
   /** base64编码的图片数据 */
   imageData: string;
-  /** 水印图片宽度 */
+  /** 水印图片宽度，只设置 width 时高度按原始宽高比推算 */
   width?: number;
-  /** 水印图片高度 */
+  /** 水印图片高度，只设置 height 时宽度按原始宽高比推算 */
   height?: number;
   /** 不透明度 (0-1) */
   transparency?: number;
@@ -126,6 +355,119 @@ export interface WorkerPoolStatus {
   queueLength: number;
 }
 
+/**
+ * probe_image 的探测结果
+ */
+export interface ImageInfo {
+  /** 图片宽度 (像素) */
+  width: number;
+  /** 图片高度 (像素) */
+  height: number;
+  /** 文件头识别出的格式 (如 'png'、'jpeg'、'webp') */
+  format: string;
+  /** 是否带透明通道（GIF 按解码后的 RGBA 计为 true） */
+  has_alpha: boolean;
+}
+
+/**
+ * 水印在底图上的叠加区域（已裁切到底图范围内）
+ */
+export interface WatermarkPlacement {
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+}
+
+/**
+ * 带元信息的水印处理结果
+ */
+export interface WatermarkResult {
+  /** 编码后的图片字节数组 */
+  readonly bytes: Uint8Array;
+  /** 输出图片宽度 (像素) */
+  readonly width: number;
+  /** 输出图片高度 (像素) */
+  readonly height: number;
+  /** 输出格式 (如 'png'，由 output_format 或输入图片格式决定) */
+  readonly format: string;
+  /** 建议的下载文件名 (如 'photo_watermarked.png') */
+  readonly suggested_filename: string;
+  /** 每个叠加的水印在底图上的区域，坐标基于 autocrop 等后处理之前的底图 */
+  readonly placements: WatermarkPlacement[];
+  /** 所有水印区域的外接矩形（平铺时即网格范围），没有叠加任何水印时为 undefined */
+  readonly placement_bounds: WatermarkPlacement | undefined;
+}
+
+/**
+ * 分块输出中的单个块
+ */
+export interface WatermarkTile {
+  /** 网格列号 */
+  readonly col: number;
+  /** 网格行号 */
+  readonly row: number;
+  /** 在完整图片中的 X 坐标 */
+  readonly x: number;
+  /** 在完整图片中的 Y 坐标 */
+  readonly y: number;
+  /** 块宽度 */
+  readonly width: number;
+  /** 块高度 */
+  readonly height: number;
+  /** 编码后的 PNG 字节数组（忽略 output_format） */
+  readonly bytes: Uint8Array;
+}
+
+/**
+ * 错误类别（WatermarkError.error_code 的取值）
+ */
+export declare const ErrorCode: {
+  /** 配置无法解析或参数不合法 */
+  readonly InvalidConfig: 0;
+  /** 底图或水印解码失败（如 base64 无效、图片数据损坏） */
+  readonly DecodeFailed: 1;
+  /** 无法识别的输入格式或不支持的输出格式 */
+  readonly UnsupportedFormat: 2;
+  /** 输出编码失败（含超出 max_output_bytes） */
+  readonly EncodeFailed: 3;
+  /** 其他处理错误 */
+  readonly ProcessingFailed: 4;
+};
+export type ErrorCode = typeof ErrorCode[keyof typeof ErrorCode];
+
+/**
+ * WASM 函数抛出的错误
+ */
+export interface WatermarkError {
+  /** 错误信息 */
+  readonly message: string;
+  /** 错误类别 */
+  readonly error_code: ErrorCode;
+}
+
+/**
+ * 高层函数（addWatermark 等）抛出的错误，保留 WASM 错误的类别
+ */
+export interface WatermarkProcessingError extends Error {
+  /** 错误类别（WASM 初始化失败或参数缺失等 JS 侧错误没有此字段） */
+  error_code?: ErrorCode;
+}
+
+/**
+ * 可复用的水印处理器（WASM 对象）
+ */
+export interface WatermarkProcessor {
+  /**
+   * 为一张图片添加水印，输出与 add_watermark 相同
+   * @param imageData - 图片字节数组
+   * @returns 处理后的图片字节数组
+   */
+  apply(imageData: Uint8Array): Uint8Array;
+  /** 释放WASM内存，之后不能再调用 apply */
+  free(): void;
+}
+
 /**
  * WASM函数接口
  */
@@ -140,11 +482,184 @@ export interface WasmFunctions {
 
   /**
    * 直接调用WASM的add_watermark_async函数
+   * 各处理步骤之间通过 setTimeout 让出事件循环，单个步骤内部仍同步执行；输出与 add_watermark 逐字节一致
    * @param imageData - 图片字节数组
    * @param config - 水印配置
    * @returns 处理后的图片字节数组
    */
   add_watermark_async(imageData: Uint8Array, config: WatermarkConfig): Promise<Uint8Array>;
+
+  /**
+   * 直接调用WASM的add_watermark_ex函数
+   * @param imageData - 图片字节数组
+   * @param config - 水印配置
+   * @returns 带元信息的处理结果
+   */
+  add_watermark_ex(imageData: Uint8Array, config: WatermarkConfig): WatermarkResult;
+
+  /**
+   * 直接调用WASM的add_watermark_data_url函数
+   * @param imageData - 图片字节数组
+   * @param config - 水印配置
+   * @returns data:image/<格式>;base64,... 字符串，MIME 与输出格式一致，可直接赋给 <img> 的 src
+   */
+  add_watermark_data_url(imageData: Uint8Array, config: WatermarkConfig): string;
+
+  /**
+   * 直接调用WASM的add_watermark_raw函数，水印图片以原始字节传入，省去 base64 编码和解码
+   * 水印字节优先于 config 中的 watermark_key 和 image_data，image_data 可以省略
+   * @param imageData - 图片字节数组
+   * @param watermarkBytes - 水印图片字节数组（PNG、JPEG 等）
+   * @param config - 水印配置
+   * @returns 处理后的图片字节数组
+   */
+  add_watermark_raw(imageData: Uint8Array, watermarkBytes: Uint8Array, config: WatermarkConfig): Uint8Array;
+
+  /**
+   * 直接调用WASM的add_watermark_json函数，配置以 JSON 字符串传入，省去 JSON.parse 和对象转换
+   * 配置解析失败时 InvalidConfig 错误的 message 中包含出错的行号和列号
+   * @param imageData - 图片字节数组
+   * @param configJson - JSON 字符串形式的水印配置
+   * @returns 处理后的图片字节数组
+   */
+  add_watermark_json(imageData: Uint8Array, configJson: string): Uint8Array;
+
+  /**
+   * 直接调用WASM的add_watermark_sweep函数，按 config.transparency_sweep 中的每个不透明度分别输出
+   * @param imageData - 图片字节数组
+   * @param config - 水印配置（需设置 transparency_sweep）
+   * @returns 与 transparency_sweep 顺序一致的结果数组
+   */
+  add_watermark_sweep(imageData: Uint8Array, config: WatermarkConfig): WatermarkResult[];
+
+  /**
+   * 直接调用WASM的add_watermark_formats函数，添加一次水印并按 config.output_formats 分别编码
+   * @param imageData - 图片字节数组
+   * @param config - 水印配置（需设置 output_formats）
+   * @returns 与 output_formats 顺序一致的结果数组
+   */
+  add_watermark_formats(imageData: Uint8Array, config: WatermarkConfig): WatermarkResult[];
+
+  /**
+   * 直接调用WASM的add_watermark_batch函数，用同一个配置批量添加水印
+   * 配置只解析一次，水印只解码、准备一次并复用到每张图片（自水印和 width_pct/height_pct 仍逐张准备）
+   * @param images - 图片字节数组列表
+   * @param config - 水印配置
+   * @returns 与输入顺序一致的数组，单张图片失败时对应位置为 WatermarkError，不会中断整批
+   */
+  add_watermark_batch(images: Uint8Array[], config: WatermarkConfig): Array<Uint8Array | WatermarkError>;
+
+  /**
+   * 直接调用WASM的add_watermark_batch_with_progress函数，与 add_watermark_batch 相同，每张图片处理完后回调进度
+   * @param images - 图片字节数组列表
+   * @param config - 水印配置
+   * @param onProgress - 进度回调，index 为刚处理完的图片序号（从 0 开始）；回调抛出的异常会被忽略，不会中断整批
+   * @returns 与输入顺序一致的数组，单张图片失败时对应位置为 WatermarkError
+   */
+  add_watermark_batch_with_progress(
+    images: Uint8Array[],
+    config: WatermarkConfig,
+    onProgress: (index: number, total: number) => void
+  ): Array<Uint8Array | WatermarkError>;
+
+  /**
+   * 创建WASM的WatermarkProcessor，配置只解析一次、水印只解码和准备一次
+   * 自水印和 width_pct/height_pct 依赖底图尺寸，仍在每次 apply 时准备水印
+   * @param config - 水印配置
+   * @returns 可复用的处理器，不再使用时调用 free()
+   */
+  create_watermark_processor(config: WatermarkConfig): WatermarkProcessor;
+
+  /**
+   * 直接调用WASM的add_watermark_tiles函数，添加水印后按 config.tile_output 切成网格，每块编码为 PNG
+   * @param imageData - 图片字节数组
+   * @param config - 水印配置（需设置 tile_output）
+   * @returns 按行优先排列的分块数组
+   */
+  add_watermark_tiles(imageData: Uint8Array, config: WatermarkConfig): WatermarkTile[];
+
+  /**
+   * 直接调用WASM的add_watermarks函数，一次叠加多个水印
+   * 按 z_index 从小到大绘制，输出参数（autocrop、force_rgb 等）取自第一个配置
+   * @param imageData - 图片字节数组
+   * @param configs - 水印配置数组，文字水印需已包含渲染好的 image_data
+   * @returns 处理后的图片字节数组
+   */
+  add_watermarks(imageData: Uint8Array, configs: WatermarkConfig[]): Uint8Array;
+
+  /**
+   * 直接调用WASM的add_watermark_qs函数
+   * @param imageData - 图片字节数组
+   * @param query - 查询字符串形式的配置，如 'transparency=0.5&rotate=30&anchor=bottom-right'
+   * @param watermarkBase64 - base64编码的水印图片
+   * @returns 处理后的图片字节数组
+   */
+  add_watermark_qs(imageData: Uint8Array, query: string, watermarkBase64: string): Uint8Array;
+
+  /**
+   * 直接调用WASM的add_watermark_simple函数（图片水印 + 不透明度 + 锚点）
+   * @param imageData - 图片字节数组
+   * @param watermarkBase64 - base64编码的水印图片
+   * @param transparency - 不透明度 (0-1)
+   * @param anchor - 锚点位置，默认 'bottom-right'
+   * @returns 处理后的图片字节数组
+   */
+  add_watermark_simple(imageData: Uint8Array, watermarkBase64: string, transparency: number, anchor?: BaseWatermarkConfig['position']): Uint8Array;
+
+  /**
+   * 直接调用WASM的watermark_svg函数，返回与栅格化结果定位一致的 SVG 水印片段
+   * 不支持 border_watermark、self_watermark、subject_tile、tile_variance_threshold、corner_opacities、gradient、shadow、tile_alternate_image_data、opacity_map_data、subpixel_offset、auto_contrast 和 rotation_jitter
+   * @param imageWidth - 底图宽度
+   * @param imageHeight - 底图高度
+   * @param config - 水印配置
+   * @returns SVG <g> 片段
+   */
+  watermark_svg(imageWidth: number, imageHeight: number, config: WatermarkConfig): string;
+
+  /**
+   * 直接调用WASM的lerp_config函数，按因子 t 在两套水印配置之间插值
+   * 数值字段（transparency、rotate、偏移量、width/height、coverage_percent、corner_opacities）在两侧都设置时线性插值，
+   * 其余字段 t < 0.5 取 a，否则取 b
+   * @param a - 起始配置（t = 0）
+   * @param b - 结束配置（t = 1）
+   * @param t - 插值因子，超出 0-1 时截断到端点；非有限数值时抛出 InvalidConfig 错误
+   * @returns 插值后的配置
+   */
+  lerp_config<T extends WatermarkConfig>(a: T, b: T, t: number): T;
+
+  /**
+   * 直接调用WASM的probe_image函数，只读取文件头探测图片信息，不解码像素
+   * 无法识别或不支持的格式抛出 error_code 为 UnsupportedFormat 的 WatermarkError，文件头损坏为 DecodeFailed
+   * @param imageData - 图片字节数组
+   * @returns 图片尺寸、格式和是否带透明通道
+   */
+  probe_image(imageData: Uint8Array): ImageInfo;
+
+  /**
+   * 直接调用WASM的extract_watermark函数，读取 type 为 'invisible' 时嵌入的 payload
+   * 没有找到水印（或已被有损压缩、缩放、裁剪破坏）时抛出 error_code 为 DecodeFailed 的 WatermarkError
+   * @param imageData - 图片字节数组（PNG）
+   * @returns 嵌入的字符串
+   */
+  extract_watermark(imageData: Uint8Array): string;
+
+  /**
+   * 预加载水印图片，之后可在配置中通过 watermark_key 引用
+   * @param key - 水印名称
+   * @param imageBase64 - base64编码的水印图片
+   */
+  preload_watermark(key: string, imageBase64: string): void;
+
+  /**
+   * 清空所有预加载的水印
+   */
+  clear_watermarks(): void;
+
+  /**
+   * 设置WASM内部的日志级别（默认关闭）
+   * @param level - 0=关闭，1=错误与警告，2=调试
+   */
+  set_log_level(level: 0 | 1 | 2): void;
 }
 
 /**
@@ -176,7 +691,7 @@ export function imageToUint8Array(imageData: File | Blob | ArrayBuffer | Uint8Ar
 /**
  * 将Uint8Array转换为Blob
  * @param data - 二进制数据
- * @param mimeType - MIME类型
+ * @param mimeType - MIME类型，默认根据文件头识别（JPEG、GIF、WebP、BMP，其余为 PNG）
  * @returns Blob
  */
 export function uint8ArrayToBlob(data: Uint8Array, mimeType?: string): Blob;
@@ -224,6 +739,15 @@ export function addWatermark(image: File | Blob | ArrayBuffer | Uint8Array, conf
  */
 export function addWatermarkAsync(image: File | Blob | ArrayBuffer | Uint8Array, config: WatermarkConfig): Promise<Blob>;
 
+/**
+ * 一次叠加多个水印，底图只解码和编码一次
+ * 文字水印没有 image_data 时先在客户端渲染，按 z_index 从小到大绘制
+ * @param image - 图片数据
+ * @param configs - 水印配置数组，输出参数取自第一个配置
+ * @returns 处理后的图片Blob
+ */
+export function addWatermarks(image: File | Blob | ArrayBuffer | Uint8Array, configs: WatermarkConfig[]): Promise<Blob>;
+
 /**
  * 使用Worker池添加水印（多线程处理）
  * @param image - 图片数据
@@ -9,6 +9,34 @@
 let wasmModule = null;
 let wasmInitialized = false;
 
+/**
+ * 错误类别，与 WASM 抛出的 WatermarkError.error_code 取值一致
+ * @readonly
+ * @enum {number}
+ */
+const ErrorCode = Object.freeze({
+  InvalidConfig: 0,
+  DecodeFailed: 1,
+  UnsupportedFormat: 2,
+  EncodeFailed: 3,
+  ProcessingFailed: 4
+});
+
+/**
+ * 包装处理错误，保留 WASM 错误的 error_code
+ * @private
+ * @param {string} prefix - 错误信息前缀
+ * @param {Error|Object} error - 原始错误
+ * @returns {Error}
+ */
+function wrapWatermarkError(prefix, error) {
+  const wrapped = new Error(`${prefix}: ${error.message}`);
+  if (error.error_code !== undefined) {
+    wrapped.error_code = error.error_code;
+  }
+  return wrapped;
+}
+
 /**
  * 初始化WASM模块
  * @param {string} [wasmPath] - WASM文件路径，默认自动查找
@@ -96,13 +124,27 @@ async function imageToUint8Array(imageData) {
   throw new Error('Unsupported image data type. Expected File, Blob, ArrayBuffer, or Uint8Array');
 }
 
+/**
+ * 根据文件头识别编码结果的MIME类型（输出格式默认沿用输入图片格式），无法识别时为 PNG
+ * @param {Uint8Array} data - 编码后的图片数据
+ * @returns {string}
+ */
+function detectImageMimeType(data) {
+  const startsWith = (bytes, offset = 0) => bytes.every((b, i) => data[offset + i] === b);
+  if (startsWith([0xff, 0xd8, 0xff])) return 'image/jpeg';
+  if (startsWith([0x47, 0x49, 0x46, 0x38])) return 'image/gif';
+  if (startsWith([0x52, 0x49, 0x46, 0x46]) && startsWith([0x57, 0x45, 0x42, 0x50], 8)) return 'image/webp';
+  if (startsWith([0x42, 0x4d])) return 'image/bmp';
+  return 'image/png';
+}
+
 /**
  * 将Uint8Array转换为Blob
  * @param {Uint8Array} data - 二进制数据
- * @param {string} [mimeType='image/png'] - MIME类型
+ * @param {string} [mimeType] - MIME类型，默认根据文件头识别
  * @returns {Blob}
  */
-function uint8ArrayToBlob(data, mimeType = 'image/png') {
+function uint8ArrayToBlob(data, mimeType = detectImageMimeType(data)) {
   return new Blob([data], { type: mimeType });
 }
 
@@ -286,7 +328,7 @@ async function addWatermark(image, config) {
   }
 
   // 如果是文字水印且没有image_data，先渲染文字为图片
-  if (config.type === 'text' && !config.image_data) {
+  if (String(config.type).toLowerCase() === 'text' && !config.image_data) {
     const renderedImage = await renderTextToImage(config.text, config);
     config = {
       ...config,
@@ -302,15 +344,15 @@ async function addWatermark(image, config) {
     const resultBytes = wasmModule.add_watermark(imageBytes, config);
 
     // 转换为Blob
-    return uint8ArrayToBlob(resultBytes, 'image/png');
+    return uint8ArrayToBlob(resultBytes);
   } catch (error) {
-    throw new Error(`Watermark processing failed: ${error.message}`);
+    throw wrapWatermarkError('Watermark processing failed', error);
   }
 }
 
 /**
  * 添加水印到图片（异步）
- * 使用WASM异步函数，适合处理大文件，避免阻塞主线程
+ * 使用WASM异步函数，处理步骤之间让出事件循环；单个步骤仍同步执行，完全不阻塞主线程请使用 addWatermarkWithWorkers
  * @param {File|Blob|ArrayBuffer|Uint8Array} image - 图片数据
  * @param {Object} config - 水印配置
  * @returns {Promise<Blob>} 处理后的图片Blob
@@ -327,7 +369,7 @@ async function addWatermarkAsync(image, config) {
   }
 
   // 如果是文字水印且没有image_data，先渲染文字为图片
-  if (config.type === 'text' && !config.image_data) {
+  if (String(config.type).toLowerCase() === 'text' && !config.image_data) {
     const renderedImage = await renderTextToImage(config.text, config);
     config = {
       ...config,
@@ -343,9 +385,44 @@ async function addWatermarkAsync(image, config) {
     const resultBytes = await wasmModule.add_watermark_async(imageBytes, config);
 
     // 转换为Blob
-    return uint8ArrayToBlob(resultBytes, 'image/png');
+    return uint8ArrayToBlob(resultBytes);
   } catch (error) {
-    throw new Error(`Watermark processing failed: ${error.message}`);
+    throw wrapWatermarkError('Watermark processing failed', error);
+  }
+}
+
+/**
+ * 一次叠加多个水印（如角落的 Logo 加底部的文字印章），底图只解码和编码一次
+ * 文字水印没有 image_data 时先在客户端渲染，按 z_index 从小到大绘制
+ * @param {File|Blob|ArrayBuffer|Uint8Array} image - 图片数据
+ * @param {Object[]} configs - 水印配置数组
+ * @returns {Promise<Blob>} 处理后的图片Blob
+ */
+async function addWatermarks(image, configs) {
+  await ensureInitialized();
+
+  if (!image) {
+    throw new Error('Image data is required');
+  }
+
+  if (!Array.isArray(configs) || configs.length === 0) {
+    throw new Error('Watermark configs array is required');
+  }
+
+  // 文字水印没有image_data时，先渲染文字为图片
+  const renderedConfigs = await Promise.all(configs.map(async (config) => {
+    if (String(config.type).toLowerCase() === 'text' && !config.image_data) {
+      return { ...config, image_data: await renderTextToImage(config.text, config) };
+    }
+    return config;
+  }));
+
+  try {
+    const imageBytes = await imageToUint8Array(image);
+    const resultBytes = wasmModule.add_watermarks(imageBytes, renderedConfigs);
+    return uint8ArrayToBlob(resultBytes);
+  } catch (error) {
+    throw wrapWatermarkError('Watermark processing failed', error);
   }
 }
 
@@ -375,6 +452,227 @@ const wasmFunctions = {
   add_watermark_async: async (imageData, config) => {
     await ensureInitialized();
     return wasmModule.add_watermark_async(imageData, config);
+  },
+
+  /**
+   * 直接调用WASM的add_watermark_ex函数
+   * @param {Uint8Array} imageData - 图片字节数组
+   * @param {Object} config - 水印配置
+   * @returns {WatermarkResult} 包含 bytes、width、height、format、suggested_filename 的处理结果
+   */
+  add_watermark_ex: async (imageData, config) => {
+    await ensureInitialized();
+    return wasmModule.add_watermark_ex(imageData, config);
+  },
+
+  /**
+   * 直接调用WASM的add_watermark_data_url函数，返回可直接赋给 <img> src 的 data URL
+   * @param {Uint8Array} imageData - 图片字节数组
+   * @param {Object} config - 水印配置
+   * @returns {string} data:image/<格式>;base64,... 字符串，MIME 与输出格式一致
+   */
+  add_watermark_data_url: async (imageData, config) => {
+    await ensureInitialized();
+    return wasmModule.add_watermark_data_url(imageData, config);
+  },
+
+  /**
+   * 直接调用WASM的add_watermark_raw函数，水印图片以原始字节传入，省去 base64 编码和解码
+   * @param {Uint8Array} imageData - 图片字节数组
+   * @param {Uint8Array} watermarkBytes - 水印图片字节数组（PNG、JPEG 等）
+   * @param {Object} config - 水印配置（无需 image_data）
+   * @returns {Uint8Array} 处理后的图片字节数组
+   */
+  add_watermark_raw: async (imageData, watermarkBytes, config) => {
+    await ensureInitialized();
+    return wasmModule.add_watermark_raw(imageData, watermarkBytes, config);
+  },
+
+  /**
+   * 直接调用WASM的add_watermark_json函数，配置以 JSON 字符串传入
+   * @param {Uint8Array} imageData - 图片字节数组
+   * @param {string} configJson - JSON 字符串形式的水印配置
+   * @returns {Uint8Array} 处理后的图片字节数组
+   */
+  add_watermark_json: async (imageData, configJson) => {
+    await ensureInitialized();
+    return wasmModule.add_watermark_json(imageData, configJson);
+  },
+
+  /**
+   * 直接调用WASM的add_watermark_sweep函数，按 config.transparency_sweep 中的每个不透明度分别输出
+   * @param {Uint8Array} imageData - 图片字节数组
+   * @param {Object} config - 水印配置（需设置 transparency_sweep）
+   * @returns {Object[]} 与 transparency_sweep 顺序一致的结果数组
+   */
+  add_watermark_sweep: async (imageData, config) => {
+    await ensureInitialized();
+    return wasmModule.add_watermark_sweep(imageData, config);
+  },
+
+  /**
+   * 直接调用WASM的add_watermark_formats函数，添加一次水印并按 config.output_formats 分别编码
+   * @param {Uint8Array} imageData - 图片字节数组
+   * @param {Object} config - 水印配置（需设置 output_formats）
+   * @returns {Object[]} 结果数组，每项包含 format、bytes 和 suggested_filename
+   */
+  add_watermark_formats: async (imageData, config) => {
+    await ensureInitialized();
+    return wasmModule.add_watermark_formats(imageData, config);
+  },
+
+  /**
+   * 直接调用WASM的add_watermark_batch函数，用同一个配置批量添加水印（配置只解析一次，水印只准备一次）
+   * @param {Uint8Array[]} images - 图片字节数组列表
+   * @param {Object} config - 水印配置
+   * @returns {Array<Uint8Array|WatermarkError>} 与输入顺序一致，成功为编码后的图片，失败为对应的 WatermarkError
+   */
+  add_watermark_batch: async (images, config) => {
+    await ensureInitialized();
+    return wasmModule.add_watermark_batch(images, config);
+  },
+
+  /**
+   * 直接调用WASM的add_watermark_batch_with_progress函数，与 add_watermark_batch 相同，每张图片处理完后回调进度
+   * @param {Uint8Array[]} images - 图片字节数组列表
+   * @param {Object} config - 水印配置
+   * @param {Function} onProgress - 进度回调 (index, total)，index 为刚处理完的图片序号（从 0 开始），回调抛出的异常会被忽略
+   * @returns {Array<Uint8Array|WatermarkError>} 与输入顺序一致，成功为编码后的图片，失败为对应的 WatermarkError
+   */
+  add_watermark_batch_with_progress: async (images, config, onProgress) => {
+    await ensureInitialized();
+    return wasmModule.add_watermark_batch_with_progress(images, config, onProgress);
+  },
+
+  /**
+   * 创建WASM的WatermarkProcessor：配置只解析一次、水印只准备一次，之后多次调用 apply
+   * 不再使用时需调用 free() 释放WASM内存
+   * @param {Object} config - 水印配置
+   * @returns {WatermarkProcessor} 处理器，apply(imageData) 返回处理后的图片字节数组
+   */
+  create_watermark_processor: async (config) => {
+    await ensureInitialized();
+    return new wasmModule.WatermarkProcessor(config);
+  },
+
+  /**
+   * 直接调用WASM的add_watermark_tiles函数，添加水印后按 config.tile_output 切成网格分别编码
+   * @param {Uint8Array} imageData - 图片字节数组
+   * @param {Object} config - 水印配置（需设置 tile_output）
+   * @returns {Object[]} 分块数组，每块包含 col、row、x、y、width、height 和 bytes
+   */
+  add_watermark_tiles: async (imageData, config) => {
+    await ensureInitialized();
+    return wasmModule.add_watermark_tiles(imageData, config);
+  },
+
+  /**
+   * 直接调用WASM的add_watermarks函数，一次叠加多个水印（按 z_index 排序）
+   * @param {Uint8Array} imageData - 图片字节数组
+   * @param {Object[]} configs - 水印配置数组，文字水印需已包含渲染好的 image_data
+   * @returns {Uint8Array} 处理后的图片字节数组
+   */
+  add_watermarks: async (imageData, configs) => {
+    await ensureInitialized();
+    return wasmModule.add_watermarks(imageData, configs);
+  },
+
+  /**
+   * 直接调用WASM的add_watermark_qs函数（查询字符串形式的配置）
+   * @param {Uint8Array} imageData - 图片字节数组
+   * @param {string} query - 如 'transparency=0.5&rotate=30&anchor=bottom-right'
+   * @param {string} watermarkBase64 - base64编码的水印图片
+   * @returns {Uint8Array} 处理后的图片字节数组
+   */
+  add_watermark_qs: async (imageData, query, watermarkBase64) => {
+    await ensureInitialized();
+    return wasmModule.add_watermark_qs(imageData, query, watermarkBase64);
+  },
+
+  /**
+   * 直接调用WASM的add_watermark_simple函数（图片水印 + 不透明度 + 锚点）
+   * @param {Uint8Array} imageData - 图片字节数组
+   * @param {string} watermarkBase64 - base64编码的水印图片
+   * @param {number} transparency - 不透明度(0-1)
+   * @param {string} [anchor='bottom-right'] - 锚点位置
+   * @returns {Uint8Array} 处理后的图片字节数组
+   */
+  add_watermark_simple: async (imageData, watermarkBase64, transparency, anchor) => {
+    await ensureInitialized();
+    return wasmModule.add_watermark_simple(imageData, watermarkBase64, transparency, anchor);
+  },
+
+  /**
+   * 直接调用WASM的watermark_svg函数，返回与栅格化结果定位一致的 SVG 水印片段
+   * @param {number} imageWidth - 底图宽度
+   * @param {number} imageHeight - 底图高度
+   * @param {Object} config - 水印配置
+   * @returns {string} SVG <g> 片段
+   */
+  watermark_svg: async (imageWidth, imageHeight, config) => {
+    await ensureInitialized();
+    return wasmModule.watermark_svg(imageWidth, imageHeight, config);
+  },
+
+  /**
+   * 直接调用WASM的lerp_config函数，按因子 t 在两套水印配置之间插值
+   * @param {Object} a - 起始配置（t = 0）
+   * @param {Object} b - 结束配置（t = 1）
+   * @param {number} t - 插值因子
+   * @returns {Object} 插值后的配置
+   */
+  lerp_config: async (a, b, t) => {
+    await ensureInitialized();
+    return wasmModule.lerp_config(a, b, t);
+  },
+
+  /**
+   * 直接调用WASM的probe_image函数，只读取文件头探测图片信息，用于在添加水印前快速拒绝无法处理的文件
+   * @param {Uint8Array} imageData - 图片字节数组
+   * @returns {Object} { width, height, format, has_alpha }
+   */
+  probe_image: async (imageData) => {
+    await ensureInitialized();
+    return wasmModule.probe_image(imageData);
+  },
+
+  /**
+   * 直接调用WASM的extract_watermark函数，读取不可见水印中嵌入的字符串
+   * @param {Uint8Array} imageData - 图片字节数组（PNG）
+   * @returns {string} 嵌入的字符串
+   */
+  extract_watermark: async (imageData) => {
+    await ensureInitialized();
+    return wasmModule.extract_watermark(imageData);
+  },
+
+  /**
+   * 预加载水印图片，之后可在配置中通过 watermark_key 引用
+   * 注意：注册表属于当前 WASM 实例，Worker 池中的每个 Worker 需要各自预加载
+   * @param {string} key - 水印名称
+   * @param {string} imageBase64 - base64编码的水印图片
+   */
+  preload_watermark: async (key, imageBase64) => {
+    await ensureInitialized();
+    return wasmModule.preload_watermark(key, imageBase64);
+  },
+
+  /**
+   * 清空所有预加载的水印
+   */
+  clear_watermarks: async () => {
+    await ensureInitialized();
+    return wasmModule.clear_watermarks();
+  },
+
+  /**
+   * 设置WASM内部的日志级别（默认关闭）
+   * 注意：日志级别属于当前 WASM 实例，Worker 池中的每个 Worker 需要各自设置
+   * @param {number} level - 0=关闭，1=错误与警告，2=调试
+   */
+  set_log_level: async (level) => {
+    await ensureInitialized();
+    return wasmModule.set_log_level(level);
   }
 };
 
@@ -431,7 +729,7 @@ async function addWatermarkWithWorkers(image, config) {
   }
 
   // 如果是文字水印且没有image_data，先渲染文字为图片
-  if (config.type === 'text' && !config.image_data) {
+  if (String(config.type).toLowerCase() === 'text' && !config.image_data) {
     config = {
       ...config,
       image_data: await renderTextToImage(config.text, config)
@@ -447,9 +745,9 @@ async function addWatermarkWithWorkers(image, config) {
       
       // 转换为Blob（检查是否为 Transferable Object）
       const blobData = resultBytes instanceof Uint8Array ? resultBytes : new Uint8Array(resultBytes);
-      return uint8ArrayToBlob(blobData, 'image/png');
+      return uint8ArrayToBlob(blobData);
   } catch (error) {
-      throw new Error(`Watermark processing failed: ${error.message}`);
+      throw wrapWatermarkError('Watermark processing failed', error);
   }
 }
 
@@ -471,7 +769,7 @@ async function addWatermarkBatch(images, config) {
   }
 
   // 如果是文字水印且没有image_data，先渲染文字为图片
-  if (config.type === 'text' && !config.image_data) {
+  if (String(config.type).toLowerCase() === 'text' && !config.image_data) {
     config = {
       ...config,
       image_data: await renderTextToImage(config.text, config)
@@ -490,10 +788,10 @@ async function addWatermarkBatch(images, config) {
       // 转换为Blob数组（检查是否为 Transferable Object）
       return resultBytesArray.map(bytes => {
           const blobData = bytes instanceof Uint8Array ? bytes : new Uint8Array(bytes);
-          return uint8ArrayToBlob(blobData, 'image/png');
+          return uint8ArrayToBlob(blobData);
       });
   } catch (error) {
-      throw new Error(`Batch watermark processing failed: ${error.message}`);
+      throw wrapWatermarkError('Batch watermark processing failed', error);
   }
 }
 
@@ -539,6 +837,7 @@ export {
   // 主要功能
   addWatermark,
   addWatermarkAsync,
+  addWatermarks,
   addWatermarkWithWorkers,
   addWatermarkBatch,
   
@@ -558,6 +857,9 @@ export {
   createTextWatermarkConfig,
   createImageWatermarkConfig,
   
+  // 错误类别
+  ErrorCode,
+  
   // WASM底层函数
   wasmFunctions
 };
@@ -573,6 +875,7 @@ if (typeof module !== 'undefined' && module.exports) {
     default: init,
     addWatermark,
     addWatermarkAsync,
+    addWatermarks,
     addWatermarkWithWorkers,
     addWatermarkBatch,
     initWorkerPool,
@@ -585,6 +888,7 @@ if (typeof module !== 'undefined' && module.exports) {
     defaultWatermarkConfig,
     createTextWatermarkConfig,
     createImageWatermarkConfig,
+    ErrorCode,
     wasmFunctions,
     add_watermark: wasmFunctions.add_watermark,
     add_watermark_async: wasmFunctions.add_watermark_async
@@ -1,35 +1,156 @@
 /* tslint:disable */
 /* eslint-disable */
 
+export enum ErrorCode {
+    InvalidConfig = 0,
+    DecodeFailed = 1,
+    UnsupportedFormat = 2,
+    EncodeFailed = 3,
+    ProcessingFailed = 4,
+}
+
 export class WatermarkError {
     private constructor();
     free(): void;
     [Symbol.dispose](): void;
+    toString(): string;
+    readonly error_code: ErrorCode;
     readonly message: string;
 }
 
+export class WatermarkProcessor {
+    free(): void;
+    [Symbol.dispose](): void;
+    apply(image_data: Uint8Array): Uint8Array;
+    constructor(config_js: any);
+}
+
+export class WatermarkResult {
+    private constructor();
+    free(): void;
+    [Symbol.dispose](): void;
+    readonly bytes: Uint8Array;
+    readonly format: string;
+    readonly height: number;
+    readonly placement_bounds: any;
+    readonly placements: any;
+    readonly suggested_filename: string;
+    readonly width: number;
+}
+
+export class WatermarkTile {
+    private constructor();
+    free(): void;
+    [Symbol.dispose](): void;
+    readonly bytes: Uint8Array;
+    readonly col: number;
+    readonly height: number;
+    readonly row: number;
+    readonly width: number;
+    readonly x: number;
+    readonly y: number;
+}
+
 export function add_watermark(image_data: Uint8Array, config_js: any): Uint8Array;
 
 export function add_watermark_async(image_data: Uint8Array, config_js: any): Promise<Uint8Array>;
 
+export function add_watermark_batch(images: Uint8Array[], config_js: any): any[];
+
+export function add_watermark_batch_with_progress(images: Uint8Array[], config_js: any, callback: Function): any[];
+
+export function add_watermark_data_url(image_data: Uint8Array, config_js: any): string;
+
+export function add_watermark_ex(image_data: Uint8Array, config_js: any): WatermarkResult;
+
+export function add_watermark_formats(image_data: Uint8Array, config_js: any): WatermarkResult[];
+
+export function add_watermark_json(image_data: Uint8Array, config_json: string): Uint8Array;
+
+export function add_watermark_qs(image_data: Uint8Array, query: string, watermark_b64: string): Uint8Array;
+
+export function add_watermark_raw(image_data: Uint8Array, watermark_bytes: Uint8Array, config_js: any): Uint8Array;
+
+export function add_watermark_simple(image_data: Uint8Array, watermark_b64: string, transparency: number, anchor?: string | null): Uint8Array;
+
+export function add_watermark_sweep(image_data: Uint8Array, config_js: any): WatermarkResult[];
+
+export function add_watermark_tiles(image_data: Uint8Array, config_js: any): WatermarkTile[];
+
+export function add_watermarks(image_data: Uint8Array, configs_js: any): Uint8Array;
+
+export function clear_watermarks(): void;
+
+export function extract_watermark(image_data: Uint8Array): string;
+
 export function init(): void;
 
+export function lerp_config(a_js: any, b_js: any, t: number): any;
+
+export function preload_watermark(key: string, image_b64: string): void;
+
+export function probe_image(image_data: Uint8Array): any;
+
+export function set_log_level(level: number): void;
+
+export function watermark_svg(image_w: number, image_h: number, config_js: any): string;
+
 export type InitInput = RequestInfo | URL | Response | BufferSource | WebAssembly.Module;
 
 export interface InitOutput {
     readonly memory: WebAssembly.Memory;
     readonly __wbg_watermarkerror_free: (a: number, b: number) => void;
+    readonly __wbg_watermarkprocessor_free: (a: number, b: number) => void;
+    readonly __wbg_watermarkresult_free: (a: number, b: number) => void;
+    readonly __wbg_watermarktile_free: (a: number, b: number) => void;
     readonly add_watermark: (a: number, b: number, c: number, d: number) => void;
     readonly add_watermark_async: (a: number, b: number, c: number) => number;
+    readonly add_watermark_batch: (a: number, b: number, c: number, d: number) => void;
+    readonly add_watermark_batch_with_progress: (a: number, b: number, c: number, d: number, e: number) => void;
+    readonly add_watermark_data_url: (a: number, b: number, c: number, d: number) => void;
+    readonly add_watermark_ex: (a: number, b: number, c: number, d: number) => void;
+    readonly add_watermark_formats: (a: number, b: number, c: number, d: number) => void;
+    readonly add_watermark_json: (a: number, b: number, c: number, d: number, e: number) => void;
+    readonly add_watermark_qs: (a: number, b: number, c: number, d: number, e: number, f: number, g: number) => void;
+    readonly add_watermark_raw: (a: number, b: number, c: number, d: number, e: number, f: number) => void;
+    readonly add_watermark_simple: (a: number, b: number, c: number, d: number, e: number, f: number, g: number, h: number) => void;
+    readonly add_watermark_sweep: (a: number, b: number, c: number, d: number) => void;
+    readonly add_watermark_tiles: (a: number, b: number, c: number, d: number) => void;
+    readonly add_watermarks: (a: number, b: number, c: number, d: number) => void;
+    readonly clear_watermarks: () => void;
+    readonly extract_watermark: (a: number, b: number, c: number) => void;
     readonly init: () => void;
+    readonly lerp_config: (a: number, b: number, c: number, d: number) => void;
+    readonly preload_watermark: (a: number, b: number, c: number, d: number, e: number) => void;
+    readonly probe_image: (a: number, b: number, c: number) => void;
+    readonly set_log_level: (a: number) => void;
+    readonly watermark_svg: (a: number, b: number, c: number, d: number) => void;
+    readonly watermarkerror_error_code: (a: number) => number;
     readonly watermarkerror_message: (a: number, b: number) => void;
-    readonly __wasm_bindgen_func_elem_365: (a: number, b: number) => void;
-    readonly __wasm_bindgen_func_elem_474: (a: number, b: number, c: number, d: number) => void;
-    readonly __wasm_bindgen_func_elem_371: (a: number, b: number, c: number) => void;
+    readonly watermarkerror_toString: (a: number, b: number) => void;
+    readonly watermarkprocessor_apply: (a: number, b: number, c: number, d: number) => void;
+    readonly watermarkprocessor_new: (a: number, b: number) => void;
+    readonly watermarkresult_bytes: (a: number, b: number) => void;
+    readonly watermarkresult_format: (a: number, b: number) => void;
+    readonly watermarkresult_height: (a: number) => number;
+    readonly watermarkresult_placement_bounds: (a: number) => number;
+    readonly watermarkresult_placements: (a: number) => number;
+    readonly watermarkresult_suggested_filename: (a: number, b: number) => void;
+    readonly watermarkresult_width: (a: number) => number;
+    readonly watermarktile_bytes: (a: number, b: number) => void;
+    readonly watermarktile_col: (a: number) => number;
+    readonly watermarktile_height: (a: number) => number;
+    readonly watermarktile_row: (a: number) => number;
+    readonly watermarktile_width: (a: number) => number;
+    readonly watermarktile_x: (a: number) => number;
+    readonly watermarktile_y: (a: number) => number;
+    readonly __wasm_bindgen_func_elem_2154: (a: number, b: number, c: number, d: number) => void;
+    readonly __wasm_bindgen_func_elem_2162: (a: number, b: number, c: number, d: number) => void;
     readonly __wbindgen_export: (a: number, b: number) => number;
     readonly __wbindgen_export2: (a: number, b: number, c: number, d: number) => number;
     readonly __wbindgen_export3: (a: number) => void;
-    readonly __wbindgen_export4: (a: number, b: number, c: number) => void;
+    readonly __wbindgen_export4: (a: number, b: number) => void;
+    readonly __wbindgen_export5: (a: number, b: number, c: number) => void;
     readonly __wbindgen_add_to_stack_pointer: (a: number) => number;
     readonly __wbindgen_start: () => void;
 }
@@ -2,16 +2,56 @@
 /* eslint-disable */
 export const memory: WebAssembly.Memory;
 export const __wbg_watermarkerror_free: (a: number, b: number) => void;
+export const __wbg_watermarkprocessor_free: (a: number, b: number) => void;
+export const __wbg_watermarkresult_free: (a: number, b: number) => void;
+export const __wbg_watermarktile_free: (a: number, b: number) => void;
 export const add_watermark: (a: number, b: number, c: number, d: number) => void;
 export const add_watermark_async: (a: number, b: number, c: number) => number;
+export const add_watermark_batch: (a: number, b: number, c: number, d: number) => void;
+export const add_watermark_batch_with_progress: (a: number, b: number, c: number, d: number, e: number) => void;
+export const add_watermark_data_url: (a: number, b: number, c: number, d: number) => void;
+export const add_watermark_ex: (a: number, b: number, c: number, d: number) => void;
+export const add_watermark_formats: (a: number, b: number, c: number, d: number) => void;
+export const add_watermark_json: (a: number, b: number, c: number, d: number, e: number) => void;
+export const add_watermark_qs: (a: number, b: number, c: number, d: number, e: number, f: number, g: number) => void;
+export const add_watermark_raw: (a: number, b: number, c: number, d: number, e: number, f: number) => void;
+export const add_watermark_simple: (a: number, b: number, c: number, d: number, e: number, f: number, g: number, h: number) => void;
+export const add_watermark_sweep: (a: number, b: number, c: number, d: number) => void;
+export const add_watermark_tiles: (a: number, b: number, c: number, d: number) => void;
+export const add_watermarks: (a: number, b: number, c: number, d: number) => void;
+export const clear_watermarks: () => void;
+export const extract_watermark: (a: number, b: number, c: number) => void;
 export const init: () => void;
+export const lerp_config: (a: number, b: number, c: number, d: number) => void;
+export const preload_watermark: (a: number, b: number, c: number, d: number, e: number) => void;
+export const probe_image: (a: number, b: number, c: number) => void;
+export const set_log_level: (a: number) => void;
+export const watermark_svg: (a: number, b: number, c: number, d: number) => void;
+export const watermarkerror_error_code: (a: number) => number;
 export const watermarkerror_message: (a: number, b: number) => void;
-export const __wasm_bindgen_func_elem_365: (a: number, b: number) => void;
-export const __wasm_bindgen_func_elem_474: (a: number, b: number, c: number, d: number) => void;
-export const __wasm_bindgen_func_elem_371: (a: number, b: number, c: number) => void;
+export const watermarkerror_toString: (a: number, b: number) => void;
+export const watermarkprocessor_apply: (a: number, b: number, c: number, d: number) => void;
+export const watermarkprocessor_new: (a: number, b: number) => void;
+export const watermarkresult_bytes: (a: number, b: number) => void;
+export const watermarkresult_format: (a: number, b: number) => void;
+export const watermarkresult_height: (a: number) => number;
+export const watermarkresult_placement_bounds: (a: number) => number;
+export const watermarkresult_placements: (a: number) => number;
+export const watermarkresult_suggested_filename: (a: number, b: number) => void;
+export const watermarkresult_width: (a: number) => number;
+export const watermarktile_bytes: (a: number, b: number) => void;
+export const watermarktile_col: (a: number) => number;
+export const watermarktile_height: (a: number) => number;
+export const watermarktile_row: (a: number) => number;
+export const watermarktile_width: (a: number) => number;
+export const watermarktile_x: (a: number) => number;
+export const watermarktile_y: (a: number) => number;
+export const __wasm_bindgen_func_elem_2154: (a: number, b: number, c: number, d: number) => void;
+export const __wasm_bindgen_func_elem_2162: (a: number, b: number, c: number, d: number) => void;
 export const __wbindgen_export: (a: number, b: number) => number;
 export const __wbindgen_export2: (a: number, b: number, c: number, d: number) => number;
 export const __wbindgen_export3: (a: number) => void;
-export const __wbindgen_export4: (a: number, b: number, c: number) => void;
+export const __wbindgen_export4: (a: number, b: number) => void;
+export const __wbindgen_export5: (a: number, b: number, c: number) => void;
 export const __wbindgen_add_to_stack_pointer: (a: number) => number;
 export const __wbindgen_start: () => void;
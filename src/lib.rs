@@ -2,8 +2,14 @@ use wasm_bindgen::prelude::*;
 use image::{DynamicImage, RgbaImage, GenericImageView};
 use serde::{Deserialize, Serialize};
 use std::io::Cursor;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU8, Ordering};
 use base64::{Engine as _, engine::general_purpose::STANDARD};
 
+use image::ImageEncoder;
+
 // 只在开发时启用 panic hook
 #[cfg(feature = "console_error_panic_hook")]
 use console_error_panic_hook::set_once;
@@ -11,9 +17,9 @@ use console_error_panic_hook::set_once;
 // 水印配置结构体
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WatermarkConfig {
-    // 水印类型：text 或 image
+    // 水印类型：text、image 或 invisible（不区分大小写）
     #[serde(rename = "type")]
-    pub watermark_type: String,
+    pub watermark_type: WatermarkType,
     
     // 水印布局和效果参数
     #[serde(default)]
@@ -25,7 +31,77 @@ pub struct WatermarkConfig {
     #[serde(default)]
     pub y_offset: Option<i32>,
     #[serde(default)]
+    pub x_offset_pct: Option<f32>, // X 偏移占底图宽度的百分比 (-100 到 100)，设置后取代 x_offset
+    #[serde(default)]
+    pub y_offset_pct: Option<f32>, // Y 偏移占底图高度的百分比 (-100 到 100)，设置后取代 y_offset
+    #[serde(default)]
+    pub subpixel_offset: Option<[f32; 2]>, // 亚像素偏移 [x, y]，每项在 [0, 1) 内，叠加在整数偏移之上，用于平滑动画
+    #[serde(default)]
+    pub overlay_sampling: Option<String>, // 亚像素偏移的采样方式："bilinear"（默认，边缘平滑）或 "nearest"（更快，四舍五入到整像素，边缘不发虚）
+    #[serde(default)]
+    pub negative_offset_mode: Option<String>, // 未设置 position 时负偏移量的含义："start"（默认，水印左上角距图片右/下边缘 |offset|）或 "css"（水印右/下边缘距图片右/下边缘 |offset|）
+    #[serde(default)]
+    pub clamp_to_bounds: Option<bool>, // 单个水印超出图片时移回图片内（贴近最近的边缘），默认 false：部分超出的被裁切，完全在图片外的返回错误
+    #[serde(default)]
+    pub position: Option<String>, // 锚点位置：top-left/top/top-right/left/center/right/bottom-left/bottom/bottom-right
+    #[serde(default)]
     pub tile: Option<bool>,
+    #[serde(default)]
+    pub coverage_percent: Option<f32>, // 平铺水印的目标覆盖率（按水印实际不透明像素计算，0-100），设置后自动平铺
+    #[serde(default)]
+    pub subject_tile: Option<bool>, // 主体平铺：只在底图 alpha 非透明像素的外接矩形（主体范围）内平铺水印，用于透明底的商品图
+    #[serde(default)]
+    pub border_watermark: Option<BorderConfig>, // 边框水印：沿四周边框带重复绘制水印，内部保持干净
+    #[serde(default)]
+    pub self_watermark: Option<SelfWatermarkConfig>, // 自水印：以底图自身缩小、去色后的副本作为水印平铺回原图，无需 image_data
+    #[serde(default)]
+    pub ensure_visible: Option<bool>, // 水印与背景对比不足时自动提高不透明度，保证可见
+    #[serde(default)]
+    pub ensure_visible_max: Option<f32>, // 自动提高不透明度的上限 (0-1]，默认 1.0
+    #[serde(default)]
+    pub auto_contrast: Option<bool>, // 按水印下方的局部背景亮度把水印逐区域绘制为黑色或白色（保留 alpha）
+    #[serde(default)]
+    pub blend_mode: Option<String>, // 混合模式："normal"（默认）、"multiply"（正片叠底，适合阴影层）、"screen"（滤色）或 "overlay"（叠加）
+    #[serde(default)]
+    pub z_index: Option<i32>, // 多水印叠加时的层级，数值小的先绘制（位于下层），相同层级保持数组顺序
+    #[serde(default)]
+    pub tile_ensure_full: Option<bool>, // 平铺时保证至少有一个完整可见的水印：若所有平铺水印都被边缘裁切，则在中心额外放置一个
+    #[serde(default)]
+    pub tile_angle: Option<f32>, // 平铺网格的旋转角度（度），非 0 时沿斜向网格平铺并延伸到图片外，边缘水印被裁切而不是跳过
+    #[serde(default)]
+    pub tile_spacing_x: Option<u32>, // 平铺网格的横向间距（像素，相邻水印左上角之间的距离），默认水印宽度 + |x_offset|
+    #[serde(default)]
+    pub tile_spacing_y: Option<u32>, // 平铺网格的纵向间距（像素），默认水印高度 + |y_offset|
+    #[serde(default)]
+    pub tile_margin_x: Option<u32>, // 相邻平铺水印之间的横向空隙（像素），设置后间距为水印宽度 + 空隙，x_offset 只决定起始位置
+    #[serde(default)]
+    pub tile_margin_y: Option<u32>, // 相邻平铺水印之间的纵向空隙（像素），设置后间距为水印高度 + 空隙
+    #[serde(default)]
+    pub tile_rows: Option<u32>, // 平铺的行数上限，设置后只绘制 行数×列数 的水印块，按 position 定位（默认居中）
+    #[serde(default)]
+    pub tile_cols: Option<u32>, // 平铺的列数上限，未设置的一方按图片尺寸铺满
+    #[serde(default)]
+    pub jitter: Option<f32>, // 平铺位置随机抖动幅度 (0-1)，每个水印在两个方向上最多偏移 jitter × 间距
+    #[serde(default)]
+    pub seed: Option<u64>, // 随机抖动（jitter、rotation_jitter）的种子，相同种子输出完全一致，默认 0
+    #[serde(default)]
+    pub rotation_jitter: Option<f32>, // 平铺时每个水印的随机旋转幅度（度），实际角度为 rotate ± rotation_jitter 内的随机值
+    #[serde(default)]
+    pub tile_alternate_image_data: Option<String>, // 平铺时与主水印按棋盘格交替使用的第二个水印（base64）
+    #[serde(default)]
+    pub opacity_map_data: Option<String>, // 不透明度热力图（base64 灰度图，缩放到底图尺寸），逐像素乘以水印的有效不透明度
+    #[serde(default)]
+    pub clip_polygon: Option<Vec<[i32; 2]>>, // 裁剪多边形（底图像素坐标），水印只作用于多边形内部，边缘抗锯齿
+    #[serde(default)]
+    pub fast: Option<bool>, // 快速预览模式：缩放和旋转全部使用最近邻采样，并跳过耗时的效果（如 SDF 文字缩放），适合交互拖动
+    #[serde(default)]
+    pub rotation_quality: Option<String>, // 旋转质量："fast"（默认，双线性插值）或 "high"（2 倍分辨率旋转后 Lanczos 缩小，斜边抗锯齿）；fast 模式下忽略
+    #[serde(default)]
+    pub resize_filter: Option<String>, // 缩放滤波器："nearest"、"triangle"、"catmullrom"、"gaussian"、"lanczos3"（默认），不区分大小写；fast 模式下忽略
+    #[serde(default)]
+    pub tile_variance_threshold: Option<f32>, // 平铺时按底图局部方差筛选位置（亮度归一化到 0-1）
+    #[serde(default)]
+    pub tile_variance_polarity: Option<String>, // "above"（只放在细节丰富区域，默认）或 "below"（只放在平坦区域）
     
     // 图片水印参数
     #[serde(default)]
@@ -34,27 +110,280 @@ pub struct WatermarkConfig {
     pub width: Option<u32>,
     #[serde(default)]
     pub height: Option<u32>,
+    #[serde(default)]
+    pub width_pct: Option<f32>, // 水印宽度占底图宽度的百分比 (0-100]，设置后取代 width
+    #[serde(default)]
+    pub height_pct: Option<f32>, // 水印高度占底图高度的百分比 (0-100]，设置后取代 height
+    #[serde(default)]
+    pub text: Option<String>, // 文字水印内容，未提供 image_data 时用内置字体直接渲染（需启用 text-render 特性），支持 \n 换行
+    #[serde(default)]
+    pub payload: Option<String>, // 不可见水印（type 为 invisible）嵌入的字符串，写入蓝色通道最低位，通过 extract_watermark 读取
+    #[serde(default)]
+    pub font_size: Option<f32>, // 原生渲染的字号（像素），默认 30
+    #[serde(default)]
+    pub font_color: Option<String>, // 原生渲染的文字颜色（十六进制），默认 "#FFFFFF"
+    #[serde(default)]
+    pub text_gradient: Option<GradientConfig>, // 文字水印的渐变填充：保留字形 alpha，按方向用渐变色重新着色
+    #[serde(default)]
+    pub label_background: Option<LabelBgConfig>, // 文字水印的标签底板：按文字实际范围加内边距绘制（圆角）背景，再叠加字形
+    #[serde(default)]
+    pub flip: Option<String>, // 翻转水印："horizontal"、"vertical" 或 "both"
+    #[serde(default)]
+    pub transform_order: Option<Vec<String>>, // 水印变换顺序，可选步骤 "resize"、"flip"、"rotate"，默认 resize → flip → rotate，未列出的步骤按默认顺序追加
+    #[serde(default)]
+    pub sdf_text: Option<bool>, // 文字水印使用 SDF（有向距离场）按 width/height 缩放（需启用 sdf-text 特性）
+    #[serde(default)]
+    pub watermark_key: Option<String>, // 通过 preload_watermark 预加载的水印名称，设置后忽略 image_data
+    #[serde(default)]
+    pub fallback_image_data: Option<String>, // image_data 解码失败时使用的备用水印（base64）
+    #[serde(skip)]
+    pub watermark_bytes: Option<Vec<u8>>, // 未经 base64 编码的水印图片字节（PNG、JPEG 等），由 add_watermark_raw 或原生调用方设置，优先于 watermark_key 和 image_data
+    #[serde(default)]
+    pub color_key: Option<[u8; 3]>, // 视为透明的背景色 [R, G, B]，用于没有 alpha 通道的水印（如 JPEG Logo）
+    #[serde(default)]
+    pub color_key_tolerance: Option<u8>, // 颜色键容差（各通道最大差值），默认 0
+    #[serde(default)]
+    pub mask: Option<String>, // 形状遮罩："circle"（内切圆/椭圆）或 "rounded"（圆角矩形），在缩放之后应用，边缘羽化抗锯齿
+    #[serde(default)]
+    pub corner_radius: Option<u32>, // mask 为 "rounded" 时的圆角半径（像素），默认短边的 1/8，超过短边一半时按短边一半处理
+    #[serde(default)]
+    pub corner_opacities: Option<[f32; 4]>, // 四角不透明度 [左上, 右上, 左下, 右下]，在水印内双线性插值
+    #[serde(default)]
+    pub grayscale: Option<bool>, // 去色：按亮度 (0.299R + 0.587G + 0.114B) 转为灰度，保留 alpha
+    #[serde(default)]
+    pub tint_color: Option<String>, // 着色（十六进制），把水印所有非透明像素的 RGB 替换为该颜色，保留 alpha；与 grayscale 同时设置时按亮度着色为单色
+    #[serde(default)]
+    pub gradient: Option<String>, // 不透明度渐隐："horizontal"（左到右）、"vertical"（上到下）、"radial"（中心到四角）或 "none"
+    #[serde(default)]
+    pub shadow: Option<bool>, // 投影：由水印的 alpha 生成阴影层，偏移（可模糊）后垫在水印下方，提高在复杂背景上的辨识度
+    #[serde(default)]
+    pub shadow_offset_x: Option<i32>, // 阴影水平偏移（像素，可为负），默认 4
+    #[serde(default)]
+    pub shadow_offset_y: Option<i32>, // 阴影垂直偏移（像素，可为负），默认 4
+    #[serde(default)]
+    pub shadow_blur: Option<f32>, // 阴影高斯模糊的 sigma（像素），默认 2.0，0 为硬边阴影
+    #[serde(default)]
+    pub shadow_color: Option<String>, // 阴影颜色（十六进制），默认 "#000000"
+    
+    // 输出参数
+    #[serde(default)]
+    pub source_name: Option<String>, // 源文件名，用于生成建议的下载文件名
+    #[serde(default)]
+    pub respect_exif_orientation: Option<bool>, // 按 JPEG 底图的 EXIF 方向旋转/翻转后再添加水印（手机竖拍照片），默认 true
+    #[serde(default)]
+    pub max_pixels: Option<u64>, // 底图像素数（宽 x 高）上限，超出时报错而不是继续处理，默认 50,000,000
+    #[serde(default)]
+    pub autocrop: Option<bool>, // 合成后按整张图片的 alpha 外接矩形裁掉透明边框
+    #[serde(default)]
+    pub force_rgb: Option<bool>, // 丢弃 alpha 通道（以背景色拍平），输出 RGB 图片
+    #[serde(default)]
+    pub background_color: Option<String>, // 拍平透明度时使用的背景色（十六进制，用于 JPEG 输出和 force_rgb），默认 "#FFFFFF"
+    #[serde(default)]
+    pub jpeg_chroma_subsampling: Option<String>, // JPEG 色度抽样："4:4:4"、"4:2:2"、"4:2:0"，默认使用编码器默认值
+    #[serde(default)]
+    pub premultiply_output: Option<bool>, // 输出前将 RGB 预乘 alpha（用于要求预乘 alpha 的 WebGL 纹理）
+    #[serde(default)]
+    pub tile_output: Option<[u32; 2]>, // 分块输出的块宽高，配合 add_watermark_tiles 将结果切成网格分别编码（用于深度缩放查看器）
+    #[serde(default)]
+    pub transparency_sweep: Option<Vec<f32>>, // 配合 add_watermark_sweep 以多个不透明度分别输出同一位置的水印，便于对比选择强度
+    #[serde(default)]
+    pub output_format: Option<String>, // 输出格式："png"、"jpeg"、"webp"（无损）、"gif"、"bmp"，默认沿用输入图片的格式
+    #[serde(default)]
+    pub quality: Option<u8>, // 有损输出（JPEG）的压缩质量 (0-100)，默认 85
+    #[serde(default)]
+    pub preserve_exif: Option<bool>, // JPEG 底图输出为 JPEG 时保留原图的 EXIF（方向重置为 1，像素已按方向摆正），默认 false
+    #[serde(default)]
+    pub animated: Option<bool>, // 动画 GIF 逐帧添加水印并输出动画 GIF，默认 false（只处理第一帧，动画丢失）
+    #[serde(default)]
+    pub frame_selector: Option<String>, // 动画中添加水印的帧："all"（默认）、"first"（只在第一帧）或 "every:N"（第 0、N、2N... 帧），其余帧原样保留
+    #[serde(default)]
+    pub output_formats: Option<Vec<String>>, // 配合 add_watermark_formats 一次输出多种格式，可选值同 output_format
+    #[serde(default)]
+    pub png_interlace: Option<bool>, // PNG 输出是否使用 Adam7 隔行扫描，默认不隔行（输入是否隔行不影响输出）
+    #[serde(default)]
+    pub max_output_bytes: Option<u32>, // PNG 输出的字节数上限，超出时（启用 quantize 特性）逐步减少调色板颜色数直到满足
+    #[serde(default)]
+    pub diff_output: Option<bool>, // 输出原图与水印结果的差异图（放大显示），用于检查水印强度
+    #[serde(default)]
+    pub diff_amplification: Option<f32>, // 差异放大倍数，默认 10
+    
+    // 边缘去晕参数：将半透明边缘像素向指定颜色轻度混合，避免在未知背景上出现黑边
+    #[serde(default)]
+    pub edge_matte_color: Option<String>, // 十六进制颜色，如 "#FFFFFF"
+    #[serde(default)]
+    pub edge_matte_strength: Option<f32>, // 混合强度 (0-1)，默认 0.5
+}
+
+// 渐变配置：两个或以上颜色沿方向均匀分布
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GradientConfig {
+    // 十六进制颜色，至少两个
+    pub colors: Vec<String>,
+    // 渐变方向："vertical"（从上到下，默认）或 "horizontal"（从左到右）
+    #[serde(default)]
+    pub direction: Option<String>,
+}
+
+// 标签底板配置：文字后方的纯色或渐变背景
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LabelBgConfig {
+    // 十六进制底色，默认 "#000000"
+    #[serde(default)]
+    pub color: Option<String>,
+    // 渐变底色，设置后取代 color
+    #[serde(default)]
+    pub gradient: Option<GradientConfig>,
+    // 文字范围四周的内边距（像素），默认 8
+    #[serde(default)]
+    pub padding: Option<u32>,
+    // 圆角半径（像素），默认 0，超过短边一半时按短边一半处理
+    #[serde(default)]
+    pub corner_radius: Option<u32>,
+    // 底板不透明度 (0-1)，默认 1.0
+    #[serde(default)]
+    pub opacity: Option<f32>,
+}
+
+// 自水印配置：以底图自身缩小、去色后的副本作为水印
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelfWatermarkConfig {
+    // 叠加不透明度 (0-1)，默认 0.1
+    #[serde(default)]
+    pub strength: Option<f32>,
+    // 副本相对底图的缩放比例 (0-1]，默认 0.25
+    #[serde(default)]
+    pub scale: Option<f32>,
+}
+
+// 边框水印配置：沿图片四周的边框带重复绘制水印
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BorderConfig {
+    // 边框带宽度（像素）
+    pub thickness: u32,
+    // 边框带底色（十六进制），不设置时保持原图
+    #[serde(default)]
+    pub color: Option<String>,
+    // 相邻水印之间的间距（像素），默认为边框宽度的一半
+    #[serde(default)]
+    pub spacing: Option<u32>,
 }
 
 impl Default for WatermarkConfig {
     fn default() -> Self {
         Self {
-            watermark_type: "text".to_string(),
+            watermark_type: WatermarkType::Text,
             transparency: Some(0.5),
             rotate: Some(0.0),
             x_offset: Some(10),
             y_offset: Some(10),
+            x_offset_pct: None,
+            y_offset_pct: None,
+            subpixel_offset: None,
+            overlay_sampling: None,
+            negative_offset_mode: None,
+            clamp_to_bounds: None,
+            position: None,
             tile: Some(false),
+            coverage_percent: None,
+            subject_tile: None,
+            border_watermark: None,
+            self_watermark: None,
+            ensure_visible: None,
+            ensure_visible_max: None,
+            auto_contrast: None,
+            blend_mode: None,
+            z_index: None,
+            tile_ensure_full: None,
+            tile_angle: None,
+            tile_spacing_x: None,
+            tile_spacing_y: None,
+            tile_margin_x: None,
+            tile_margin_y: None,
+            tile_rows: None,
+            tile_cols: None,
+            jitter: None,
+            seed: None,
+            rotation_jitter: None,
+            tile_alternate_image_data: None,
+            opacity_map_data: None,
+            clip_polygon: None,
+            fast: None,
+            rotation_quality: None,
+            resize_filter: None,
+            tile_variance_threshold: None,
+            tile_variance_polarity: None,
             image_data: None,
             width: None,
             height: None,
+            width_pct: None,
+            height_pct: None,
+            text: None,
+            payload: None,
+            font_size: None,
+            font_color: None,
+            text_gradient: None,
+            label_background: None,
+            flip: None,
+            transform_order: None,
+            sdf_text: None,
+            watermark_key: None,
+            fallback_image_data: None,
+            watermark_bytes: None,
+            color_key: None,
+            color_key_tolerance: None,
+            mask: None,
+            corner_radius: None,
+            corner_opacities: None,
+            grayscale: None,
+            tint_color: None,
+            gradient: None,
+            shadow: None,
+            shadow_offset_x: None,
+            shadow_offset_y: None,
+            shadow_blur: None,
+            shadow_color: None,
+            source_name: None,
+            respect_exif_orientation: None,
+            max_pixels: None,
+            autocrop: None,
+            force_rgb: None,
+            background_color: None,
+            jpeg_chroma_subsampling: None,
+            premultiply_output: None,
+            tile_output: None,
+            transparency_sweep: None,
+            output_format: None,
+            quality: None,
+            preserve_exif: None,
+            animated: None,
+            frame_selector: None,
+            output_formats: None,
+            png_interlace: None,
+            max_output_bytes: None,
+            diff_output: None,
+            diff_amplification: None,
+            edge_matte_color: None,
+            edge_matte_strength: None,
         }
     }
 }
 
+// 错误类别，JS 端可据此区分错误而不必解析 message
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    InvalidConfig = 0,     // 配置无法解析或参数不合法
+    DecodeFailed = 1,      // 底图或水印解码失败（如 base64 无效、图片数据损坏）
+    UnsupportedFormat = 2, // 无法识别的输入格式或不支持的输出格式
+    EncodeFailed = 3,      // 输出编码失败（含超出 max_output_bytes）
+    ProcessingFailed = 4,  // 其他处理错误
+}
+
 // 错误处理
 #[wasm_bindgen]
+#[derive(Debug)]
 pub struct WatermarkError {
+    code: ErrorCode,
     message: String,
 }
 
@@ -64,154 +393,1837 @@ impl WatermarkError {
     pub fn message(&self) -> String {
         self.message.clone()
     }
+    
+    #[wasm_bindgen(getter)]
+    pub fn error_code(&self) -> ErrorCode {
+        self.code
+    }
+    
+    // 让 JS 中的字符串拼接和 String(error) 得到错误信息
+    #[wasm_bindgen(js_name = toString)]
+    pub fn to_js_string(&self) -> String {
+        self.message.clone()
+    }
 }
 
-// 参数验证
-fn validate_config(config: &WatermarkConfig) -> Result<(), String> {
-    // 验证水印类型
-    if !matches!(config.watermark_type.as_str(), "text" | "image") {
-        return Err(format!("Invalid watermark type '{}'. Must be 'text' or 'image'", config.watermark_type));
+impl WatermarkError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self::with_code(ErrorCode::ProcessingFailed, message)
     }
     
-    // 验证透明度范围
-    if let Some(transparency) = config.transparency {
-        if transparency < 0.0 || transparency > 1.0 {
-            return Err(format!("Transparency must be between 0.0 and 1.0, got {}", transparency));
-        }
+    pub fn with_code(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
     }
     
-    // 验证旋转角度
-    if let Some(rotate) = config.rotate {
-        if rotate < -360.0 || rotate > 360.0 {
-            return Err(format!("Rotation angle must be between -360 and 360 degrees, got {}", rotate));
-        }
+    // 在错误信息前加上上下文，保留错误类别
+    fn context(self, context: impl std::fmt::Display) -> Self {
+        Self { message: format!("{}: {}", context, self.message), ..self }
     }
-    
-    // 验证图片数据
-    if config.image_data.is_none() {
-        return Err("image_data parameter is required".to_string());
+}
+
+// 内部函数的 String 错误归为 ProcessingFailed；需要区分类别的路径显式使用 with_code
+impl From<String> for WatermarkError {
+    fn from(message: String) -> Self {
+        Self::new(message)
     }
-    
-    // 验证尺寸参数
-    if let Some(width) = config.width {
-        if width == 0 {
-            return Err("Width must be greater than 0".to_string());
-        }
+}
+
+impl From<&str> for WatermarkError {
+    fn from(message: &str) -> Self {
+        Self::new(message)
     }
-    
-    if let Some(height) = config.height {
-        if height == 0 {
-            return Err("Height must be greater than 0".to_string());
-        }
+}
+
+impl From<WatermarkError> for String {
+    fn from(error: WatermarkError) -> Self {
+        error.message
     }
-    
-    Ok(())
 }
 
-// 解码base64图片数据
-fn decode_base64_image(image_data: &str) -> Result<Vec<u8>, String> {
-    // web_sys::console::log_1(&format!("开始解码base64图片数据，原始数据长度: {}", image_data.len()).into());
+impl std::fmt::Display for WatermarkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for WatermarkError {}
+
+// 水印处理结果（包含编码后的字节和元信息）
+#[wasm_bindgen]
+pub struct WatermarkResult {
+    bytes: Vec<u8>,
+    width: u32,
+    height: u32,
+    format: String,
+    suggested_filename: String,
+    placements: Vec<WatermarkPlacement>,
+}
+
+#[wasm_bindgen]
+impl WatermarkResult {
+    #[wasm_bindgen(getter)]
+    pub fn bytes(&self) -> Vec<u8> {
+        self.bytes.clone()
+    }
     
-    let base64_data = image_data.trim_start_matches("data:image/");
-    let base64_data = base64_data.split(',').nth(1).unwrap_or(image_data);
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
     
-    // web_sys::console::log_1(&format!("处理后base64数据长度: {}", base64_data.len()).into());
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
     
-    if base64_data.is_empty() {
-        return Err("Empty base64 data".to_string());
+    #[wasm_bindgen(getter)]
+    pub fn format(&self) -> String {
+        self.format.clone()
     }
     
-    let result = STANDARD.decode(base64_data)
-        .map_err(|e| format!("Failed to decode base64: {}", e));
+    #[wasm_bindgen(getter)]
+    pub fn suggested_filename(&self) -> String {
+        self.suggested_filename.clone()
+    }
     
-    // match &result {
-    //     Ok(data) => {
-    //         web_sys::console::log_1(&format!("base64解码成功，解码后数据长度: {}", data.len()).into());
-    //     }
-    //     Err(e) => {
-    //         web_sys::console::log_1(&format!("base64解码失败: {}", e).into());
-    //     }
-    // }
+    // 每个叠加的水印在底图上的矩形区域：[{ x, y, width, height }, ...]
+    #[wasm_bindgen(getter)]
+    pub fn placements(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.placements).unwrap_or(JsValue::UNDEFINED)
+    }
     
-    result
+    // 所有水印区域的外接矩形（平铺时即网格范围），没有叠加任何水印时为 undefined
+    #[wasm_bindgen(getter)]
+    pub fn placement_bounds(&self) -> JsValue {
+        placement_bounds(&self.placements)
+            .and_then(|bounds| serde_wasm_bindgen::to_value(&bounds).ok())
+            .unwrap_or(JsValue::UNDEFINED)
+    }
 }
 
-// 加载并调整水印图片
-fn load_and_prepare_watermark(
-    config: &WatermarkConfig,
-) -> Result<RgbaImage, String> {
-    // web_sys::console::log_1(&format!("开始加载并准备水印图片").into());
-    
-    let image_data = config.image_data.as_ref()
-        .ok_or("image_data parameter is required")?;
-    
-    // web_sys::console::log_1(&format!("水印配置中的image_data存在，长度: {}", image_data.len()).into());
+// 水印在底图上的叠加区域（已裁切到底图范围内），坐标基于后处理（autocrop 等）之前的底图
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+struct WatermarkPlacement {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl WatermarkPlacement {
+    // 左上角位于 (x, y) 的水印与底图的交集，完全落在底图之外时返回 None
+    fn clipped(x: i64, y: i64, (width, height): (u32, u32), img_width: u32, img_height: u32) -> Option<Self> {
+        let (left, top) = (x.max(0), y.max(0));
+        let right = (x + width as i64).min(img_width as i64);
+        let bottom = (y + height as i64).min(img_height as i64);
+        (left < right && top < bottom).then(|| WatermarkPlacement {
+            x: left as u32,
+            y: top as u32,
+            width: (right - left) as u32,
+            height: (bottom - top) as u32,
+        })
+    }
+}
+
+// 多个水印区域的外接矩形
+fn placement_bounds(placements: &[WatermarkPlacement]) -> Option<WatermarkPlacement> {
+    let left = placements.iter().map(|p| p.x).min()?;
+    let top = placements.iter().map(|p| p.y).min()?;
+    let right = placements.iter().map(|p| p.x + p.width).max()?;
+    let bottom = placements.iter().map(|p| p.y + p.height).max()?;
+    Some(WatermarkPlacement { x: left, y: top, width: right - left, height: bottom - top })
+}
+
+// 分块输出中的单个块：网格行列号、在完整图片中的位置和尺寸，以及编码后的 PNG 数据
+#[wasm_bindgen]
+pub struct WatermarkTile {
+    col: u32,
+    row: u32,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    bytes: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl WatermarkTile {
+    #[wasm_bindgen(getter)]
+    pub fn col(&self) -> u32 {
+        self.col
+    }
     
-    // 解码base64图片数据
-    let image_bytes = decode_base64_image(image_data)?;
+    #[wasm_bindgen(getter)]
+    pub fn row(&self) -> u32 {
+        self.row
+    }
     
-    // web_sys::console::log_1(&format!("开始从内存加载图片，数据长度: {}", image_bytes.len()).into());
+    #[wasm_bindgen(getter)]
+    pub fn x(&self) -> u32 {
+        self.x
+    }
     
-    // 加载图片
-    let mut watermark_img = image::load_from_memory(&image_bytes)
-        .map_err(|e| {
-            // web_sys::console::log_1(&format!("图片加载失败: {}", e).into());
-            format!("Failed to load watermark image: {}", e)
-        })?;
+    #[wasm_bindgen(getter)]
+    pub fn y(&self) -> u32 {
+        self.y
+    }
     
-    // 调整水印图片大小（仅对图片水印有效，文字水印不调整大小）
-    if config.watermark_type == "image" {
-        if let Some(width) = config.width {
-            let height = config.height.unwrap_or((watermark_img.height() * width) / watermark_img.width());
-            if watermark_img.width() == 0 {
-                return Err("Watermark image has zero width".to_string());
-            }
-            // web_sys::console::log_1(&format!("调整水印图片大小: {}x{} -> {}x{}",
-            //     watermark_img.width(), watermark_img.height(), width, height).into());
-            watermark_img = watermark_img.resize(width, height, image::imageops::FilterType::Lanczos3);
-        }
-    } else {
-        // web_sys::console::log_1(&format!("文字水印不调整大小，保持原始尺寸: {}x{}",
-        //     watermark_img.width(), watermark_img.height()).into());
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.width
     }
     
-    // 旋转图片
-    let rotate = config.rotate.unwrap_or(0.0);
-    watermark_img = rotate_image(&watermark_img, rotate);
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
     
-    Ok(watermark_img.to_rgba8())
+    #[wasm_bindgen(getter)]
+    pub fn bytes(&self) -> Vec<u8> {
+        self.bytes.clone()
+    }
 }
 
-// 双线性插值辅助函数
-fn bilinear_interpolate(
-    img_data: &[u8],
-    width: usize,
-    height: usize,
-    x: f32,
-    y: f32,
-) -> [u8; 4] {
-    let x0 = x.floor() as i32;
-    let y0 = y.floor() as i32;
-    let x1 = x0 + 1;
-    let y1 = y0 + 1;
+// 根据源文件名和输出格式生成建议的下载文件名，如 photo.jpg -> photo_watermarked.png
+fn suggested_filename(source_name: Option<&str>, format: &str) -> String {
+    let stem = source_name
+        .map(|name| name.rsplit(['/', '\\']).next().unwrap_or(name))
+        .map(|name| match name.rfind('.') {
+            Some(idx) if idx > 0 => &name[..idx],
+            _ => name,
+        })
+        .map(str::trim)
+        .filter(|stem| !stem.is_empty());
     
-    let fx = x - x.floor();
-    let fy = y - y.floor();
-    
-    // 边界检查
-    let get_pixel = |xi: i32, yi: i32| -> [u8; 4] {
-        if xi >= 0 && xi < width as i32 && yi >= 0 && yi < height as i32 {
-            let idx = (yi as usize * width + xi as usize) * 4;
-            [img_data[idx], img_data[idx + 1], img_data[idx + 2], img_data[idx + 3]]
-        } else {
-            [0, 0, 0, 0]
+    match stem {
+        Some(stem) => format!("{}_watermarked.{}", stem, format),
+        None => format!("watermarked.{}", format),
+    }
+}
+
+// JPEG 色度抽样方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChromaSubsampling {
+    Yuv444,
+    Yuv422,
+    Yuv420,
+}
+
+// 动画中需要添加水印的帧
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameSelector {
+    All,
+    First,
+    Every(usize),
+}
+
+impl FrameSelector {
+    fn parse(value: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid frame_selector '{}'. Must be 'all', 'first' or 'every:N' with N >= 1", value);
+        match value {
+            "all" => Ok(Self::All),
+            "first" => Ok(Self::First),
+            _ => match value.strip_prefix("every:").map(str::parse::<usize>) {
+                Some(Ok(step)) if step >= 1 => Ok(Self::Every(step)),
+                _ => Err(invalid()),
+            },
         }
-    };
+    }
     
-    let p00 = get_pixel(x0, y0);
-    let p10 = get_pixel(x1, y0);
-    let p01 = get_pixel(x0, y1);
-    let p11 = get_pixel(x1, y1);
+    // 第 index 帧（从 0 开始）是否添加水印
+    fn selects(self, index: usize) -> bool {
+        match self {
+            Self::All => true,
+            Self::First => index == 0,
+            Self::Every(step) => index.is_multiple_of(step),
+        }
+    }
+}
+
+impl ChromaSubsampling {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "4:4:4" => Ok(Self::Yuv444),
+            "4:2:2" => Ok(Self::Yuv422),
+            "4:2:0" => Ok(Self::Yuv420),
+            _ => Err(format!(
+                "Invalid jpeg_chroma_subsampling '{}'. Must be '4:4:4', '4:2:2' or '4:2:0'",
+                value
+            )),
+        }
+    }
+    
+    fn sampling_factor(self) -> jpeg_encoder::SamplingFactor {
+        match self {
+            Self::Yuv444 => jpeg_encoder::SamplingFactor::R_4_4_4,
+            Self::Yuv422 => jpeg_encoder::SamplingFactor::R_4_2_2,
+            Self::Yuv420 => jpeg_encoder::SamplingFactor::R_4_2_0,
+        }
+    }
+}
+
+// 水印类型：序列化为小写字符串，反序列化时不区分大小写（"Text"、"IMAGE" 同样有效）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatermarkType {
+    Text,
+    Image,
+    Invisible,
+}
+
+impl WatermarkType {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value.to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "image" => Ok(Self::Image),
+            "invisible" => Ok(Self::Invisible),
+            _ => Err(format!("Invalid watermark type '{}'. Must be 'text', 'image' or 'invisible'", value)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for WatermarkType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        Self::parse(&value).map_err(serde::de::Error::custom)
+    }
+}
+
+// 水印混合模式：先按模式计算混合色，再按 alpha 与底图混合
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BlendMode {
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+}
+
+impl BlendMode {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "normal" => Ok(Self::Normal),
+            "multiply" => Ok(Self::Multiply),
+            "screen" => Ok(Self::Screen),
+            "overlay" => Ok(Self::Overlay),
+            _ => Err(format!("Invalid blend_mode '{}'. Must be 'normal', 'multiply', 'screen' or 'overlay'", value)),
+        }
+    }
+    
+    fn from_config(config: &WatermarkConfig) -> Result<Self, String> {
+        config.blend_mode.as_deref().map_or(Ok(Self::Normal), Self::parse)
+    }
+    
+    // 单通道混合色（0-255）
+    fn blend(self, target: f32, overlay: f32) -> f32 {
+        match self {
+            Self::Normal => overlay,
+            Self::Multiply => target * overlay / 255.0,
+            Self::Screen => 255.0 - (255.0 - target) * (255.0 - overlay) / 255.0,
+            // 底图暗部按正片叠底、亮部按滤色，保留底图的明暗对比
+            Self::Overlay if target < 128.0 => 2.0 * target * overlay / 255.0,
+            Self::Overlay => 255.0 - 2.0 * (255.0 - target) * (255.0 - overlay) / 255.0,
+        }
+    }
+}
+
+// 未设置 quality 时的 JPEG 压缩质量
+const DEFAULT_JPEG_QUALITY: u8 = 85;
+
+// 支持的输出格式
+const OUTPUT_FORMATS: [&str; 5] = ["png", "jpeg", "webp", "gif", "bmp"];
+
+// 支持的锚点位置
+const POSITIONS: [&str; 9] = [
+    "top-left", "top", "top-right",
+    "left", "center", "right",
+    "bottom-left", "bottom", "bottom-right",
+];
+
+// 验证渐变配置，name 用于错误信息中的字段名
+fn validate_gradient(gradient: &GradientConfig, name: &str) -> Result<(), String> {
+    if gradient.colors.len() < 2 {
+        return Err(format!("{}.colors must contain at least 2 colors", name));
+    }
+    for color in &gradient.colors {
+        parse_hex_color(color)?;
+    }
+    if let Some(direction) = &gradient.direction {
+        if !matches!(direction.as_str(), "vertical" | "horizontal") {
+            return Err(format!("Invalid {}.direction '{}'. Must be 'vertical' or 'horizontal'", name, direction));
+        }
+    }
+    Ok(())
+}
+
+// 参数验证
+fn validate_config(config: &WatermarkConfig) -> Result<(), String> {
+    // 验证浮点参数必须为有限值（来自 JS 的 NaN/Infinity 会绕过后续的范围比较）
+    let float_fields = [
+        ("transparency", config.transparency),
+        ("font_size", config.font_size),
+        ("x_offset_pct", config.x_offset_pct),
+        ("y_offset_pct", config.y_offset_pct),
+        ("width_pct", config.width_pct),
+        ("height_pct", config.height_pct),
+        ("rotate", config.rotate),
+        ("edge_matte_strength", config.edge_matte_strength),
+        ("coverage_percent", config.coverage_percent),
+        ("tile_angle", config.tile_angle),
+        ("tile_variance_threshold", config.tile_variance_threshold),
+        ("diff_amplification", config.diff_amplification),
+        ("ensure_visible_max", config.ensure_visible_max),
+        ("shadow_blur", config.shadow_blur),
+        ("jitter", config.jitter),
+        ("rotation_jitter", config.rotation_jitter),
+    ];
+    for (name, value) in float_fields {
+        if let Some(value) = value {
+            if !value.is_finite() {
+                return Err(format!("{} must be a finite number, got {}", name, value));
+            }
+        }
+    }
+    
+    if let Some(corners) = config.corner_opacities {
+        if let Some(value) = corners.iter().find(|v| !v.is_finite()) {
+            return Err(format!("corner_opacities must be finite numbers, got {}", value));
+        }
+        if let Some(value) = corners.iter().find(|v| !(0.0..=1.0).contains(*v)) {
+            return Err(format!("corner_opacities must be between 0.0 and 1.0, got {}", value));
+        }
+    }
+    
+    // 验证透明度范围
+    if let Some(transparency) = config.transparency {
+        if !(0.0..=1.0).contains(&transparency) {
+            return Err(format!("Transparency must be between 0.0 and 1.0, got {}", transparency));
+        }
+    }
+    
+    // 验证旋转角度
+    if let Some(rotate) = config.rotate {
+        if !(-360.0..=360.0).contains(&rotate) {
+            return Err(format!("Rotation angle must be between -360 and 360 degrees, got {}", rotate));
+        }
+    }
+    
+    // 验证锚点位置
+    if let Some(position) = &config.position {
+        if !POSITIONS.contains(&position.as_str()) {
+            return Err(format!(
+                "Invalid position '{}'. Must be one of: {}",
+                position,
+                POSITIONS.join(", ")
+            ));
+        }
+    }
+    
+    // 验证覆盖率
+    if let Some(coverage) = config.coverage_percent {
+        if coverage <= 0.0 || coverage > 100.0 {
+            return Err(format!("coverage_percent must be in (0, 100], got {}", coverage));
+        }
+    }
+    
+    // 验证平铺网格参数：显式间距与 coverage_percent 都决定间距，不能同时使用
+    for (name, value) in [("tile_spacing_x", config.tile_spacing_x), ("tile_spacing_y", config.tile_spacing_y)] {
+        if value == Some(0) {
+            return Err(format!("{} must be greater than 0", name));
+        }
+        if value.is_some() && config.coverage_percent.is_some() {
+            return Err(format!("{} cannot be combined with coverage_percent", name));
+        }
+    }
+    // 空隙同样决定间距，不能与显式间距或 coverage_percent 同时使用
+    for (name, margin, spacing_name, spacing) in [
+        ("tile_margin_x", config.tile_margin_x, "tile_spacing_x", config.tile_spacing_x),
+        ("tile_margin_y", config.tile_margin_y, "tile_spacing_y", config.tile_spacing_y),
+    ] {
+        if margin.is_none() {
+            continue;
+        }
+        if spacing.is_some() {
+            return Err(format!("{} cannot be combined with {}", name, spacing_name));
+        }
+        if config.coverage_percent.is_some() {
+            return Err(format!("{} cannot be combined with coverage_percent", name));
+        }
+    }
+    
+    if let Some(jitter) = config.jitter {
+        if !(0.0..=1.0).contains(&jitter) {
+            return Err(format!("jitter must be between 0.0 and 1.0, got {}", jitter));
+        }
+    }
+    if let Some(rotation_jitter) = config.rotation_jitter {
+        if rotation_jitter < 0.0 {
+            return Err(format!("rotation_jitter must be non-negative, got {}", rotation_jitter));
+        }
+        // 逐个旋转时其余变换只执行一次，因此旋转必须是最后一个变换步骤
+        if rotation_jitter > 0.0 {
+            if config.self_watermark.is_some() {
+                return Err("rotation_jitter cannot be combined with self_watermark".to_string());
+            }
+            if transform_order(config)?.last() != Some(&"rotate") {
+                return Err("transform_order must end with 'rotate' when rotation_jitter is set".to_string());
+            }
+        }
+    }
+    
+    // 验证平铺行列数：水印块按矩形网格定位，不支持旋转网格
+    for (name, value) in [("tile_rows", config.tile_rows), ("tile_cols", config.tile_cols)] {
+        if value == Some(0) {
+            return Err(format!("{} must be greater than 0", name));
+        }
+        if let Some(count) = value.filter(|&count| count > MAX_TILE_BLOCKS) {
+            return Err(format!("{} must be at most {}, got {}", name, MAX_TILE_BLOCKS, count));
+        }
+        if value.is_some() && config.tile_angle.is_some_and(|angle| angle != 0.0) {
+            return Err(format!("{} cannot be combined with tile_angle", name));
+        }
+    }
+    
+    // 验证边框水印参数
+    if let Some(border) = &config.border_watermark {
+        if border.thickness == 0 {
+            return Err("border_watermark.thickness must be greater than 0".to_string());
+        }
+        if let Some(color) = &border.color {
+            parse_hex_color(color)?;
+        }
+        if config.subject_tile == Some(true) {
+            return Err("subject_tile cannot be combined with border_watermark".to_string());
+        }
+        if config.auto_contrast == Some(true) {
+            return Err("auto_contrast cannot be combined with border_watermark".to_string());
+        }
+    }
+    
+    // 验证局部方差筛选参数
+    if let Some(threshold) = config.tile_variance_threshold {
+        if threshold < 0.0 {
+            return Err(format!("tile_variance_threshold must be non-negative, got {}", threshold));
+        }
+    }
+    
+    if let Some(polarity) = &config.tile_variance_polarity {
+        if !matches!(polarity.as_str(), "above" | "below") {
+            return Err(format!("Invalid tile_variance_polarity '{}'. Must be 'above' or 'below'", polarity));
+        }
+    }
+    
+    if let Some(gradient) = &config.text_gradient {
+        if config.watermark_type != WatermarkType::Text {
+            return Err("text_gradient is only supported for text watermarks".to_string());
+        }
+        validate_gradient(gradient, "text_gradient")?;
+    }
+    
+    if let Some(label) = &config.label_background {
+        if config.watermark_type != WatermarkType::Text {
+            return Err("label_background is only supported for text watermarks".to_string());
+        }
+        if let Some(color) = &label.color {
+            parse_hex_color(color)?;
+        }
+        if let Some(gradient) = &label.gradient {
+            validate_gradient(gradient, "label_background.gradient")?;
+        }
+        if let Some(opacity) = label.opacity {
+            if !opacity.is_finite() || !(0.0..=1.0).contains(&opacity) {
+                return Err(format!("label_background.opacity must be between 0.0 and 1.0, got {}", opacity));
+            }
+        }
+    }
+    
+    if let Some(self_watermark) = &config.self_watermark {
+        if let Some(strength) = self_watermark.strength {
+            if !strength.is_finite() || !(0.0..=1.0).contains(&strength) {
+                return Err(format!("self_watermark.strength must be between 0.0 and 1.0, got {}", strength));
+            }
+        }
+        if let Some(scale) = self_watermark.scale {
+            if !scale.is_finite() || scale <= 0.0 || scale > 1.0 {
+                return Err(format!("self_watermark.scale must be in (0.0, 1.0], got {}", scale));
+            }
+        }
+    }
+    
+    // 验证百分比参数：偏移可为负（从右/下边缘计算），尺寸必须为正且不超过底图
+    for (name, value) in [("x_offset_pct", config.x_offset_pct), ("y_offset_pct", config.y_offset_pct)] {
+        if let Some(pct) = value {
+            if !(-100.0..=100.0).contains(&pct) {
+                return Err(format!("{} must be between -100 and 100, got {}", name, pct));
+            }
+        }
+    }
+    for (name, value) in [("width_pct", config.width_pct), ("height_pct", config.height_pct)] {
+        if let Some(pct) = value {
+            if pct <= 0.0 || pct > 100.0 {
+                return Err(format!("{} must be in (0, 100], got {}", name, pct));
+            }
+        }
+    }
+    
+    // 验证图片数据：使用预加载水印或自水印时可以省略；文字水印提供 text 时原生渲染，也不需要 image_data
+    let has_image_source = config.image_data.is_some() || config.watermark_bytes.is_some()
+        || config.watermark_key.is_some() || config.self_watermark.is_some();
+    match config.watermark_type {
+        WatermarkType::Image if !has_image_source => {
+            return Err("Image watermark requires image_data or watermark_key parameter".to_string());
+        }
+        WatermarkType::Text if !has_image_source && config.text.is_none() => {
+            return Err("Text watermark requires image_data (rendered by client), watermark_key or text parameter".to_string());
+        }
+        WatermarkType::Invisible => validate_invisible_config(config)?,
+        _ => {}
+    }
+    if config.payload.is_some() && config.watermark_type != WatermarkType::Invisible {
+        return Err("payload is only supported for invisible watermarks".to_string());
+    }
+    
+    // 原生文字渲染需要编译时启用 text-render 特性
+    if renders_text_natively(config) {
+        #[cfg(not(feature = "text-render"))]
+        return Err("Rendering text requires the 'text-render' feature to be enabled; pass client-rendered image_data instead".to_string());
+        #[cfg(feature = "text-render")]
+        {
+            if let Some(size) = config.font_size {
+                if size <= 0.0 {
+                    return Err(format!("font_size must be greater than 0, got {}", size));
+                }
+            }
+            if let Some(color) = &config.font_color {
+                parse_hex_color(color)?;
+            }
+        }
+    }
+    
+    if let Some([tile_width, tile_height]) = config.tile_output {
+        if tile_width == 0 || tile_height == 0 {
+            return Err("tile_output width and height must be greater than 0".to_string());
+        }
+    }
+    
+    if let Some(levels) = &config.transparency_sweep {
+        if let Some(level) = levels.iter().find(|level| !level.is_finite() || !(0.0..=1.0).contains(*level)) {
+            return Err(format!("transparency_sweep values must be between 0.0 and 1.0, got {}", level));
+        }
+    }
+    
+    if let Some(quality) = config.quality {
+        if quality > 100 {
+            return Err(format!("quality must be between 0 and 100, got {}", quality));
+        }
+    }
+    
+    if let Some(format) = &config.output_format {
+        if !OUTPUT_FORMATS.contains(&format.as_str()) {
+            return Err(format!("Unsupported output format '{}'. Must be one of: {}", format, OUTPUT_FORMATS.join(", ")));
+        }
+    }
+    
+    if let Some(formats) = &config.output_formats {
+        if let Some(format) = formats.iter().find(|f| !OUTPUT_FORMATS.contains(&f.as_str())) {
+            return Err(format!("Unsupported output format '{}'. Must be one of: {}", format, OUTPUT_FORMATS.join(", ")));
+        }
+    }
+    
+    // 动画逐帧输出，帧尺寸必须一致且只能编码为 GIF
+    if config.animated.unwrap_or(false) {
+        if config.output_format.as_deref().is_some_and(|format| format != "gif") {
+            return Err("animated requires GIF output; remove output_format or set it to 'gif'".to_string());
+        }
+        if config.autocrop.unwrap_or(false) {
+            return Err("animated cannot be combined with autocrop".to_string());
+        }
+    }
+    if let Some(selector) = &config.frame_selector {
+        if !config.animated.unwrap_or(false) {
+            return Err("frame_selector requires animated to be true".to_string());
+        }
+        FrameSelector::parse(selector)?;
+    }
+    
+    if config.max_pixels == Some(0) {
+        return Err("max_pixels must be greater than 0".to_string());
+    }
+    
+    if config.max_output_bytes == Some(0) {
+        return Err("max_output_bytes must be greater than 0".to_string());
+    }
+    
+    if let Some(max) = config.ensure_visible_max {
+        if max <= 0.0 || max > 1.0 {
+            return Err(format!("ensure_visible_max must be in (0.0, 1.0], got {}", max));
+        }
+    }
+    
+    if let Some(polygon) = &config.clip_polygon {
+        if polygon.len() < 3 {
+            return Err("clip_polygon must contain at least 3 points".to_string());
+        }
+    }
+    
+    // 验证尺寸参数
+    if let Some(width) = config.width {
+        if width == 0 {
+            return Err("Width must be greater than 0".to_string());
+        }
+    }
+    
+    if let Some(height) = config.height {
+        if height == 0 {
+            return Err("Height must be greater than 0".to_string());
+        }
+    }
+    
+    // SDF 文字缩放需要编译时启用 sdf-text 特性
+    #[cfg(not(feature = "sdf-text"))]
+    if config.sdf_text == Some(true) {
+        return Err("sdf_text requires the 'sdf-text' feature to be enabled".to_string());
+    }
+    
+    if let Some(subsampling) = &config.jpeg_chroma_subsampling {
+        ChromaSubsampling::parse(subsampling)?;
+    }
+    
+    BlendMode::from_config(config)?;
+    
+    if let Some(flip) = &config.flip {
+        if !matches!(flip.as_str(), "horizontal" | "vertical" | "both") {
+            return Err(format!("Invalid flip '{}'. Must be 'horizontal', 'vertical' or 'both'", flip));
+        }
+    }
+    transform_order(config)?;
+    
+    if let Some(mask) = &config.mask {
+        if !matches!(mask.as_str(), "circle" | "rounded") {
+            return Err(format!("Invalid mask '{}'. Must be 'circle' or 'rounded'", mask));
+        }
+    }
+    if config.corner_radius.is_some() && config.mask.as_deref() != Some("rounded") {
+        return Err("corner_radius requires mask 'rounded'".to_string());
+    }
+    
+    if let Some(gradient) = &config.gradient {
+        if !matches!(gradient.as_str(), "horizontal" | "vertical" | "radial" | "none") {
+            return Err(format!("Invalid gradient '{}'. Must be 'horizontal', 'vertical', 'radial' or 'none'", gradient));
+        }
+    }
+    
+    if let Some(offset) = config.subpixel_offset {
+        if let Some(value) = offset.iter().find(|v| !v.is_finite() || !(0.0..1.0).contains(*v)) {
+            return Err(format!("subpixel_offset values must be in [0.0, 1.0), got {}", value));
+        }
+    }
+    
+    if let Some(quality) = &config.rotation_quality {
+        if !matches!(quality.as_str(), "fast" | "high") {
+            return Err(format!("Invalid rotation_quality '{}'. Must be 'fast' or 'high'", quality));
+        }
+    }
+    
+    if let Some(filter) = &config.resize_filter {
+        parse_resize_filter(filter)?;
+    }
+    
+    if let Some(sampling) = &config.overlay_sampling {
+        if !matches!(sampling.as_str(), "bilinear" | "nearest") {
+            return Err(format!("Invalid overlay_sampling '{}'. Must be 'bilinear' or 'nearest'", sampling));
+        }
+    }
+    
+    if let Some(mode) = &config.negative_offset_mode {
+        if !matches!(mode.as_str(), "start" | "css") {
+            return Err(format!("Invalid negative_offset_mode '{}'. Must be 'start' or 'css'", mode));
+        }
+    }
+    
+    if let Some(amplification) = config.diff_amplification {
+        if amplification <= 0.0 {
+            return Err(format!("diff_amplification must be greater than 0, got {}", amplification));
+        }
+    }
+    
+    if let Some(color) = &config.tint_color {
+        parse_hex_color(color)?;
+    }
+    
+    // 验证投影参数
+    // 模糊边距为 3 sigma，与偏移一起扩展水印画布，需要限制范围
+    if let Some(blur) = config.shadow_blur {
+        if !(0.0..=MAX_SHADOW_BLUR).contains(&blur) {
+            return Err(format!("shadow_blur must be between 0 and {}, got {}", MAX_SHADOW_BLUR, blur));
+        }
+    }
+    for (name, value) in [("shadow_offset_x", config.shadow_offset_x), ("shadow_offset_y", config.shadow_offset_y)] {
+        if let Some(offset) = value.filter(|offset| offset.unsigned_abs() > MAX_SHADOW_OFFSET) {
+            return Err(format!("{} must be between -{} and {}, got {}", name, MAX_SHADOW_OFFSET, MAX_SHADOW_OFFSET, offset));
+        }
+    }
+    if let Some(color) = &config.shadow_color {
+        parse_hex_color(color)?;
+    }
+    
+    if let Some(color) = &config.background_color {
+        parse_hex_color(color)?;
+    }
+    
+    // 验证边缘去晕参数
+    if let Some(color) = &config.edge_matte_color {
+        parse_hex_color(color)?;
+    }
+    
+    if let Some(strength) = config.edge_matte_strength {
+        if !(0.0..=1.0).contains(&strength) {
+            return Err(format!("Edge matte strength must be between 0.0 and 1.0, got {}", strength));
+        }
+    }
+    
+    Ok(())
+}
+
+// 拍平透明度时使用的背景色，默认白色
+fn flatten_background(config: &WatermarkConfig) -> Result<[u8; 3], String> {
+    config.background_color.as_deref().map_or(Ok([255, 255, 255]), parse_hex_color)
+}
+
+// 解析十六进制颜色（支持 #RRGGBB 和 #RGB，'#' 可省略）
+fn parse_hex_color(color: &str) -> Result<[u8; 3], String> {
+    let hex = color.trim().trim_start_matches('#');
+    let invalid = || format!("Invalid hex color '{}'. Expected #RRGGBB or #RGB", color);
+    
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    
+    match hex.len() {
+        6 => {
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+            Ok([channel(0)?, channel(2)?, channel(4)?])
+        }
+        3 => {
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16)
+                .map(|v| v * 17)
+                .map_err(|_| invalid());
+            Ok([channel(0)?, channel(1)?, channel(2)?])
+        }
+        _ => Err(invalid()),
+    }
+}
+
+// 日志级别：0=关闭（默认），1=错误与警告，2=调试
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(0);
+const LOG_ERROR: u8 = 1;
+const LOG_DEBUG: u8 = 2;
+
+// 按当前日志级别输出，消息在需要输出时才格式化（关闭时不产生任何开销）
+fn log_at(level: u8, message: impl FnOnce() -> String) {
+    if LOG_LEVEL.load(Ordering::Relaxed) < level {
+        return;
+    }
+    let message = message();
+    #[cfg(target_arch = "wasm32")]
+    match level {
+        LOG_ERROR => web_sys::console::warn_1(&message.into()),
+        _ => web_sys::console::log_1(&message.into()),
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    eprintln!("{}", message);
+}
+
+// WASM导出函数：设置日志级别（0=关闭，1=错误与警告，2=调试），超过 2 按 2 处理
+#[wasm_bindgen]
+pub fn set_log_level(level: u8) {
+    LOG_LEVEL.store(level.min(LOG_DEBUG), Ordering::Relaxed);
+}
+
+// 解码base64图片数据
+fn decode_base64_image(image_data: &str) -> Result<Vec<u8>, String> {
+    log_at(LOG_DEBUG, || format!("开始解码base64图片数据，原始数据长度: {}", image_data.len()));
+    
+    let base64_data = image_data.trim_start_matches("data:image/");
+    let base64_data = base64_data.split(',').nth(1).unwrap_or(image_data);
+    
+    log_at(LOG_DEBUG, || format!("处理后base64数据长度: {}", base64_data.len()));
+    
+    if base64_data.is_empty() {
+        return Err("Empty base64 data".to_string());
+    }
+    
+    let result = STANDARD.decode(base64_data)
+        .map_err(|e| format!("Failed to decode base64: {}", e));
+    
+    match &result {
+        Ok(data) => log_at(LOG_DEBUG, || format!("base64解码成功，解码后数据长度: {}", data.len())),
+        Err(e) => log_at(LOG_ERROR, || format!("base64解码失败: {}", e)),
+    }
+    
+    result
+}
+
+// 预加载的水印注册表（作用域为当前 WASM 实例），键为调用方指定的名称
+thread_local! {
+    static WATERMARK_REGISTRY: RefCell<HashMap<String, DynamicImage>> = RefCell::new(HashMap::new());
+}
+
+// 解码 base64 水印数据并加载为图片
+fn decode_watermark_image(image_data: &str) -> Result<DynamicImage, WatermarkError> {
+    // 解码base64图片数据
+    let image_bytes = decode_base64_image(image_data)
+        .map_err(|e| WatermarkError::with_code(ErrorCode::DecodeFailed, e))?;
+    
+    decode_watermark_bytes(&image_bytes)
+}
+
+// 从图片字节加载水印
+fn decode_watermark_bytes(image_bytes: &[u8]) -> Result<DynamicImage, WatermarkError> {
+    log_at(LOG_DEBUG, || format!("开始从内存加载图片，数据长度: {}", image_bytes.len()));
+    
+    let code = decode_error_code(image_bytes);
+    image::load_from_memory(image_bytes)
+        .map_err(|e| {
+            log_at(LOG_ERROR, || format!("图片加载失败: {}", e));
+            WatermarkError::with_code(code, format!("Failed to load watermark image: {}", e))
+        })
+}
+
+// 图片解码失败时的错误类别：无法识别格式为 UnsupportedFormat，其余为 DecodeFailed
+fn decode_error_code(image_data: &[u8]) -> ErrorCode {
+    match image::guess_format(image_data) {
+        Ok(_) => ErrorCode::DecodeFailed,
+        Err(_) => ErrorCode::UnsupportedFormat,
+    }
+}
+
+// 图片探测结果（只读取文件头，不解码像素）
+#[derive(Debug, Serialize)]
+struct ImageInfo {
+    width: u32,
+    height: u32,
+    format: String,
+    has_alpha: bool,
+}
+
+// 按文件头识别格式并读取尺寸和颜色类型，用于在添加水印前快速拒绝无法处理的文件
+// 只校验文件头，像素数据损坏的文件仍可能在添加水印时解码失败
+fn probe_image_info(image_data: &[u8]) -> Result<ImageInfo, WatermarkError> {
+    use image::ImageDecoder;
+    use image::codecs::{bmp::BmpDecoder, gif::GifDecoder, ico::IcoDecoder, jpeg::JpegDecoder, png::PngDecoder, webp::WebPDecoder};
+    
+    fn header<'a>(decoder: impl ImageDecoder<'a>) -> ((u32, u32), image::ColorType) {
+        (decoder.dimensions(), decoder.color_type())
+    }
+    
+    let format = image::guess_format(image_data)
+        .map_err(|_| WatermarkError::with_code(ErrorCode::UnsupportedFormat, "Unrecognized image format"))?;
+    let header_error = |e: image::ImageError| WatermarkError::with_code(ErrorCode::DecodeFailed, format!("Failed to read image header: {}", e));
+    let reader = Cursor::new(image_data);
+    let ((width, height), color) = match format {
+        image::ImageFormat::Png => header(PngDecoder::new(reader).map_err(header_error)?),
+        image::ImageFormat::Jpeg => header(JpegDecoder::new(reader).map_err(header_error)?),
+        image::ImageFormat::Gif => header(GifDecoder::new(reader).map_err(header_error)?),
+        image::ImageFormat::WebP => header(WebPDecoder::new(reader).map_err(header_error)?),
+        image::ImageFormat::Bmp => header(BmpDecoder::new(reader).map_err(header_error)?),
+        image::ImageFormat::Ico => header(IcoDecoder::new(reader).map_err(header_error)?),
+        #[cfg(feature = "hdr")]
+        image::ImageFormat::Hdr => header(image::codecs::hdr::HdrAdapter::new(reader).map_err(header_error)?),
+        #[cfg(feature = "hdr")]
+        image::ImageFormat::OpenExr => header(image::codecs::openexr::OpenExrDecoder::new(reader).map_err(header_error)?),
+        _ => return Err(WatermarkError::with_code(
+            ErrorCode::UnsupportedFormat,
+            format!("Unsupported image format: {:?}", format),
+        )),
+    };
+    if width == 0 || height == 0 {
+        return Err(WatermarkError::with_code(
+            ErrorCode::DecodeFailed,
+            format!("Image has zero width or height ({}x{})", width, height),
+        ));
+    }
+    
+    Ok(ImageInfo {
+        width,
+        height,
+        format: format!("{:?}", format).to_lowercase(),
+        has_alpha: color.has_alpha(),
+    })
+}
+
+// 原生文字渲染使用的内置字体
+#[cfg(feature = "text-render")]
+static DEFAULT_FONT: &[u8] = include_bytes!("../fonts/DejaVuSans.ttf");
+
+// 用内置字体把文字渲染为透明底的 RGBA 图片，按 \n 分行左对齐
+// 画布大小取所有行框（行宽 x 行高）与字形实际外接矩形的并集，保证超出步进宽度的字形不被裁掉
+#[cfg(feature = "text-render")]
+fn render_text_watermark(text: &str, font_size: f32, color: [u8; 3]) -> Result<RgbaImage, String> {
+    use ab_glyph::{Font, FontRef, PxScale, ScaleFont};
+    
+    if text.trim().is_empty() {
+        return Err("text must not be empty".to_string());
+    }
+    
+    let font = FontRef::try_from_slice(DEFAULT_FONT)
+        .map_err(|e| format!("Failed to load built-in font: {}", e))?;
+    let scaled = font.as_scaled(PxScale::from(font_size));
+    let line_height = scaled.height() + scaled.line_gap();
+    
+    // 排版：逐行累加步进宽度和字距
+    let mut outlines = Vec::new();
+    let mut max_x = 0.0f32;
+    let lines: Vec<&str> = text.split('\n').map(|line| line.trim_end_matches('\r')).collect();
+    for (index, line) in lines.iter().enumerate() {
+        let baseline = scaled.ascent() + index as f32 * line_height;
+        let mut caret = 0.0f32;
+        let mut previous = None;
+        for ch in line.chars() {
+            let glyph_id = font.glyph_id(ch);
+            if let Some(previous) = previous {
+                caret += scaled.kern(previous, glyph_id);
+            }
+            let glyph = glyph_id.with_scale_and_position(scaled.scale(), ab_glyph::point(caret, baseline));
+            caret += scaled.h_advance(glyph_id);
+            previous = Some(glyph_id);
+            if let Some(outline) = font.outline_glyph(glyph) {
+                outlines.push(outline);
+            }
+        }
+        max_x = max_x.max(caret);
+    }
+    
+    // 行框与字形外接矩形的并集
+    let (mut min_x, mut min_y) = (0.0f32, 0.0f32);
+    let mut max_y = lines.len() as f32 * line_height;
+    for outline in &outlines {
+        let bounds = outline.px_bounds();
+        min_x = min_x.min(bounds.min.x);
+        min_y = min_y.min(bounds.min.y);
+        max_x = max_x.max(bounds.max.x);
+        max_y = max_y.max(bounds.max.y);
+    }
+    let width = (max_x - min_x).ceil().max(1.0) as u32;
+    let height = (max_y - min_y).ceil().max(1.0) as u32;
+    check_image_dimensions(&DynamicImage::new_luma8(width, height))?;
+    
+    let mut canvas = RgbaImage::from_pixel(width, height, image::Rgba([color[0], color[1], color[2], 0]));
+    for outline in &outlines {
+        let bounds = outline.px_bounds();
+        let origin_x = (bounds.min.x - min_x) as i64;
+        let origin_y = (bounds.min.y - min_y) as i64;
+        outline.draw(|x, y, coverage| {
+            let (px, py) = (origin_x + x as i64, origin_y + y as i64);
+            if px < 0 || py < 0 || px >= width as i64 || py >= height as i64 {
+                return;
+            }
+            // 相邻字形重叠处取较大覆盖率
+            let pixel = canvas.get_pixel_mut(px as u32, py as u32);
+            pixel[3] = pixel[3].max((coverage.clamp(0.0, 1.0) * 255.0).round() as u8);
+        });
+    }
+    
+    Ok(canvas)
+}
+
+// 按原始宽高比由一条边的目标长度推算另一条边（四舍五入，至少为 1，避免极宽/极高水印被缩放为 0）
+fn proportional_length(src_known: u32, src_other: u32, target_known: u32) -> Result<u32, String> {
+    if src_known == 0 {
+        return Err("Watermark image has zero size".to_string());
+    }
+    
+    let length = (src_other as u64 * target_known as u64 + src_known as u64 / 2) / src_known as u64;
+    Ok(length.clamp(1, u32::MAX as u64) as u32)
+}
+
+// 水印缩放的目标尺寸：宽高都设置时精确缩放，只设置一边时按原始宽高比推算另一边，都未设置时不缩放
+fn target_size(config: &WatermarkConfig, src_width: u32, src_height: u32) -> Result<Option<(u32, u32)>, String> {
+    Ok(match (config.width, config.height) {
+        (Some(width), Some(height)) => Some((width, height)),
+        (Some(width), None) => Some((width, proportional_length(src_width, src_height, width)?)),
+        (None, Some(height)) => Some((proportional_length(src_height, src_width, height)?, height)),
+        (None, None) => None,
+    })
+}
+
+// 不可见水印只能无损输出为 PNG，且嵌入之后不能再改动像素
+fn validate_invisible_config(config: &WatermarkConfig) -> Result<(), String> {
+    if config.payload.as_deref().is_none_or(str::is_empty) {
+        return Err("Invisible watermark requires a non-empty payload".to_string());
+    }
+    let formats = config.output_format.iter().chain(config.output_formats.iter().flatten());
+    if let Some(format) = formats.into_iter().find(|format| *format != "png") {
+        return Err(format!("Invisible watermark requires PNG output, got '{}'", format));
+    }
+    let pixel_changes = [
+        ("autocrop", config.autocrop.unwrap_or(false)),
+        ("force_rgb", config.force_rgb.unwrap_or(false)),
+        ("premultiply_output", config.premultiply_output.unwrap_or(false)),
+        ("edge_matte_color", config.edge_matte_color.is_some()),
+        ("diff_output", config.diff_output.unwrap_or(false)),
+        ("max_output_bytes", config.max_output_bytes.is_some()),
+        ("animated", config.animated.unwrap_or(false)),
+        ("transparency_sweep", config.transparency_sweep.is_some()),
+        ("tile_output", config.tile_output.is_some()),
+    ];
+    if let Some((name, _)) = pixel_changes.iter().find(|(_, set)| *set) {
+        return Err(format!("Invisible watermark cannot be combined with {}", name));
+    }
+    Ok(())
+}
+
+// 不可见水印的标记，提取时据此判断图片中是否嵌入了水印
+const INVISIBLE_MAGIC: [u8; 4] = *b"FWM1";
+
+// 不可见水印：把 标记 + payload 字节数（u32 大端）+ payload 的 UTF-8 字节逐位（高位在前）
+// 按行优先顺序从左上角开始写入每个像素蓝色通道的最低位，肉眼不可见
+fn embed_invisible_watermark(img: &mut DynamicImage, payload: &str) -> Result<(), String> {
+    let mut bytes = INVISIBLE_MAGIC.to_vec();
+    bytes.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(payload.as_bytes());
+    
+    let target = rgba8_in_place(img);
+    let capacity = target.width() as u64 * target.height() as u64;
+    if bytes.len() as u64 * 8 > capacity {
+        return Err(format!(
+            "Payload of {} bytes needs {} pixels but the image only has {}",
+            payload.len(),
+            bytes.len() * 8,
+            capacity,
+        ));
+    }
+    let bits = bytes.iter().flat_map(|byte| (0..8).rev().map(move |bit| (byte >> bit) & 1));
+    for (pixel, bit) in target.pixels_mut().zip(bits) {
+        pixel[2] = (pixel[2] & !1) | bit;
+    }
+    Ok(())
+}
+
+// 按 embed_invisible_watermark 的布局读取 payload
+fn extract_invisible_watermark(img: &RgbaImage) -> Result<String, String> {
+    let mut bits = img.pixels().map(|pixel| pixel[2] & 1);
+    let mut remaining = img.width() as u64 * img.height() as u64;
+    let mut read_bytes = |count: usize| -> Option<Vec<u8>> {
+        remaining = remaining.checked_sub(count as u64 * 8)?;
+        Some((0..count).map(|_| (0..8).fold(0u8, |byte, _| byte << 1 | bits.next().unwrap_or(0))).collect())
+    };
+    
+    if read_bytes(INVISIBLE_MAGIC.len()).as_deref() != Some(&INVISIBLE_MAGIC[..]) {
+        return Err("No invisible watermark found".to_string());
+    }
+    let length = read_bytes(4)
+        .map(|length| u32::from_be_bytes([length[0], length[1], length[2], length[3]]) as usize)
+        .ok_or("Invisible watermark is truncated")?;
+    let payload = read_bytes(length).ok_or("Invisible watermark is truncated")?;
+    String::from_utf8(payload).map_err(|_| "Invisible watermark payload is not valid UTF-8".to_string())
+}
+
+// 文字水印没有 image_data 和预加载水印时，由 text 直接渲染
+fn renders_text_natively(config: &WatermarkConfig) -> bool {
+    config.watermark_type == WatermarkType::Text
+        && config.image_data.is_none()
+        && config.watermark_key.is_none()
+        && config.watermark_bytes.is_none()
+        && config.text.is_some()
+}
+
+// 加载原始水印图片：优先使用直接传入的图片字节和预加载的水印，否则解码 image_data（失败时尝试备用水印）
+fn load_watermark_source(config: &WatermarkConfig) -> Result<DynamicImage, WatermarkError> {
+    if let Some(bytes) = &config.watermark_bytes {
+        return decode_watermark_bytes(bytes);
+    }
+    
+    if let Some(key) = &config.watermark_key {
+        return WATERMARK_REGISTRY.with(|registry| {
+            registry.borrow().get(key).cloned().ok_or_else(|| WatermarkError::with_code(
+                ErrorCode::InvalidConfig,
+                format!("No preloaded watermark registered under key '{}'", key),
+            ))
+        });
+    }
+    
+    #[cfg(feature = "text-render")]
+    if let (true, Some(text)) = (renders_text_natively(config), &config.text) {
+        let color = parse_hex_color(config.font_color.as_deref().unwrap_or("#FFFFFF"))?;
+        return Ok(DynamicImage::ImageRgba8(render_text_watermark(text, config.font_size.unwrap_or(30.0), color)?));
+    }
+    
+    let image_data = config.image_data.as_ref()
+        .ok_or_else(|| WatermarkError::with_code(ErrorCode::InvalidConfig, "image_data parameter is required"))?;
+    
+    log_at(LOG_DEBUG, || format!("水印配置中的image_data存在，长度: {}", image_data.len()));
+    
+    // 加载图片（主水印解码失败时尝试备用水印）
+    match decode_watermark_image(image_data) {
+        Ok(img) => Ok(img),
+        Err(primary_err) => match &config.fallback_image_data {
+            Some(fallback) => {
+                log_at(LOG_ERROR, || format!(
+                    "Primary watermark failed to decode ({}), using fallback_image_data", primary_err
+                ));
+                decode_watermark_image(fallback)
+                    .map_err(|e| e.context(format!("{}; fallback also failed", primary_err.message)))
+            }
+            None => Err(primary_err),
+        },
+    }
+}
+
+// 水印变换步骤的默认顺序，transform_order 未列出的步骤按此顺序追加在后面
+const DEFAULT_TRANSFORM_ORDER: [&str; 3] = ["resize", "flip", "rotate"];
+
+// 解析变换顺序：先按 transform_order 排列，再按默认顺序补齐未列出的步骤
+fn transform_order(config: &WatermarkConfig) -> Result<Vec<&'static str>, String> {
+    let mut order = Vec::with_capacity(DEFAULT_TRANSFORM_ORDER.len());
+    for step in config.transform_order.iter().flatten() {
+        let step = DEFAULT_TRANSFORM_ORDER.iter().copied().find(|s| s == step).ok_or_else(|| {
+            format!("Invalid transform_order step '{}'. Must be 'resize', 'flip' or 'rotate'", step)
+        })?;
+        if order.contains(&step) {
+            return Err(format!("Duplicate transform_order step '{}'", step));
+        }
+        order.push(step);
+    }
+    for step in DEFAULT_TRANSFORM_ORDER {
+        if !order.contains(&step) {
+            order.push(step);
+        }
+    }
+    Ok(order)
+}
+
+// 加载并调整水印图片
+fn load_and_prepare_watermark(
+    config: &WatermarkConfig,
+) -> Result<RgbaImage, WatermarkError> {
+    log_at(LOG_DEBUG, || "开始加载并准备水印图片".to_string());
+    
+    let watermark_img = load_and_transform_watermark(config, &transform_order(config)?)?;
+    finish_watermark(watermark_img.to_rgba8(), config)
+}
+
+// 加载水印并执行旋转以外的变换步骤（校验已保证 rotate 是最后一步），供平铺时逐个旋转
+fn load_unrotated_watermark(config: &WatermarkConfig) -> Result<DynamicImage, WatermarkError> {
+    let order = transform_order(config)?;
+    let steps: Vec<_> = order.into_iter().filter(|step| *step != "rotate").collect();
+    load_and_transform_watermark(config, &steps)
+}
+
+// 平铺位置 (row, col) 上按随机角度单独旋转的水印：rotate ± rotation_jitter，再执行旋转之后的处理
+fn rotation_jittered_watermark(
+    unrotated: &DynamicImage,
+    config: &WatermarkConfig,
+    rotation_jitter: f32,
+    (row, col): (i64, i64),
+) -> Result<RgbaImage, WatermarkError> {
+    let mut rng = tile_rng(config.seed.unwrap_or(0), TILE_RNG_ROTATION, row, col);
+    let angle = config.rotate.unwrap_or(0.0) + (rng.next_signed() * rotation_jitter as f64) as f32;
+    finish_watermark(rotate_watermark(unrotated, angle, config).to_rgba8(), config)
+}
+
+// 变换之后的水印处理：颜色调整、不透明度渐变、投影和亚像素偏移
+fn finish_watermark(mut watermark_rgba: RgbaImage, config: &WatermarkConfig) -> Result<RgbaImage, WatermarkError> {
+    // 去色和着色放在缩放、旋转之后，插值产生的半透明边缘同样被处理
+    apply_color_adjustments(&mut watermark_rgba, config)?;
+    
+    // 四角不透明度渐变（作用于变换后的水印外接矩形）
+    if let Some(corners) = config.corner_opacities {
+        apply_corner_opacities(&mut watermark_rgba, corners);
+    }
+    if let Some(gradient) = config.gradient.as_deref().filter(|g| *g != "none") {
+        apply_opacity_gradient(&mut watermark_rgba, gradient);
+    }
+    
+    // 投影基于最终的 alpha 生成，渐隐的水印得到同样渐隐的阴影
+    if let Some((offset, blur)) = shadow_params(config) {
+        let color = parse_hex_color(config.shadow_color.as_deref().unwrap_or("#000000"))?;
+        watermark_rgba = apply_drop_shadow(&watermark_rgba, offset, blur, color);
+    }
+    
+    // 亚像素偏移放在最后，直接平移最终的水印像素
+    if let Some([dx, dy]) = config.subpixel_offset {
+        let nearest = config.overlay_sampling.as_deref() == Some("nearest");
+        watermark_rgba = shift_subpixel(&watermark_rgba, dx, dy, nearest);
+    }
+    
+    Ok(watermark_rgba)
+}
+
+// 投影模糊 sigma 与偏移量的上限
+const MAX_SHADOW_BLUR: f32 = 100.0;
+const MAX_SHADOW_OFFSET: u32 = 1000;
+
+// 开启 shadow 时的 (偏移, 模糊 sigma)
+fn shadow_params(config: &WatermarkConfig) -> Option<((i32, i32), f32)> {
+    if !config.shadow.unwrap_or(false) {
+        return None;
+    }
+    let offset = (config.shadow_offset_x.unwrap_or(4), config.shadow_offset_y.unwrap_or(4));
+    // 快速预览模式跳过模糊，只绘制硬边阴影
+    let blur = if config.fast.unwrap_or(false) { 0.0 } else { config.shadow_blur.unwrap_or(2.0) };
+    Some((offset, blur))
+}
+
+// 按配置计算投影扩展出的边距，未开启 shadow 时全为 0
+fn shadow_margins(config: &WatermarkConfig) -> (u32, u32, u32, u32) {
+    shadow_params(config).map_or((0, 0, 0, 0), |(offset, blur)| drop_shadow_margins(offset, blur))
+}
+
+// 投影在水印四周扩展出的边距 (左, 上, 右, 下)：四周各留 3 sigma 的模糊边距，再向偏移方向扩展
+fn drop_shadow_margins((dx, dy): (i32, i32), blur: f32) -> (u32, u32, u32, u32) {
+    let margin = (blur * 3.0).ceil() as u32;
+    (
+        margin + dx.min(0).unsigned_abs(),
+        margin + dy.min(0).unsigned_abs(),
+        margin + dx.max(0) as u32,
+        margin + dy.max(0) as u32,
+    )
+}
+
+// 由水印的 alpha 生成阴影层，偏移、模糊后与水印合成为一张图
+// 水印在新画布中位于 (左边距, 上边距)，叠加时 composite_watermark 减去该偏移，水印本身仍在配置的位置
+fn apply_drop_shadow(watermark: &RgbaImage, offset: (i32, i32), blur: f32, color: [u8; 3]) -> RgbaImage {
+    let (width, height) = watermark.dimensions();
+    let (dx, dy) = offset;
+    let (left, top, right, bottom) = drop_shadow_margins(offset, blur);
+    let canvas_width = width + left + right;
+    let canvas_height = height + top + bottom;
+    
+    // 只模糊 alpha 遮罩，避免透明像素的黑色 RGB 渗入阴影颜色
+    let mut mask = image::GrayImage::new(canvas_width, canvas_height);
+    let (shadow_x, shadow_y) = ((left as i64 + dx as i64) as u32, (top as i64 + dy as i64) as u32);
+    for (x, y, p) in watermark.enumerate_pixels() {
+        mask.put_pixel(x + shadow_x, y + shadow_y, image::Luma([p[3]]));
+    }
+    if blur > 0.0 {
+        mask = image::imageops::blur(&mask, blur);
+    }
+    
+    let mut combined = RgbaImage::from_fn(canvas_width, canvas_height, |x, y| {
+        image::Rgba([color[0], color[1], color[2], mask.get_pixel(x, y)[0]])
+    });
+    image::imageops::overlay(&mut combined, watermark, left as i64, top as i64);
+    combined
+}
+
+// 将水印平移 (dx, dy) 个像素（0 <= d < 1），画布各扩展 1 像素容纳平移后的边缘
+// nearest 时四舍五入到整像素，否则双线性插值
+fn shift_subpixel(img: &RgbaImage, dx: f32, dy: f32, nearest: bool) -> RgbaImage {
+    let (width, height) = img.dimensions();
+    if nearest {
+        let (sx, sy) = (dx.round() as u32, dy.round() as u32);
+        let mut result = RgbaImage::new(width + 1, height + 1);
+        image::imageops::replace(&mut result, img, sx as i64, sy as i64);
+        return result;
+    }
+    
+    let sample = |x: i64, y: i64| -> [f32; 4] {
+        if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+            return [0.0; 4];
+        }
+        let p = img.get_pixel(x as u32, y as u32);
+        // 按预乘 alpha 插值，避免透明像素的颜色渗入边缘
+        let a = p[3] as f32 / 255.0;
+        [p[0] as f32 * a, p[1] as f32 * a, p[2] as f32 * a, p[3] as f32]
+    };
+    RgbaImage::from_fn(width + 1, height + 1, |x, y| {
+        // 目标像素 (x, y) 对应源坐标 (x - dx, y - dy)，在 (x - 1, y - 1) 与 (x, y) 之间插值
+        let (x, y) = (x as i64, y as i64);
+        let weights = [
+            ((x - 1, y - 1), dx * dy),
+            ((x, y - 1), (1.0 - dx) * dy),
+            ((x - 1, y), dx * (1.0 - dy)),
+            ((x, y), (1.0 - dx) * (1.0 - dy)),
+        ];
+        let mut acc = [0.0f32; 4];
+        for ((sx, sy), weight) in weights {
+            let p = sample(sx, sy);
+            for c in 0..4 {
+                acc[c] += p[c] * weight;
+            }
+        }
+        let alpha = acc[3];
+        if alpha <= 0.0 {
+            return image::Rgba([0, 0, 0, 0]);
+        }
+        let unpremultiply = |c: f32| (c * 255.0 / alpha).round().clamp(0.0, 255.0) as u8;
+        image::Rgba([unpremultiply(acc[0]), unpremultiply(acc[1]), unpremultiply(acc[2]), alpha.round() as u8])
+    })
+}
+
+// 加载水印，完成抠图后按给定顺序执行变换步骤
+fn load_and_transform_watermark(config: &WatermarkConfig, steps: &[&str]) -> Result<DynamicImage, WatermarkError> {
+    let mut watermark_img = load_watermark_source(config)?;
+    
+    // 颜色键抠图（在缩放前处理，避免插值把背景色混入边缘）
+    if let Some(key) = config.color_key {
+        let mut rgba = watermark_img.to_rgba8();
+        apply_color_key(&mut rgba, key, config.color_key_tolerance.unwrap_or(0));
+        watermark_img = DynamicImage::ImageRgba8(rgba);
+    }
+    
+    for step in steps {
+        watermark_img = match *step {
+            // 遮罩紧跟缩放，羽化宽度按最终尺寸计算，并在旋转前应用，形状随水印一起旋转
+            "resize" => mask_watermark(resize_watermark(watermark_img, config)?, config),
+            "flip" => flip_watermark(watermark_img, config),
+            "rotate" => rotate_watermark(&watermark_img, config.rotate.unwrap_or(0.0), config),
+            _ => unreachable!("transform_order only yields known steps"),
+        };
+    }
+    
+    Ok(watermark_img)
+}
+
+// 形状遮罩边缘的羽化宽度（像素）
+const MASK_FEATHER: f32 = 1.5;
+
+// 遮罩步骤：按 mask 把形状以外的 alpha 清零，边缘按到形状边界的距离羽化
+fn mask_watermark(watermark_img: DynamicImage, config: &WatermarkConfig) -> DynamicImage {
+    let Some(mask) = config.mask.as_deref() else {
+        return watermark_img;
+    };
+    let mut rgba = watermark_img.to_rgba8();
+    let (width, height) = (rgba.width() as f32, rgba.height() as f32);
+    let radius = match config.corner_radius {
+        Some(radius) => radius as f32,
+        None => width.min(height) / 8.0,
+    }.min(width.min(height) / 2.0);
+    let (rx, ry) = (width / 2.0, height / 2.0);
+    
+    for (x, y, pixel) in rgba.enumerate_pixels_mut() {
+        let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+        // 像素中心到形状边界的有向距离（内部为正）
+        let inside = if mask == "circle" {
+            // 椭圆按归一化半径近似，换算到短半轴的像素距离
+            let d = (((px - rx) / rx).powi(2) + ((py - ry) / ry).powi(2)).sqrt();
+            (1.0 - d) * rx.min(ry)
+        } else {
+            let dx = (radius - px).max(px - (width - radius)).max(0.0);
+            let dy = (radius - py).max(py - (height - radius)).max(0.0);
+            if dx > 0.0 && dy > 0.0 { radius - (dx * dx + dy * dy).sqrt() } else { MASK_FEATHER }
+        };
+        let coverage = (inside / MASK_FEATHER + 0.5).clamp(0.0, 1.0);
+        pixel[3] = (pixel[3] as f32 * coverage).round() as u8;
+    }
+    
+    DynamicImage::ImageRgba8(rgba)
+}
+
+// 缩放步骤
+fn resize_watermark(mut watermark_img: DynamicImage, config: &WatermarkConfig) -> Result<DynamicImage, String> {
+    // 调整水印图片大小（仅对图片水印有效，文字水印默认不调整大小）
+    if config.watermark_type == WatermarkType::Image {
+        if let Some((width, height)) = target_size(config, watermark_img.width(), watermark_img.height())? {
+            log_at(LOG_DEBUG, || format!("调整水印图片大小: {}x{} -> {}x{}",
+                watermark_img.width(), watermark_img.height(), width, height));
+            watermark_img = watermark_img.resize_exact(width, height, resize_filter(config));
+        }
+    } else if config.sdf_text.unwrap_or(false) {
+        // 文字水印使用 SDF 缩放：一次生成距离场，任意目标尺寸下边缘都保持锐利
+        #[cfg(feature = "sdf-text")]
+        if let Some((width, height)) = target_size(config, watermark_img.width(), watermark_img.height())? {
+            watermark_img = if config.fast.unwrap_or(false) {
+                // 快速预览模式跳过距离场计算，直接最近邻缩放
+                watermark_img.resize_exact(width, height, image::imageops::FilterType::Nearest)
+            } else {
+                DynamicImage::ImageRgba8(sdf_resize(&watermark_img.to_rgba8(), width, height))
+            };
+        }
+    } else {
+        log_at(LOG_DEBUG, || format!("文字水印不调整大小，保持原始尺寸: {}x{}",
+            watermark_img.width(), watermark_img.height()));
+    }
+    
+    // 文字渐变填充紧跟在缩放之后（SDF 缩放会把颜色合并为单一填充色）
+    if let Some(gradient) = &config.text_gradient {
+        let mut rgba = watermark_img.to_rgba8();
+        apply_gradient_fill(&mut rgba, gradient)?;
+        watermark_img = DynamicImage::ImageRgba8(rgba);
+    }
+    
+    // 标签底板按缩放后的文字范围绘制，之后的翻转、旋转会连同底板一起变换
+    if let Some(label) = &config.label_background {
+        watermark_img = DynamicImage::ImageRgba8(draw_label_background(&watermark_img.to_rgba8(), label)?);
+    }
+    
+    Ok(watermark_img)
+}
+
+// 标签底板：裁剪到文字的非透明范围，四周加内边距后绘制（圆角）背景，再把字形叠加在上面
+fn draw_label_background(text: &RgbaImage, label: &LabelBgConfig) -> Result<RgbaImage, String> {
+    let (text_x, text_y, text_width, text_height) = alpha_bounding_box(text)
+        .ok_or("label_background requires a text watermark with visible pixels")?;
+    let padding = label.padding.unwrap_or(8);
+    let width = text_width + padding * 2;
+    let height = text_height + padding * 2;
+    
+    let color = parse_hex_color(label.color.as_deref().unwrap_or("#000000"))?;
+    let mut chip = RgbaImage::from_pixel(width, height, image::Rgba([color[0], color[1], color[2], 255]));
+    if let Some(gradient) = &label.gradient {
+        apply_gradient_fill(&mut chip, gradient)?;
+    }
+    
+    // 圆角：按像素中心到圆角圆心的距离计算覆盖率，边缘做 1 像素抗锯齿
+    let radius = (label.corner_radius.unwrap_or(0) as f32).min(width.min(height) as f32 / 2.0);
+    let opacity = label.opacity.unwrap_or(1.0);
+    for (x, y, pixel) in chip.enumerate_pixels_mut() {
+        let px = x as f32 + 0.5;
+        let py = y as f32 + 0.5;
+        let dx = (radius - px).max(px - (width as f32 - radius)).max(0.0);
+        let dy = (radius - py).max(py - (height as f32 - radius)).max(0.0);
+        let coverage = if dx > 0.0 && dy > 0.0 {
+            (radius - (dx * dx + dy * dy).sqrt() + 0.5).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        pixel[3] = (255.0 * opacity * coverage).round() as u8;
+    }
+    
+    // 字形以 "over" 方式合成到底板上
+    for y in 0..text_height {
+        for x in 0..text_width {
+            let glyph = text.get_pixel(text_x + x, text_y + y);
+            let glyph_alpha = glyph[3] as f32 / 255.0;
+            if glyph_alpha == 0.0 {
+                continue;
+            }
+            let background = chip.get_pixel_mut(x + padding, y + padding);
+            let background_alpha = background[3] as f32 / 255.0;
+            let out_alpha = glyph_alpha + background_alpha * (1.0 - glyph_alpha);
+            for c in 0..3 {
+                let value = glyph[c] as f32 * glyph_alpha + background[c] as f32 * background_alpha * (1.0 - glyph_alpha);
+                background[c] = (value / out_alpha).round() as u8;
+            }
+            background[3] = (out_alpha * 255.0).round() as u8;
+        }
+    }
+    
+    Ok(chip)
+}
+
+// 翻转步骤
+fn flip_watermark(watermark_img: DynamicImage, config: &WatermarkConfig) -> DynamicImage {
+    match config.flip.as_deref() {
+        Some("horizontal") => watermark_img.fliph(),
+        Some("vertical") => watermark_img.flipv(),
+        Some("both") => watermark_img.fliph().flipv(),
+        _ => watermark_img,
+    }
+}
+
+// 渐变填充：保留每个像素的 alpha，RGB 替换为按位置插值的渐变色
+fn apply_gradient_fill(img: &mut RgbaImage, gradient: &GradientConfig) -> Result<(), String> {
+    let colors = gradient.colors.iter().map(|c| parse_hex_color(c)).collect::<Result<Vec<_>, _>>()?;
+    let horizontal = gradient.direction.as_deref() == Some("horizontal");
+    let (width, height) = img.dimensions();
+    let length = if horizontal { width } else { height };
+    let segments = (colors.len() - 1) as f32;
+    
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        let position = if horizontal { x } else { y };
+        // 首尾像素分别对应第一个和最后一个颜色
+        let t = if length > 1 { position as f32 / (length - 1) as f32 } else { 0.0 };
+        let scaled = t * segments;
+        let index = (scaled.floor() as usize).min(colors.len() - 2);
+        let local = scaled - index as f32;
+        let (from, to) = (colors[index], colors[index + 1]);
+        for c in 0..3 {
+            pixel[c] = (from[c] as f32 + (to[c] as f32 - from[c] as f32) * local).round() as u8;
+        }
+    }
+    Ok(())
+}
+
+// 颜色键：将与 key 颜色在容差内的像素设为完全透明
+fn apply_color_key(img: &mut RgbaImage, key: [u8; 3], tolerance: u8) {
+    for pixel in img.pixels_mut() {
+        let matches = (0..3).all(|c| pixel[c].abs_diff(key[c]) <= tolerance);
+        if matches {
+            pixel[3] = 0;
+        }
+    }
+}
+
+// 去色与着色：先去色再着色，两者同时设置时按亮度把灰度映射为 tint_color 的深浅（单色化）
+fn apply_color_adjustments(img: &mut RgbaImage, config: &WatermarkConfig) -> Result<(), String> {
+    let grayscale = config.grayscale.unwrap_or(false);
+    if grayscale {
+        apply_grayscale(img);
+    }
+    if let Some(color) = &config.tint_color {
+        let color = parse_hex_color(color)?;
+        if grayscale {
+            colorize_grayscale(img, color);
+        } else {
+            apply_tint(img, color);
+        }
+    }
+    Ok(())
+}
+
+// 按亮度去色，保留 alpha
+fn apply_grayscale(img: &mut RgbaImage) {
+    for pixel in img.pixels_mut() {
+        let luma = (0.299 * pixel[0] as f32 + 0.587 * pixel[1] as f32 + 0.114 * pixel[2] as f32).round() as u8;
+        pixel[0] = luma;
+        pixel[1] = luma;
+        pixel[2] = luma;
+    }
+}
+
+// 灰度图按亮度乘以目标颜色：白色得到 color，黑色仍为黑色
+fn colorize_grayscale(img: &mut RgbaImage, color: [u8; 3]) {
+    for pixel in img.pixels_mut() {
+        for c in 0..3 {
+            pixel[c] = ((pixel[c] as u32 * color[c] as u32 + 127) / 255) as u8;
+        }
+    }
+}
+
+// 着色：非透明像素的 RGB 替换为指定颜色，alpha 不变
+fn apply_tint(img: &mut RgbaImage, color: [u8; 3]) {
+    for pixel in img.pixels_mut() {
+        if pixel[3] > 0 {
+            pixel[0] = color[0];
+            pixel[1] = color[1];
+            pixel[2] = color[2];
+        }
+    }
+}
+
+// 按四角不透明度双线性插值调制 alpha 通道，corners 顺序为 [左上, 右上, 左下, 右下]
+fn apply_corner_opacities(img: &mut RgbaImage, corners: [f32; 4]) {
+    let (width, height) = img.dimensions();
+    let [top_left, top_right, bottom_left, bottom_right] = corners;
+    let max_x = (width.max(2) - 1) as f32;
+    let max_y = (height.max(2) - 1) as f32;
+    
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        let u = x as f32 / max_x;
+        let v = y as f32 / max_y;
+        let top = top_left * (1.0 - u) + top_right * u;
+        let bottom = bottom_left * (1.0 - u) + bottom_right * u;
+        let factor = top * (1.0 - v) + bottom * v;
+        pixel[3] = (pixel[3] as f32 * factor).round() as u8;
+    }
+}
+
+// 按方向让 alpha 从完全保留渐隐到 0：horizontal 从左到右，vertical 从上到下，radial 从中心到四角
+// 只调制水印自身的 alpha，transparency 仍作为整体上限
+fn apply_opacity_gradient(img: &mut RgbaImage, gradient: &str) {
+    let (width, height) = img.dimensions();
+    let max_x = (width.max(2) - 1) as f32;
+    let max_y = (height.max(2) - 1) as f32;
+    
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        let u = x as f32 / max_x;
+        let v = y as f32 / max_y;
+        let factor = match gradient {
+            "horizontal" => 1.0 - u,
+            "vertical" => 1.0 - v,
+            // 到中心的归一化距离，四角为 1
+            _ => 1.0 - ((u - 0.5).powi(2) + (v - 0.5).powi(2)).sqrt() / std::f32::consts::FRAC_1_SQRT_2,
+        };
+        pixel[3] = (pixel[3] as f32 * factor.clamp(0.0, 1.0)).round() as u8;
+    }
+}
+
+// 将 RGBA 图片以指定背景色拍平为 RGB（用于不支持透明度的输出）
+fn flatten_alpha(img: &RgbaImage, background: [u8; 3]) -> image::RgbImage {
+    image::RgbImage::from_fn(img.width(), img.height(), |x, y| {
+        let p = img.get_pixel(x, y);
+        let alpha = p[3] as f32 / 255.0;
+        let blend = |c: usize| (p[c] as f32 * alpha + background[c] as f32 * (1.0 - alpha)).round() as u8;
+        image::Rgb([blend(0), blend(1), blend(2)])
+    })
+}
+
+// RGB 预乘 alpha
+fn premultiply_alpha(img: &mut RgbaImage) {
+    for pixel in img.pixels_mut() {
+        let alpha = pixel[3] as u32;
+        for c in 0..3 {
+            pixel[c] = ((pixel[c] as u32 * alpha + 127) / 255) as u8;
+        }
+    }
+}
+
+// 一维平方欧氏距离变换（Felzenszwalb & Huttenlocher 下包络算法）
+#[cfg(feature = "sdf-text")]
+fn distance_transform_1d(f: &[f64], out: &mut [f64]) {
+    const INF: f64 = 1e20;
+    let n = f.len();
+    let mut v = vec![0usize; n];
+    let mut z = vec![0.0f64; n + 1];
+    let mut k = 0;
+    z[0] = -INF;
+    z[1] = INF;
+    
+    let intersect = |q: usize, p: usize| {
+        ((f[q] + (q * q) as f64) - (f[p] + (p * p) as f64)) / (2.0 * (q as f64 - p as f64))
+    };
+    
+    for q in 1..n {
+        let mut s = intersect(q, v[k]);
+        while s <= z[k] {
+            k -= 1;
+            s = intersect(q, v[k]);
+        }
+        k += 1;
+        v[k] = q;
+        z[k] = s;
+        z[k + 1] = INF;
+    }
+    
+    k = 0;
+    for (q, slot) in out.iter_mut().enumerate() {
+        while z[k + 1] < q as f64 {
+            k += 1;
+        }
+        let d = q as f64 - v[k] as f64;
+        *slot = d * d + f[v[k]];
+    }
+}
+
+// 二维欧氏距离变换：返回每个像素到最近目标像素的距离
+#[cfg(feature = "sdf-text")]
+fn distance_transform(is_target: &[bool], width: usize, height: usize) -> Vec<f64> {
+    let mut grid: Vec<f64> = is_target.iter().map(|&t| if t { 0.0 } else { 1e20 }).collect();
+    let mut column = vec![0.0; height];
+    let mut column_out = vec![0.0; height];
+    for x in 0..width {
+        for y in 0..height {
+            column[y] = grid[y * width + x];
+        }
+        distance_transform_1d(&column, &mut column_out);
+        for y in 0..height {
+            grid[y * width + x] = column_out[y];
+        }
+    }
+    
+    let mut row_out = vec![0.0; width];
+    for y in 0..height {
+        distance_transform_1d(&grid[y * width..(y + 1) * width], &mut row_out);
+        grid[y * width..(y + 1) * width].copy_from_slice(&row_out);
+    }
+    
+    grid.iter().map(|d| d.sqrt()).collect()
+}
+
+// 由 alpha 通道生成有向距离场（单位为像素，内部为负、外部为正）
+#[cfg(feature = "sdf-text")]
+fn signed_distance_field(img: &RgbaImage) -> Vec<f32> {
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let inside: Vec<bool> = img.pixels().map(|p| p[3] >= 128).collect();
+    let outside: Vec<bool> = inside.iter().map(|&i| !i).collect();
+    let to_inside = distance_transform(&inside, width, height);
+    let to_outside = distance_transform(&outside, width, height);
+    
+    to_inside.iter().zip(&to_outside)
+        .map(|(&d_in, &d_out)| if d_in > 0.0 { (d_in - 0.5) as f32 } else { (0.5 - d_out) as f32 })
+        .collect()
+}
+
+// 使用有向距离场缩放文字水印：对距离场双线性采样后按目标尺度重新阈值化，得到锐利的抗锯齿边缘
+#[cfg(feature = "sdf-text")]
+fn sdf_resize(img: &RgbaImage, width: u32, height: u32) -> RgbaImage {
+    let (src_width, src_height) = img.dimensions();
+    let sdf = signed_distance_field(img);
+    
+    // 文字通常为单色，取不透明像素按 alpha 加权的平均颜色作为填充色
+    let mut color_sum = [0.0f64; 3];
+    let mut alpha_sum = 0.0f64;
+    for p in img.pixels() {
+        let a = p[3] as f64;
+        for c in 0..3 {
+            color_sum[c] += p[c] as f64 * a;
+        }
+        alpha_sum += a;
+    }
+    let fill = if alpha_sum > 0.0 {
+        color_sum.map(|c| (c / alpha_sum).round() as u8)
+    } else {
+        [0, 0, 0]
+    };
+    
+    let scale_x = width as f32 / src_width as f32;
+    let scale_y = height as f32 / src_height as f32;
+    let scale = (scale_x + scale_y) / 2.0;
+    let sample = |x: i64, y: i64| {
+        let x = x.clamp(0, src_width as i64 - 1) as usize;
+        let y = y.clamp(0, src_height as i64 - 1) as usize;
+        sdf[y * src_width as usize + x]
+    };
+    
+    RgbaImage::from_fn(width, height, |x, y| {
+        let sx = (x as f32 + 0.5) / scale_x - 0.5;
+        let sy = (y as f32 + 0.5) / scale_y - 0.5;
+        let (x0, y0) = (sx.floor() as i64, sy.floor() as i64);
+        let (fx, fy) = (sx - sx.floor(), sy - sy.floor());
+        let top = sample(x0, y0) * (1.0 - fx) + sample(x0 + 1, y0) * fx;
+        let bottom = sample(x0, y0 + 1) * (1.0 - fx) + sample(x0 + 1, y0 + 1) * fx;
+        let distance = (top * (1.0 - fy) + bottom * fy) * scale;
+        let alpha = (0.5 - distance).clamp(0.0, 1.0);
+        image::Rgba([fill[0], fill[1], fill[2], (alpha * 255.0).round() as u8])
+    })
+}
+
+// 支持的缩放滤波器名称
+const RESIZE_FILTERS: [&str; 5] = ["nearest", "triangle", "catmullrom", "gaussian", "lanczos3"];
+
+// 解析缩放滤波器名称（不区分大小写）
+fn parse_resize_filter(name: &str) -> Result<image::imageops::FilterType, String> {
+    use image::imageops::FilterType;
+    match name.to_ascii_lowercase().as_str() {
+        "nearest" => Ok(FilterType::Nearest),
+        "triangle" => Ok(FilterType::Triangle),
+        "catmullrom" => Ok(FilterType::CatmullRom),
+        "gaussian" => Ok(FilterType::Gaussian),
+        "lanczos3" => Ok(FilterType::Lanczos3),
+        _ => Err(format!("Invalid resize_filter '{}'. Must be one of: {}", name, RESIZE_FILTERS.join(", "))),
+    }
+}
+
+// 水印缩放使用的插值滤波器：按 resize_filter 选择，默认 Lanczos3（快速预览模式下使用最近邻）
+fn resize_filter(config: &WatermarkConfig) -> image::imageops::FilterType {
+    if config.fast.unwrap_or(false) {
+        return image::imageops::FilterType::Nearest;
+    }
+    // 配置已在 validate_config 中校验，无效名称不会到达这里
+    config.resize_filter.as_deref()
+        .and_then(|name| parse_resize_filter(name).ok())
+        .unwrap_or(image::imageops::FilterType::Lanczos3)
+}
+
+// 双线性插值辅助函数
+fn bilinear_interpolate(
+    img_data: &[u8],
+    width: usize,
+    height: usize,
+    x: f32,
+    y: f32,
+) -> [u8; 4] {
+    let x0 = x.floor() as i32;
+    let y0 = y.floor() as i32;
+    let x1 = x0 + 1;
+    let y1 = y0 + 1;
+    
+    let fx = x - x.floor();
+    let fy = y - y.floor();
+    
+    // 越界的邻居取最近的边缘像素，最后一行、一列也能正常插值
+    let get_pixel = |xi: i32, yi: i32| -> [u8; 4] {
+        let xi = xi.clamp(0, width as i32 - 1) as usize;
+        let yi = yi.clamp(0, height as i32 - 1) as usize;
+        let idx = (yi * width + xi) * 4;
+        [img_data[idx], img_data[idx + 1], img_data[idx + 2], img_data[idx + 3]]
+    };
+    
+    let p00 = get_pixel(x0, y0);
+    let p10 = get_pixel(x1, y0);
+    let p01 = get_pixel(x0, y1);
+    let p11 = get_pixel(x1, y1);
     
     let interpolate = |c00: u8, c10: u8, c01: u8, c11: u8| -> u8 {
         let c00 = c00 as f32;
@@ -219,397 +2231,4116 @@ fn bilinear_interpolate(
         let c01 = c01 as f32;
         let c11 = c11 as f32;
         
-        let top = c00 * (1.0 - fx) + c10 * fx;
-        let bottom = c01 * (1.0 - fx) + c11 * fx;
-        let result = top * (1.0 - fy) + bottom * fy;
+        let top = c00 * (1.0 - fx) + c10 * fx;
+        let bottom = c01 * (1.0 - fx) + c11 * fx;
+        let result = top * (1.0 - fy) + bottom * fy;
+        
+        result.clamp(0.0, 255.0) as u8
+    };
+    
+    [
+        interpolate(p00[0], p10[0], p01[0], p11[0]),
+        interpolate(p00[1], p10[1], p01[1], p11[1]),
+        interpolate(p00[2], p10[2], p01[2], p11[2]),
+        interpolate(p00[3], p10[3], p01[3], p11[3]),
+    ]
+}
+
+// 旋转后的外接矩形尺寸
+fn rotated_dimensions(width: u32, height: u32, angle_degrees: f32) -> (u32, u32) {
+    if angle_degrees == 0.0 {
+        return (width, height);
+    }
+    let angle_rad = angle_degrees * std::f32::consts::PI / 180.0;
+    let (sin_r, cos_r) = (angle_rad.sin().abs(), angle_rad.cos().abs());
+    (
+        (width as f32 * cos_r + height as f32 * sin_r).ceil() as u32,
+        (width as f32 * sin_r + height as f32 * cos_r).ceil() as u32,
+    )
+}
+
+// 按配置选择旋转方式：快速预览用最近邻，rotation_quality 为 "high" 时超采样，否则双线性插值
+fn rotate_watermark(img: &DynamicImage, angle_degrees: f32, config: &WatermarkConfig) -> DynamicImage {
+    if config.fast.unwrap_or(false) {
+        return rotate_image(img, angle_degrees, true);
+    }
+    if config.rotation_quality.as_deref() == Some("high") && angle_degrees != 0.0 {
+        return rotate_supersampled(img, angle_degrees);
+    }
+    rotate_image(img, angle_degrees, false)
+}
+
+// 超采样旋转：放大 2 倍（最近邻，保持原像素不变）后旋转，再用 Lanczos 缩小到原分辨率下的外接矩形尺寸
+// 缩小时按预乘 alpha 计算，避免画布外的透明黑色在斜边上形成暗边
+fn rotate_supersampled(img: &DynamicImage, angle_degrees: f32) -> DynamicImage {
+    let (width, height) = img.dimensions();
+    let upscaled = img.resize_exact(width * 2, height * 2, image::imageops::FilterType::Nearest);
+    let mut rotated = rotate_image(&upscaled, angle_degrees, false).to_rgba32f();
+    for pixel in rotated.pixels_mut() {
+        let alpha = pixel[3];
+        pixel[0] *= alpha;
+        pixel[1] *= alpha;
+        pixel[2] *= alpha;
+    }
+    
+    let (target_width, target_height) = rotated_dimensions(width, height, angle_degrees);
+    let downsampled = image::imageops::resize(&rotated, target_width, target_height, image::imageops::FilterType::Lanczos3);
+    let result = RgbaImage::from_fn(target_width, target_height, |x, y| {
+        let p = downsampled.get_pixel(x, y);
+        let alpha = p[3].clamp(0.0, 1.0);
+        if alpha <= 0.0 {
+            return image::Rgba([0, 0, 0, 0]);
+        }
+        let unpremultiply = |c: f32| ((c / alpha).clamp(0.0, 1.0) * 255.0).round() as u8;
+        image::Rgba([unpremultiply(p[0]), unpremultiply(p[1]), unpremultiply(p[2]), (alpha * 255.0).round() as u8])
+    });
+    DynamicImage::ImageRgba8(result)
+}
+
+// 旋转图片（默认使用双线性插值提高清晰度，nearest 为 true 时使用最近邻采样以提高速度）
+fn rotate_image(img: &DynamicImage, angle_degrees: f32, nearest: bool) -> DynamicImage {
+    if angle_degrees == 0.0 {
+        return img.clone();
+    }
+    
+    let angle_rad = angle_degrees * std::f32::consts::PI / 180.0;
+    let cos_r = angle_rad.cos();
+    let sin_r = angle_rad.sin();
+    
+    let (width, height) = img.dimensions();
+    let center_x = width as f32 / 2.0;
+    let center_y = height as f32 / 2.0;
+    
+    // 计算旋转后的新尺寸
+    let (new_width, new_height) = rotated_dimensions(width, height, angle_degrees);
+    
+    let mut result = RgbaImage::new(new_width, new_height);
+    let new_center_x = new_width as f32 / 2.0;
+    let new_center_y = new_height as f32 / 2.0;
+    
+    // 转换为 RGBA8 以便便快速访问
+    let img_rgba = img.to_rgba8();
+    let img_data = img_rgba.as_ref();
+    let result_data = result.as_mut();
+    
+    let width_usize = width as usize;
+    let height_usize = height as usize;
+    let new_width_usize = new_width as usize;
+    
+    // 按行处理每个像素
+    for y in 0..new_height {
+        let y_f32 = y as f32;
+        let rel_y = y_f32 - new_center_y;
+        
+        for x in 0..new_width {
+            let x_f32 = x as f32;
+            let rel_x = x_f32 - new_center_x;
+            
+            // 逆旋转到原图坐标
+            let orig_x = rel_x * cos_r + rel_y * sin_r + center_x;
+            let orig_y = -rel_x * sin_r + rel_y * cos_r + center_y;
+            
+            // 边界检查（插值时越界的邻居按边缘像素复制）
+            if orig_x >= 0.0 && orig_x < width as f32 &&
+               orig_y >= 0.0 && orig_y < height as f32 {
+                let pixel = if nearest {
+                    // 最近邻采样
+                    let nearest_x = (orig_x.round() as usize).min(width_usize - 1);
+                    let nearest_y = (orig_y.round() as usize).min(height_usize - 1);
+                    let idx = (nearest_y * width_usize + nearest_x) * 4;
+                    [img_data[idx], img_data[idx + 1], img_data[idx + 2], img_data[idx + 3]]
+                } else {
+                    // 使用双线性插值
+                    bilinear_interpolate(img_data, width_usize, height_usize, orig_x, orig_y)
+                };
+                let target_idx = (y as usize * new_width_usize + x as usize) * 4;
+                
+                result_data[target_idx] = pixel[0];
+                result_data[target_idx + 1] = pixel[1];
+                result_data[target_idx + 2] = pixel[2];
+                result_data[target_idx + 3] = pixel[3];
+            }
+        }
+    }
+    
+    DynamicImage::ImageRgba8(result)
+}
+
+// 叠加图片（直接操作 RGBA8，带透明度参数）
+fn overlay_image_rgba_with_transparency(
+    target: &mut RgbaImage,
+    overlay: &RgbaImage,
+    x: i64,
+    y: i64,
+    transparency: f32,
+    blend: BlendMode,
+) {
+    log_at(LOG_DEBUG, || format!("开始叠加图片，位置: ({}, {}), 透明度: {}", x, y, transparency));
+    
+    let (target_width, target_height) = target.dimensions();
+    let (overlay_width, overlay_height) = overlay.dimensions();
+    
+    log_at(LOG_DEBUG, || format!("目标图片尺寸: {}x{}, 水印图片尺寸: {}x{}",
+        target_width, target_height, overlay_width, overlay_height));
+    
+    // 预计算透明度因子
+    let transparency_factor = transparency;
+    
+    // 获取像素数据切片
+    let target_data = target.as_mut();
+    let overlay_data = overlay.as_ref();
+    
+    // 计算边界
+    // 底图尺寸已由 check_image_dimensions 保证 RGBA 字节数不超过 usize，行内索引不会溢出；
+    // 水印完全落在底图之外时直接返回，避免 end - start 下溢；
+    // 坐标可为负（旋转平铺的边缘水印），超出左/上边的部分从水印内部跳过
+    let end_x = x.saturating_add(overlay_width as i64).min(target_width as i64);
+    let end_y = y.saturating_add(overlay_height as i64).min(target_height as i64);
+    if x.max(0) >= end_x || y.max(0) >= end_y {
+        return;
+    }
+    let skip_x = (-x).max(0) as usize;
+    let skip_y = (-y).max(0) as usize;
+    let start_x = x.max(0) as usize;
+    let start_y = y.max(0) as usize;
+    let end_x = end_x as usize;
+    let end_y = end_y as usize;
+    
+    // 像素循环内不输出日志，只在进入前记录一次处理区域
+    log_at(LOG_DEBUG, || format!("开始像素混合，处理区域: ({}, {}) 到 ({}, {})",
+        start_x, start_y, end_x, end_y));
+    
+    // 逐行取出水印与底图的重叠部分，逐像素混合（标量实现；切片按行预先取好，循环内没有边界检查）
+    let row_bytes = (end_x - start_x) * 4;
+    for oy in 0..(end_y - start_y) {
+        let overlay_row_start = ((skip_y + oy) * overlay_width as usize + skip_x) * 4;
+        let target_row_start = (start_y + oy) * target_width as usize * 4 + start_x * 4;
+        let overlay_row = &overlay_data[overlay_row_start..overlay_row_start + row_bytes];
+        let target_row = &mut target_data[target_row_start..target_row_start + row_bytes];
+        
+        for (overlay_px, target_px) in overlay_row.chunks_exact(4).zip(target_row.chunks_exact_mut(4)) {
+            blend_pixel(target_px, overlay_px, transparency_factor, blend);
+        }
+    }
+}
+
+// 单像素混合：Porter-Duff source-over
+// out_a = src_a + dst_a * (1 - src_a)，out_rgb = (src_rgb * src_a + dst_rgb * dst_a * (1 - src_a)) / out_a
+fn blend_pixel(target: &mut [u8], overlay: &[u8], transparency: f32, blend: BlendMode) {
+    // 水印全透明的像素不需要任何修改（文字水印的大部分区域）
+    if overlay[3] == 0 {
+        return;
+    }
+    
+    let src_alpha = overlay[3] as f32 / 255.0 * transparency;
+    let dst_alpha = target[3] as f32 / 255.0;
+    let out_alpha = src_alpha + dst_alpha * (1.0 - src_alpha);
+    if out_alpha <= 0.0 {
+        return;
+    }
+    
+    // 混合模式只在底图不透明处生效（W3C 合成规范），底图透明处直接使用水印颜色
+    let dst_weight = dst_alpha * (1.0 - src_alpha);
+    for c in 0..3 {
+        let target_c = target[c] as f32;
+        let overlay_c = overlay[c] as f32;
+        let source = overlay_c * (1.0 - dst_alpha) + blend.blend(target_c, overlay_c) * dst_alpha;
+        target[c] = ((source * src_alpha + target_c * dst_weight) / out_alpha).round().clamp(0.0, 255.0) as u8;
+    }
+    target[3] = (out_alpha * 255.0).round() as u8;
+}
+
+// 取得底图的 RGBA8 缓冲区以便就地叠加：已经是 RGBA8（如 PNG 底图）时直接借用，不复制整张图；
+// 其他格式只转换一次并替换原图，之后的叠加都复用同一块缓冲区
+fn rgba8_in_place(img: &mut DynamicImage) -> &mut RgbaImage {
+    if !matches!(img, DynamicImage::ImageRgba8(_)) {
+        *img = DynamicImage::ImageRgba8(img.to_rgba8());
+    }
+    match img {
+        DynamicImage::ImageRgba8(rgba) => rgba,
+        _ => unreachable!("image was converted to RGBA8 above"),
+    }
+}
+
+// 沿长度为 length 的边框带均匀排布水印，返回每个水印的起始偏移（两端留白相等）
+fn border_slots(length: u32, item: u32, spacing: u32) -> Vec<u32> {
+    if item == 0 || item > length {
+        return Vec::new();
+    }
+    
+    let count = (length + spacing) / (item + spacing);
+    let used = count * item + (count - 1) * spacing;
+    let start = (length - used) / 2;
+    (0..count).map(|i| start + i * (item + spacing)).collect()
+}
+
+// 绘制边框水印：先填充边框带底色，再把水印缩放到边框宽度内，沿上下边水平排布、沿左右边旋转 90° 后竖直排布
+fn draw_border_watermark(
+    target: &mut RgbaImage,
+    watermark: &RgbaImage,
+    border: &BorderConfig,
+    transparency: f32,
+    filter: image::imageops::FilterType,
+    blend: BlendMode,
+) -> Result<Vec<WatermarkPlacement>, String> {
+    let (img_width, img_height) = target.dimensions();
+    let thickness = border.thickness.min(img_width / 2).min(img_height / 2);
+    if thickness == 0 {
+        return Err("Image is too small for border_watermark".to_string());
+    }
+    
+    // 边框带底色
+    if let Some(color) = &border.color {
+        let [r, g, b] = parse_hex_color(color)?;
+        for (x, y, pixel) in target.enumerate_pixels_mut() {
+            let in_band = x < thickness || y < thickness
+                || x >= img_width - thickness || y >= img_height - thickness;
+            if in_band {
+                *pixel = image::Rgba([r, g, b, 255]);
+            }
+        }
+    }
+    
+    // 水印缩放到边框带内（上下各留 10% 边距）
+    let (wm_width, wm_height) = watermark.dimensions();
+    if wm_width == 0 || wm_height == 0 {
+        return Ok(Vec::new());
+    }
+    let item_height = ((thickness as f32 * 0.8).round() as u32).max(1);
+    let item_width = ((wm_width as f32 * item_height as f32 / wm_height as f32).round() as u32).max(1);
+    let item = image::imageops::resize(watermark, item_width, item_height, filter);
+    let spacing = border.spacing.unwrap_or(thickness / 2);
+    let inset = (thickness - item_height) / 2;
+    
+    let mut placements = Vec::new();
+    let mut place = |target: &mut RgbaImage, item: &RgbaImage, x: u32, y: u32| {
+        overlay_image_rgba_with_transparency(target, item, x as i64, y as i64, transparency, blend);
+        placements.extend(WatermarkPlacement::clipped(x as i64, y as i64, item.dimensions(), img_width, img_height));
+    };
+    
+    // 上下边：水平排布（角落留给左右边之外的区域）
+    for x in border_slots(img_width - 2 * thickness, item_width, spacing) {
+        place(target, &item, thickness + x, inset);
+        place(target, &item, thickness + x, img_height - thickness + inset);
+    }
+    
+    // 左右边：旋转 90° 使水印沿边框方向排列
+    let left_item = image::imageops::rotate270(&item);
+    let right_item = image::imageops::rotate90(&item);
+    for y in border_slots(img_height - 2 * thickness, item_width, spacing) {
+        place(target, &left_item, inset, thickness + y);
+        place(target, &right_item, img_width - thickness + inset, thickness + y);
+    }
+    
+    Ok(placements)
+}
+
+// 计算锚点对应的水印左上角坐标（水印大于图片时可能为负）
+fn anchor_position(position: &str, img_width: u32, img_height: u32, wm_width: u32, wm_height: u32) -> (i64, i64) {
+    let free_x = img_width as i64 - wm_width as i64;
+    let free_y = img_height as i64 - wm_height as i64;
+    
+    let x = match position {
+        "top-left" | "left" | "bottom-left" => 0,
+        "top-right" | "right" | "bottom-right" => free_x,
+        _ => free_x / 2,
+    };
+    let y = match position {
+        "top-left" | "top" | "top-right" => 0,
+        "bottom-left" | "bottom" | "bottom-right" => free_y,
+        _ => free_y / 2,
+    };
+    
+    (x, y)
+}
+
+// 根据目标覆盖率计算平铺间距
+// 每个水印的“墨量”为其 alpha 之和，目标平铺数量 N = 覆盖率 * 图片面积 / 单个墨量，
+// 网格单元按水印宽高比等比放大到 图片面积 / N（不小于水印本身，避免重叠）
+fn coverage_spacing(
+    watermark: &RgbaImage,
+    img_width: u32,
+    img_height: u32,
+    coverage_percent: f32,
+) -> Result<(u32, u32), String> {
+    let (wm_width, wm_height) = watermark.dimensions();
+    let ink: f64 = watermark.pixels().map(|p| p[3] as f64 / 255.0).sum();
+    if ink <= 0.0 {
+        return Err("Cannot compute coverage: watermark has no opaque pixels".to_string());
+    }
+    
+    let image_area = img_width as f64 * img_height as f64;
+    let tile_count = (coverage_percent as f64 / 100.0) * image_area / ink;
+    let cell_area = image_area / tile_count.max(f64::MIN_POSITIVE);
+    let scale = (cell_area / (wm_width as f64 * wm_height as f64)).sqrt().max(1.0);
+    
+    let spacing_x = (wm_width as f64 * scale).round().max(1.0) as u32;
+    let spacing_y = (wm_height as f64 * scale).round().max(1.0) as u32;
+    Ok((spacing_x, spacing_y))
+}
+
+// 亮度局部方差图（积分图实现，任意矩形的方差查询为 O(1)）
+struct VarianceMap {
+    width: usize,
+    height: usize,
+    sum: Vec<f64>,
+    sum_sq: Vec<f64>,
+}
+
+impl VarianceMap {
+    fn new(img: &RgbaImage) -> Self {
+        let (width, height) = (img.width() as usize, img.height() as usize);
+        let stride = width + 1;
+        let mut sum = vec![0.0; stride * (height + 1)];
+        let mut sum_sq = vec![0.0; stride * (height + 1)];
+        
+        for y in 0..height {
+            let mut row_sum = 0.0;
+            let mut row_sum_sq = 0.0;
+            for x in 0..width {
+                let p = img.get_pixel(x as u32, y as u32);
+                let luma = (0.299 * p[0] as f64 + 0.587 * p[1] as f64 + 0.114 * p[2] as f64) / 255.0;
+                row_sum += luma;
+                row_sum_sq += luma * luma;
+                let idx = (y + 1) * stride + x + 1;
+                sum[idx] = sum[idx - stride] + row_sum;
+                sum_sq[idx] = sum_sq[idx - stride] + row_sum_sq;
+            }
+        }
+        
+        Self { width, height, sum, sum_sq }
+    }
+    
+    // 计算矩形区域（超出图片的部分被裁剪）的亮度方差
+    fn variance(&self, x: i64, y: i64, w: u32, h: u32) -> f64 {
+        let clamp_x = |v: i64| v.clamp(0, self.width as i64) as usize;
+        let clamp_y = |v: i64| v.clamp(0, self.height as i64) as usize;
+        let (x0, x1) = (clamp_x(x), clamp_x(x + w as i64));
+        let (y0, y1) = (clamp_y(y), clamp_y(y + h as i64));
+        let count = ((x1 - x0) * (y1 - y0)) as f64;
+        if count == 0.0 {
+            return 0.0;
+        }
+        
+        let stride = self.width + 1;
+        let rect = |table: &[f64]| {
+            table[y1 * stride + x1] - table[y0 * stride + x1] - table[y1 * stride + x0] + table[y0 * stride + x0]
+        };
+        let mean = rect(&self.sum) / count;
+        (rect(&self.sum_sq) / count - mean * mean).max(0.0)
+    }
+}
+
+// 由底图生成自水印：按比例缩小并去色
+fn self_watermark_image(img: &DynamicImage, self_watermark: &SelfWatermarkConfig, filter: image::imageops::FilterType) -> RgbaImage {
+    let scale = self_watermark.scale.unwrap_or(0.25);
+    let width = ((img.width() as f32 * scale).round() as u32).max(1);
+    let height = ((img.height() as f32 * scale).round() as u32).max(1);
+    let gray = img.resize_exact(width, height, filter).grayscale();
+    gray.to_rgba8()
+}
+
+// 偏移量：使用锚点时是相对锚点的增量，默认为 0；否则默认为 10
+fn placement_offsets(config: &WatermarkConfig) -> (i32, i32) {
+    let default_offset = if config.position.is_some() { 0 } else { 10 };
+    (config.x_offset.unwrap_or(default_offset), config.y_offset.unwrap_or(default_offset))
+}
+
+// 单个水印的左上角坐标（wm_width/wm_height 为旋转后的尺寸）
+fn single_position(config: &WatermarkConfig, img_width: u32, img_height: u32, wm_width: u32, wm_height: u32) -> (u32, u32) {
+    let (x_offset, y_offset) = placement_offsets(config);
+    if let Some(position) = &config.position {
+        // 锚点定位：先按锚点计算基准坐标，再叠加偏移量
+        let (anchor_x, anchor_y) = anchor_position(position, img_width, img_height, wm_width, wm_height);
+        (
+            (anchor_x + x_offset as i64).max(0) as u32,
+            (anchor_y + y_offset as i64).max(0) as u32,
+        )
+    } else {
+        // css 模式下负偏移量是水印远端边缘到图片远端边缘的距离，需要再减去水印尺寸
+        let css = config.negative_offset_mode.as_deref() == Some("css");
+        let (wm_x, wm_y) = if css { (wm_width as i64, wm_height as i64) } else { (0, 0) };
+        
+        let x = if x_offset >= 0 {
+            x_offset as u32
+        } else {
+            (img_width as i64 + x_offset as i64 - wm_x).max(0) as u32
+        };
+        
+        let y = if y_offset >= 0 {
+            y_offset as u32
+        } else {
+            (img_height as i64 + y_offset as i64 - wm_y).max(0) as u32
+        };
+        
+        (x, y)
+    }
+}
+
+// 单个水印的最终坐标：完全落在图片外时报错（否则叠加结果与原图相同，难以排查），
+// 设置 clamp_to_bounds 时移到贴近最近边缘的图片内位置
+fn bounded_single_position(config: &WatermarkConfig, img_width: u32, img_height: u32, wm_width: u32, wm_height: u32) -> Result<(u32, u32), String> {
+    let (x, y) = single_position(config, img_width, img_height, wm_width, wm_height);
+    if config.clamp_to_bounds.unwrap_or(false) {
+        return Ok((
+            x.min(img_width.saturating_sub(wm_width)),
+            y.min(img_height.saturating_sub(wm_height)),
+        ));
+    }
+    if x >= img_width || y >= img_height {
+        return Err(format!(
+            "Watermark at ({}, {}) lies entirely outside the {}x{} image; adjust x_offset/y_offset or set clamp_to_bounds",
+            x, y, img_width, img_height
+        ));
+    }
+    Ok((x, y))
+}
+
+// 平铺网格中每个水印的 (行, 列, x, y)
+// 坐标直接由整数行列号乘以间距得到，不做浮点累加，大图上网格间距始终一致不会漂移
+fn tile_positions(
+    start_x: i64,
+    start_y: i64,
+    spacing_x: u32,
+    spacing_y: u32,
+    img_width: u32,
+    img_height: u32,
+) -> impl Iterator<Item = (i64, i64, i64, i64)> {
+    // 起始位置可为负（从左/上边缘外开始），按剩余长度计算行列数
+    let count = |length: u32, start: i64, spacing: u32| (length as i64 - start).max(0).unsigned_abs().div_ceil(spacing as u64) as i64;
+    let rows = count(img_height, start_y, spacing_y);
+    let cols = count(img_width, start_x, spacing_x);
+    (0..rows).flat_map(move |row| {
+        (0..cols).map(move |col| {
+            let x = start_x + col * spacing_x as i64;
+            let y = start_y + row * spacing_y as i64;
+            (row, col, x, y)
+        })
+    })
+}
+
+// 旋转平铺网格：以 (start_x, start_y) 为原点，沿旋转 angle 度后的两个方向按间距排列
+// 网格向图片外延伸，保留所有与图片有交集的位置（边缘水印由叠加时裁切），45° 时四角也不会留空
+fn rotated_tile_positions(
+    origin: (i64, i64),
+    spacing: (u32, u32),
+    angle: f32,
+    watermark: (u32, u32),
+    image: (u32, u32),
+) -> Vec<(i64, i64, i64, i64)> {
+    let (origin_x, origin_y) = (origin.0 as f64, origin.1 as f64);
+    let (spacing_x, spacing_y) = (spacing.0 as f64, spacing.1 as f64);
+    let (wm_width, wm_height) = (watermark.0 as i64, watermark.1 as i64);
+    let (img_width, img_height) = (image.0 as i64, image.1 as i64);
+    let (sin, cos) = (angle as f64).to_radians().sin_cos();
+    
+    // 水印左上角落在 (-wm_width, img_width) x (-wm_height, img_height) 内才与图片相交，
+    // 把该区域四角投影到网格坐标，得到需要遍历的行列范围
+    let corners = [
+        (-wm_width, -wm_height),
+        (img_width, -wm_height),
+        (-wm_width, img_height),
+        (img_width, img_height),
+    ];
+    let (mut col_min, mut col_max, mut row_min, mut row_max) = (f64::MAX, f64::MIN, f64::MAX, f64::MIN);
+    for (x, y) in corners {
+        let (dx, dy) = (x as f64 - origin_x, y as f64 - origin_y);
+        let col = (dx * cos + dy * sin) / spacing_x;
+        let row = (dy * cos - dx * sin) / spacing_y;
+        col_min = col_min.min(col);
+        col_max = col_max.max(col);
+        row_min = row_min.min(row);
+        row_max = row_max.max(row);
+    }
+    
+    let mut positions = Vec::new();
+    for row in row_min.floor() as i64..=row_max.ceil() as i64 {
+        for col in col_min.floor() as i64..=col_max.ceil() as i64 {
+            let (u, v) = (col as f64 * spacing_x, row as f64 * spacing_y);
+            let x = (origin_x + u * cos - v * sin).round() as i64;
+            let y = (origin_y + u * sin + v * cos).round() as i64;
+            if x > -wm_width && x < img_width && y > -wm_height && y < img_height {
+                positions.push((row, col, x, y));
+            }
+        }
+    }
+    positions
+}
+
+// 按配置生成平铺位置：设置了 tile_rows/tile_cols 时使用有限的水印块，否则使用常规平铺网格；设置了 jitter 时再随机抖动
+fn tile_grid(config: &WatermarkConfig, watermark: &RgbaImage, img_width: u32, img_height: u32) -> Result<Vec<(i64, i64, i64, i64)>, String> {
+    let (start_x, start_y, spacing_x, spacing_y) = tile_layout(config, watermark, img_width, img_height)?;
+    let mut positions = if config.tile_rows.is_some() || config.tile_cols.is_some() {
+        tile_block_positions(config, (spacing_x, spacing_y), watermark.dimensions(), (img_width, img_height))
+    } else {
+        grid_positions(config, (start_x, start_y), (spacing_x, spacing_y), watermark, (img_width, img_height))
+    };
+    
+    // 随机抖动：每个位置的随机数只由种子和行列号决定，与遍历顺序、方差筛选无关
+    if let Some(jitter) = config.jitter.filter(|&jitter| jitter > 0.0) {
+        let seed = config.seed.unwrap_or(0);
+        for (row, col, x, y) in positions.iter_mut() {
+            let mut rng = tile_rng(seed, TILE_RNG_POSITION, *row, *col);
+            *x += (rng.next_signed() * jitter as f64 * spacing_x as f64).round() as i64;
+            *y += (rng.next_signed() * jitter as f64 * spacing_y as f64).round() as i64;
+        }
+    }
+    
+    Ok(positions)
+}
+
+// 常规平铺网格：设置了 tile_angle 时为旋转网格，否则为从起始位置向右下排列的矩形网格
+fn grid_positions(
+    config: &WatermarkConfig,
+    start: (i64, i64),
+    spacing: (u32, u32),
+    watermark: &RgbaImage,
+    image: (u32, u32),
+) -> Vec<(i64, i64, i64, i64)> {
+    let ((start_x, start_y), (spacing_x, spacing_y), (img_width, img_height)) = (start, spacing, image);
+    match config.tile_angle {
+        Some(angle) if angle != 0.0 => rotated_tile_positions(
+            (start_x, start_y),
+            (spacing_x, spacing_y),
+            angle,
+            watermark.dimensions(),
+            (img_width, img_height),
+        ),
+        _ => tile_positions(start_x, start_y, spacing_x, spacing_y, img_width, img_height).collect(),
+    }
+}
+
+// SplitMix64 伪随机数生成器：状态只有一个 u64，相同种子得到相同序列（用于可复现的抖动，不需要密码学强度）
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+    
+    // [-1, 1) 内均匀分布的随机数（取高 53 位作为尾数）
+    fn next_signed(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+    }
+}
+
+// 平铺随机数的用途编号：位置抖动与旋转抖动使用互不相关的序列
+const TILE_RNG_POSITION: u64 = 0;
+const TILE_RNG_ROTATION: u64 = 1;
+
+// 平铺网格中 (row, col) 位置的随机数生成器，由种子、用途编号和行列号混合得到初始状态
+fn tile_rng(seed: u64, stream: u64, row: i64, col: i64) -> SplitMix64 {
+    let mut rng = SplitMix64(
+        seed
+            ^ stream.wrapping_mul(0xE703_7ED1_A0B4_28DB)
+            ^ (row as u64).wrapping_mul(0xD6E8_FEB8_6659_FD93)
+            ^ (col as u64).wrapping_mul(0xA076_1D64_78BD_642F),
+    );
+    // 先丢弃一个输出，让相邻行列的初始状态充分打散
+    rng.next_u64();
+    rng
+}
+
+// tile_rows/tile_cols 的上限
+const MAX_TILE_BLOCKS: u32 = 1000;
+
+// 有限的平铺水印块：共 tile_rows × tile_cols 个，未设置的一方按图片尺寸铺满
+// 整个水印块按 position 锚点定位（默认居中），设置了 position 时再叠加偏移量；
+// 只返回（含抖动范围）与图片相交的水印块
+fn tile_block_positions(
+    config: &WatermarkConfig,
+    spacing: (u32, u32),
+    watermark: (u32, u32),
+    image: (u32, u32),
+) -> Vec<(i64, i64, i64, i64)> {
+    let (spacing_x, spacing_y) = spacing;
+    let (wm_width, wm_height) = watermark;
+    let (img_width, img_height) = image;
+    let rows = config.tile_rows.unwrap_or(img_height.div_ceil(spacing_y).max(1));
+    let cols = config.tile_cols.unwrap_or(img_width.div_ceil(spacing_x).max(1));
+    // 间距很大时块尺寸可能超出 u32，饱和到 u32::MAX（此时绝大部分水印块都在图片外）
+    let block_width = (cols - 1).saturating_mul(spacing_x).saturating_add(wm_width);
+    let block_height = (rows - 1).saturating_mul(spacing_y).saturating_add(wm_height);
+    
+    let (origin_x, origin_y) = match &config.position {
+        Some(position) => {
+            let (x_offset, y_offset) = placement_offsets(config);
+            let (x, y) = anchor_position(position, img_width, img_height, block_width, block_height);
+            (x + x_offset as i64, y + y_offset as i64)
+        }
+        None => anchor_position("center", img_width, img_height, block_width, block_height),
+    };
+    
+    // 抖动最多偏移 jitter × 间距，按此放宽相交判断
+    let jitter = config.jitter.unwrap_or(0.0) as f64;
+    let margin_x = (jitter * spacing_x as f64).ceil() as i64;
+    let margin_y = (jitter * spacing_y as f64).ceil() as i64;
+    let visible = |start: i64, size: u32, margin: i64, limit: u32| {
+        start + size as i64 + margin > 0 && start - margin < limit as i64
+    };
+    
+    (0..rows as i64)
+        .map(|row| (row, origin_y + row * spacing_y as i64))
+        .filter(|&(_, y)| visible(y, wm_height, margin_y, img_height))
+        .flat_map(|(row, y)| {
+            (0..cols as i64)
+                .map(move |col| (row, col, origin_x + col * spacing_x as i64, y))
+                .filter(|&(_, _, x, _)| visible(x, wm_width, margin_x, img_width))
+        })
+        .collect()
+}
+
+// 所有已放置的平铺水印都被边缘裁切时，返回额外放置在中心的位置
+fn full_tile_fallback(placed: &[(i64, i64)], img_width: u32, img_height: u32, wm_width: u32, wm_height: u32) -> Option<(i64, i64)> {
+    let has_full = placed.iter().any(|&(x, y)| {
+        x >= 0 && y >= 0 && x + wm_width as i64 <= img_width as i64 && y + wm_height as i64 <= img_height as i64
+    });
+    if has_full {
+        return None;
+    }
+    let (x, y) = anchor_position("center", img_width, img_height, wm_width, wm_height);
+    Some((x.max(0), y.max(0)))
+}
+
+// 平铺网格：返回 (起始 x, 起始 y, 横向间距, 纵向间距)
+fn tile_layout(config: &WatermarkConfig, watermark: &RgbaImage, img_width: u32, img_height: u32) -> Result<(i64, i64, u32, u32), String> {
+    let (x_offset, y_offset) = placement_offsets(config);
+    let (wm_width, wm_height) = watermark.dimensions();
+    let (spacing_x, spacing_y) = match config.coverage_percent {
+        Some(coverage) => coverage_spacing(watermark, img_width, img_height, coverage)?,
+        // 未设置空隙时沿用以偏移量作为空隙的旧行为
+        None => (
+            config.tile_spacing_x.unwrap_or(wm_width + config.tile_margin_x.unwrap_or(x_offset.unsigned_abs())),
+            config.tile_spacing_y.unwrap_or(wm_height + config.tile_margin_y.unwrap_or(y_offset.unsigned_abs())),
+        ),
+    };
+    
+    // 计算起始位置：正偏移从图片内开始，负偏移从左/上边缘外开始（不超过一个间距），
+    // 边缘水印在叠加时裁切，与右/下边缘超出图片的水印对称
+    let start = |offset: i32, spacing: u32| if offset >= 0 {
+        offset as i64
+    } else {
+        -((offset.unsigned_abs() % spacing) as i64)
+    };
+    
+    Ok((start(x_offset, spacing_x), start(y_offset, spacing_y), spacing_x, spacing_y))
+}
+
+// 应用水印（统一的实现，消除重复代码）
+fn apply_watermark(
+    img: &mut DynamicImage,
+    config: &WatermarkConfig,
+) -> Result<Vec<WatermarkPlacement>, WatermarkError> {
+    log_at(LOG_DEBUG, || "开始应用水印".to_string());
+    
+    // 验证配置
+    validate_config(config).map_err(|e| WatermarkError::with_code(ErrorCode::InvalidConfig, e))?;
+    
+    let config = &*resolve_relative_config(config, img.width(), img.height());
+    let watermark_rgba = prepare_watermark(img, config)?;
+    Ok(composite_watermark(img, config, &watermark_rgba)?)
+}
+
+// 把百分比形式的偏移和尺寸按底图尺寸换算为像素，同时设置了像素值时以百分比为准
+// 没有百分比参数时直接借用原配置，不做克隆
+fn resolve_relative_config(config: &WatermarkConfig, img_width: u32, img_height: u32) -> Cow<'_, WatermarkConfig> {
+    if config.x_offset_pct.is_none() && config.y_offset_pct.is_none()
+        && config.width_pct.is_none() && config.height_pct.is_none() {
+        return Cow::Borrowed(config);
+    }
+    
+    let warn_override = |name: &str, absolute_set: bool, pct: Option<f32>| {
+        if absolute_set && pct.is_some() {
+            log_at(LOG_DEBUG, || format!("{} 与 {}_pct 同时设置，使用百分比", name, name));
+        }
+    };
+    warn_override("x_offset", config.x_offset.is_some(), config.x_offset_pct);
+    warn_override("y_offset", config.y_offset.is_some(), config.y_offset_pct);
+    warn_override("width", config.width.is_some(), config.width_pct);
+    warn_override("height", config.height.is_some(), config.height_pct);
+    
+    let offset = |pct: f32, length: u32| (pct / 100.0 * length as f32).round() as i32;
+    let size = |pct: f32, length: u32| ((pct / 100.0 * length as f32).round() as u32).max(1);
+    let mut resolved = config.clone();
+    if let Some(pct) = config.x_offset_pct {
+        resolved.x_offset = Some(offset(pct, img_width));
+    }
+    if let Some(pct) = config.y_offset_pct {
+        resolved.y_offset = Some(offset(pct, img_height));
+    }
+    if let Some(pct) = config.width_pct {
+        resolved.width = Some(size(pct, img_width));
+    }
+    if let Some(pct) = config.height_pct {
+        resolved.height = Some(size(pct, img_height));
+    }
+    Cow::Owned(resolved)
+}
+
+// 加载并准备水印图片（自水印由底图生成）
+fn prepare_watermark(img: &DynamicImage, config: &WatermarkConfig) -> Result<RgbaImage, WatermarkError> {
+    match &config.self_watermark {
+        Some(self_watermark) => Ok(self_watermark_image(img, self_watermark, resize_filter(config))),
+        None => load_and_prepare_watermark(config),
+    }
+}
+
+// 将已准备好的水印按配置叠加到底图，返回每个水印的叠加区域
+fn composite_watermark(
+    img: &mut DynamicImage,
+    config: &WatermarkConfig,
+    watermark_rgba: &RgbaImage,
+) -> Result<Vec<WatermarkPlacement>, String> {
+    // 主体平铺：把主体范围裁出来单独平铺，再贴回原位，超出主体范围的部分不会绘制
+    if config.subject_tile.unwrap_or(false) {
+        let (x, y, width, height) = alpha_bounding_box(&img.to_rgba8())
+            .ok_or("subject_tile requires a base image with visible pixels")?;
+        let mut subject = img.crop_imm(x, y, width, height);
+        let subject_config = WatermarkConfig {
+            subject_tile: None,
+            tile: Some(true),
+            ..config.clone()
+        };
+        let placements = composite_watermark(&mut subject, &subject_config, watermark_rgba)?;
+        
+        let mut target_rgba = img.to_rgba8();
+        image::imageops::replace(&mut target_rgba, &subject.to_rgba8(), x as i64, y as i64);
+        *img = DynamicImage::ImageRgba8(target_rgba);
+        // 主体内的坐标换算回整张底图
+        return Ok(placements.into_iter()
+            .map(|placement| WatermarkPlacement { x: placement.x + x, y: placement.y + y, ..placement })
+            .collect());
+    }
+    
+    // 不透明度热力图缩放到底图尺寸
+    let opacity_map = match &config.opacity_map_data {
+        Some(data) => Some(
+            decode_watermark_image(data)
+                .map_err(|e| format!("Invalid opacity_map_data: {}", e))?
+                .resize_exact(img.width(), img.height(), image::imageops::FilterType::Triangle)
+                .to_luma8(),
+        ),
+        None => None,
+    };
+    
+    // 设置了裁剪多边形或热力图时保留原图，叠加完成后按逐像素权重与原图混合
+    let composite_original = (config.clip_polygon.is_some() || opacity_map.is_some()).then(|| img.to_rgba8());
+    
+    // 获取参数
+    let transparency = match &config.self_watermark {
+        Some(self_watermark) => self_watermark.strength.unwrap_or(0.1),
+        None => config.transparency.unwrap_or(0.5),
+    };
+    let tile = config.tile.unwrap_or(false) || config.coverage_percent.is_some() || config.self_watermark.is_some();
+    let blend = BlendMode::from_config(config)?;
+    
+    log_at(LOG_DEBUG, || format!("水印参数: 透明度={}, 平铺={}, 混合模式={:?}", transparency, tile, blend));
+    
+    let (img_width, img_height) = img.dimensions();
+    let (wm_width, wm_height) = watermark_rgba.dimensions();
+    
+    log_at(LOG_DEBUG, || format!("原始图片尺寸: {}x{}, 水印尺寸: {}x{}",
+        img_width, img_height, wm_width, wm_height));
+    
+    // 投影扩展了水印画布：定位和叠加区域按水印本身计算，叠加时画布向左上移动投影边距，只有阴影超出配置的位置
+    let (shadow_left, shadow_top, shadow_right, shadow_bottom) = shadow_margins(config);
+    let logo_size = |watermark: &RgbaImage| (
+        watermark.width().saturating_sub(shadow_left + shadow_right),
+        watermark.height().saturating_sub(shadow_top + shadow_bottom),
+    );
+    let canvas_position = |x: i64, y: i64| (x - shadow_left as i64, y - shadow_top as i64);
+    
+    let mut placements = Vec::new();
+    if let Some(border) = &config.border_watermark {
+        // 边框水印：取代常规的单个/平铺放置
+        placements = draw_border_watermark(rgba8_in_place(img), watermark_rgba, border, transparency, resize_filter(config), blend)?;
+    } else if tile {
+        // 平铺水印 - 优化版本：只转换一次目标图片
+        let positions = tile_grid(config, watermark_rgba, img_width, img_height)?;
+        
+        // 棋盘格交替的第二个水印，与主水印使用相同的缩放、旋转等参数，只准备一次
+        let alternate_config = config.tile_alternate_image_data.as_ref().map(|alternate| WatermarkConfig {
+            image_data: Some(alternate.clone()),
+            watermark_key: None,
+            watermark_bytes: None,
+            fallback_image_data: None,
+            ..config.clone()
+        });
+        let alternate_rgba = alternate_config.as_ref().map(load_and_prepare_watermark).transpose()?;
+        
+        // 逐个随机旋转：主水印（和交替水印）先各准备一份未旋转的版本，每个位置再单独旋转
+        let rotation_jitter = config.rotation_jitter.filter(|&jitter| jitter > 0.0);
+        let unrotated = match rotation_jitter {
+            Some(_) => Some((
+                load_unrotated_watermark(config)?,
+                alternate_config.as_ref().map(load_unrotated_watermark).transpose()?,
+            )),
+            None => None,
+        };
+        
+        // 就地叠加到底图的 RGBA8 缓冲区（最多转换一次，不复制整张图）
+        let target_rgba = rgba8_in_place(img);
+        let transparency = if config.ensure_visible.unwrap_or(false) {
+            let points: Vec<_> = positions.iter().map(|&(_, _, x, y)| canvas_position(x, y)).collect();
+            visible_transparency(target_rgba, watermark_rgba, &points, transparency, config.ensure_visible_max.unwrap_or(1.0))
+        } else {
+            transparency
+        };
+        
+        // 自动对比色基于叠加前的原图取样，避免受相邻已叠加水印的影响
+        let contrast_source = config.auto_contrast.unwrap_or(false).then(|| target_rgba.clone());
+        
+        // 按局部方差筛选平铺位置（基于叠加前的原图计算）
+        let variance_filter = config.tile_variance_threshold.map(|threshold| {
+            let place_above = config.tile_variance_polarity.as_deref() != Some("below");
+            (VarianceMap::new(target_rgba), threshold as f64, place_above)
+        });
+        
+        let mut placed = Vec::with_capacity(positions.len());
+        for (row, col, x, y) in positions {
+            if let Some((map, threshold, place_above)) = &variance_filter {
+                let (cx, cy) = canvas_position(x, y);
+                let variance = map.variance(cx, cy, wm_width, wm_height);
+                if (variance >= *threshold) != *place_above {
+                    continue;
+                }
+            }
+            let use_alternate = alternate_rgba.is_some() && (row + col).rem_euclid(2) == 1;
+            let tile_watermark = match &alternate_rgba {
+                Some(alternate) if use_alternate => alternate,
+                _ => watermark_rgba,
+            };
+            // 单独旋转后的水印尺寸与网格计算用的外接矩形不同，保持两者中心重合
+            let (tile_watermark, x, y) = match (&unrotated, rotation_jitter) {
+                (Some((main, alternate)), Some(jitter)) => {
+                    let source = alternate.as_ref().filter(|_| use_alternate).unwrap_or(main);
+                    let rotated = rotation_jittered_watermark(source, config, jitter, (row, col))?;
+                    let dx = (wm_width as i64 - rotated.width() as i64) / 2;
+                    let dy = (wm_height as i64 - rotated.height() as i64) / 2;
+                    (Cow::Owned(rotated), x + dx, y + dy)
+                }
+                _ => (Cow::Borrowed(tile_watermark), x, y),
+            };
+            let (cx, cy) = canvas_position(x, y);
+            overlay_image_rgba_with_transparency(target_rgba, &contrasted_watermark(contrast_source.as_ref(), &tile_watermark, cx, cy), cx, cy, transparency, blend);
+            placed.push((x, y));
+            placements.extend(WatermarkPlacement::clipped(x, y, logo_size(&tile_watermark), img_width, img_height));
+        }
+        
+        if config.tile_ensure_full.unwrap_or(false) {
+            let (logo_width, logo_height) = logo_size(watermark_rgba);
+            if let Some((x, y)) = full_tile_fallback(&placed, img_width, img_height, logo_width, logo_height) {
+                let (cx, cy) = canvas_position(x, y);
+                overlay_image_rgba_with_transparency(target_rgba, &contrasted_watermark(contrast_source.as_ref(), watermark_rgba, cx, cy), cx, cy, transparency, blend);
+                placements.extend(WatermarkPlacement::clipped(x, y, (logo_width, logo_height), img_width, img_height));
+            }
+        }
+    } else {
+        // 单个水印
+        let (logo_width, logo_height) = logo_size(watermark_rgba);
+        let (x, y) = bounded_single_position(config, img_width, img_height, logo_width, logo_height)?;
+        let (x, y) = (x as i64, y as i64);
+        let (cx, cy) = canvas_position(x, y);
+        // 与平铺相同，就地叠加到底图的 RGBA8 缓冲区；角落的小水印不再为整张底图复制一份
+        let target_rgba = rgba8_in_place(img);
+        let transparency = if config.ensure_visible.unwrap_or(false) {
+            visible_transparency(target_rgba, watermark_rgba, &[(cx, cy)], transparency, config.ensure_visible_max.unwrap_or(1.0))
+        } else {
+            transparency
+        };
+        // 单个水印叠加前底图尚未改动，自动对比色直接在底图上取样
+        let contrast_source = config.auto_contrast.unwrap_or(false).then_some(&*target_rgba);
+        let watermark = contrasted_watermark(contrast_source, watermark_rgba, cx, cy);
+        overlay_image_rgba_with_transparency(target_rgba, &watermark, cx, cy, transparency, blend);
+        placements.extend(WatermarkPlacement::clipped(x, y, (logo_width, logo_height), img_width, img_height));
+    }
+    
+    if let Some(original) = composite_original {
+        let result = rgba8_in_place(img);
+        if let Some(polygon) = &config.clip_polygon {
+            apply_clip_polygon(result, &original, polygon);
+        }
+        // 叠加结果与原图的差值和水印 alpha 成正比，按权重混合等价于逐像素缩放水印不透明度
+        if let Some(map) = &opacity_map {
+            mix_with_original(result, &original, |x, y| map.get_pixel(x, y)[0] as f32 / 255.0);
+        }
+    }
+    
+    Ok(placements)
+}
+
+// 可感知的最小平均亮度差（0-255）
+const MIN_VISIBLE_DELTA: f32 = 12.0;
+
+// 估算水印在给定位置叠加后的平均亮度变化，不足 MIN_VISIBLE_DELTA 时提高不透明度（不超过 max）
+fn visible_transparency(
+    target: &RgbaImage,
+    watermark: &RgbaImage,
+    positions: &[(i64, i64)],
+    transparency: f32,
+    max: f32,
+) -> f32 {
+    let luminance = |p: &image::Rgba<u8>| 0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32;
+    let (target_width, target_height) = target.dimensions();
+    
+    // 以水印 alpha 加权的平均亮度差（不含透明度）
+    let mut weighted_delta = 0.0f64;
+    let mut weight = 0.0f64;
+    for &(x, y) in positions {
+        for (wx, wy, wm_pixel) in watermark.enumerate_pixels() {
+            let (tx, ty) = (x + wx as i64, y + wy as i64);
+            if tx < 0 || ty < 0 || tx >= target_width as i64 || ty >= target_height as i64 || wm_pixel[3] == 0 {
+                continue;
+            }
+            let alpha = wm_pixel[3] as f64 / 255.0;
+            let delta = (luminance(wm_pixel) - luminance(target.get_pixel(tx as u32, ty as u32))).abs() as f64;
+            weighted_delta += alpha * delta;
+            weight += alpha;
+        }
+    }
+    if weight == 0.0 {
+        return transparency;
+    }
+    
+    let mean_delta = (weighted_delta / weight) as f32;
+    if mean_delta * transparency >= MIN_VISIBLE_DELTA {
+        return transparency;
+    }
+    // 颜色与背景完全相同时无法通过不透明度补救，直接提高到上限
+    let needed = if mean_delta > 0.0 { MIN_VISIBLE_DELTA / mean_delta } else { max };
+    needed.min(max).max(transparency)
+}
+
+// 自动对比色的取样半径（像素）：按水印像素周围 (2r+1)×(2r+1) 窗口内的底图平均亮度决定黑白
+const AUTO_CONTRAST_RADIUS: i64 = 8;
+
+// 设置了 auto_contrast（source 为叠加前的底图）时返回重新着色的水印，否则直接借用原水印
+fn contrasted_watermark<'a>(source: Option<&RgbaImage>, watermark: &'a RgbaImage, x: i64, y: i64) -> Cow<'a, RgbaImage> {
+    match source {
+        Some(source) => Cow::Owned(auto_contrast_watermark(source, watermark, x, y)),
+        None => Cow::Borrowed(watermark),
+    }
+}
+
+// 把放在 (x, y) 的水印按下方的局部背景亮度重新着色：暗背景处为白色，亮背景处为黑色，保留 alpha
+// 预先对水印覆盖区域（向外扩展取样半径）的底图亮度求积分图，逐像素的窗口平均亮度为 O(1)
+fn auto_contrast_watermark(target: &RgbaImage, watermark: &RgbaImage, x: i64, y: i64) -> RgbaImage {
+    let (target_width, target_height) = target.dimensions();
+    let (wm_width, wm_height) = watermark.dimensions();
+    let x0 = (x - AUTO_CONTRAST_RADIUS).clamp(0, target_width as i64);
+    let y0 = (y - AUTO_CONTRAST_RADIUS).clamp(0, target_height as i64);
+    let x1 = (x + wm_width as i64 + AUTO_CONTRAST_RADIUS).clamp(0, target_width as i64);
+    let y1 = (y + wm_height as i64 + AUTO_CONTRAST_RADIUS).clamp(0, target_height as i64);
+    let mut result = watermark.clone();
+    if x0 >= x1 || y0 >= y1 {
+        return result;
+    }
+    
+    // integral[(row + 1) * stride + col + 1] 为区域内 (0,0)-(col,row) 的亮度和
+    let (region_width, region_height) = ((x1 - x0) as usize, (y1 - y0) as usize);
+    let stride = region_width + 1;
+    let mut integral = vec![0.0f64; stride * (region_height + 1)];
+    for row in 0..region_height {
+        let mut row_sum = 0.0;
+        for col in 0..region_width {
+            let p = target.get_pixel((x0 as usize + col) as u32, (y0 as usize + row) as u32);
+            row_sum += 0.299 * p[0] as f64 + 0.587 * p[1] as f64 + 0.114 * p[2] as f64;
+            integral[(row + 1) * stride + col + 1] = integral[row * stride + col + 1] + row_sum;
+        }
+    }
+    
+    for (wx, wy, pixel) in result.enumerate_pixels_mut() {
+        if pixel[3] == 0 {
+            continue;
+        }
+        // 取样窗口转换到积分图坐标并裁剪到区域内
+        let (tx, ty) = (x + wx as i64 - x0, y + wy as i64 - y0);
+        let left = (tx - AUTO_CONTRAST_RADIUS).clamp(0, region_width as i64) as usize;
+        let top = (ty - AUTO_CONTRAST_RADIUS).clamp(0, region_height as i64) as usize;
+        let right = (tx + AUTO_CONTRAST_RADIUS + 1).clamp(0, region_width as i64) as usize;
+        let bottom = (ty + AUTO_CONTRAST_RADIUS + 1).clamp(0, region_height as i64) as usize;
+        if left >= right || top >= bottom {
+            continue;
+        }
+        let sum = integral[bottom * stride + right] - integral[top * stride + right]
+            - integral[bottom * stride + left] + integral[top * stride + left];
+        let mean = sum / ((right - left) * (bottom - top)) as f64;
+        let value = if mean < 128.0 { 255 } else { 0 };
+        pixel[0] = value;
+        pixel[1] = value;
+        pixel[2] = value;
+    }
+    
+    result
+}
+
+// 判断点是否在多边形内部（奇偶规则）
+fn point_in_polygon(px: f32, py: f32, polygon: &[[i32; 2]]) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len() - 1;
+    for i in 0..polygon.len() {
+        let (xi, yi) = (polygon[i][0] as f32, polygon[i][1] as f32);
+        let (xj, yj) = (polygon[j][0] as f32, polygon[j][1] as f32);
+        if (yi > py) != (yj > py) && px < (xj - xi) * (py - yi) / (yj - yi) + xi {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+// 按多边形遮罩混合叠加结果与原图：多边形外还原为原图，边缘像素使用 4x4 超采样覆盖率抗锯齿
+fn apply_clip_polygon(result: &mut RgbaImage, original: &RgbaImage, polygon: &[[i32; 2]]) {
+    const SAMPLES: u32 = 4;
+    let (width, height) = result.dimensions();
+    
+    // 只在多边形包围盒内逐像素计算覆盖率，包围盒外直接还原
+    let min_x = polygon.iter().map(|p| p[0]).min().unwrap_or(0).clamp(0, width as i32) as u32;
+    let max_x = polygon.iter().map(|p| p[0]).max().unwrap_or(0).clamp(0, width as i32) as u32;
+    let min_y = polygon.iter().map(|p| p[1]).min().unwrap_or(0).clamp(0, height as i32) as u32;
+    let max_y = polygon.iter().map(|p| p[1]).max().unwrap_or(0).clamp(0, height as i32) as u32;
+    
+    mix_with_original(result, original, |x, y| {
+        if x < min_x || x >= max_x || y < min_y || y >= max_y {
+            return 0.0;
+        }
+        let mut hits = 0;
+        for sy in 0..SAMPLES {
+            for sx in 0..SAMPLES {
+                let px = x as f32 + (sx as f32 + 0.5) / SAMPLES as f32;
+                let py = y as f32 + (sy as f32 + 0.5) / SAMPLES as f32;
+                if point_in_polygon(px, py, polygon) {
+                    hits += 1;
+                }
+            }
+        }
+        hits as f32 / (SAMPLES * SAMPLES) as f32
+    });
+}
+
+// 按逐像素权重混合叠加结果与原图：权重 1 保留叠加结果，0 还原为原图
+fn mix_with_original(result: &mut RgbaImage, original: &RgbaImage, weight: impl Fn(u32, u32) -> f32) {
+    for (x, y, pixel) in result.enumerate_pixels_mut() {
+        let weight = weight(x, y);
+        if weight >= 1.0 {
+            continue;
+        }
+        let orig = original.get_pixel(x, y);
+        for c in 0..4 {
+            pixel[c] = (orig[c] as f32 + (pixel[c] as f32 - orig[c] as f32) * weight).round() as u8;
+        }
+    }
+}
+
+// 生成两张同尺寸图片的差异图：各通道取绝对差并放大，输出不透明图片（未变化处为黑色）
+fn difference_image(original: &RgbaImage, result: &RgbaImage, amplification: f32) -> RgbaImage {
+    RgbaImage::from_fn(original.width(), original.height(), |x, y| {
+        let a = original.get_pixel(x, y);
+        let b = result.get_pixel(x, y);
+        // alpha 的变化也计入各颜色通道，保证透明底图上的水印同样可见
+        let alpha_diff = a[3].abs_diff(b[3]) as f32;
+        let channel = |c: usize| {
+            let diff = (a[c].abs_diff(b[c]) as f32).max(alpha_diff);
+            (diff * amplification).min(255.0) as u8
+        };
+        image::Rgba([channel(0), channel(1), channel(2), 255])
+    })
+}
+
+// 边缘去晕：半透明像素按 (1 - alpha) 的比例向去晕颜色混合，完全透明和完全不透明的像素保持不变
+fn matte_edges(img: &mut RgbaImage, color: [u8; 3], strength: f32) {
+    for pixel in img.pixels_mut() {
+        let alpha = pixel[3];
+        if alpha == 0 || alpha == 255 {
+            continue;
+        }
+        
+        let t = strength * (1.0 - alpha as f32 / 255.0);
+        for c in 0..3 {
+            pixel[c] = (pixel[c] as f32 * (1.0 - t) + color[c] as f32 * t).round() as u8;
+        }
+    }
+}
+
+// 计算非透明像素的外接矩形 (x, y, width, height)，图片完全透明时返回 None
+fn alpha_bounding_box(img: &RgbaImage) -> Option<(u32, u32, u32, u32)> {
+    let mut min_x = u32::MAX;
+    let mut min_y = u32::MAX;
+    let mut max_x = 0;
+    let mut max_y = 0;
+    
+    for (x, y, pixel) in img.enumerate_pixels() {
+        if pixel[3] > 0 {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+    }
+    
+    (min_x != u32::MAX).then(|| (min_x, min_y, max_x - min_x + 1, max_y - min_y + 1))
+}
+
+// 输出前的后处理（作用于最终的整张图片）
+fn postprocess_output(img: &mut DynamicImage, config: &WatermarkConfig) -> Result<(), String> {
+    // 自动裁剪透明边框（完全透明的图片保持原样）
+    if config.autocrop.unwrap_or(false) {
+        if let Some((x, y, width, height)) = alpha_bounding_box(&img.to_rgba8()) {
+            *img = img.crop_imm(x, y, width, height);
+        }
+    }
+    
+    if let Some(color) = &config.edge_matte_color {
+        let color = parse_hex_color(color)?;
+        let strength = config.edge_matte_strength.unwrap_or(0.5);
+        let mut rgba = img.to_rgba8();
+        matte_edges(&mut rgba, color, strength);
+        *img = DynamicImage::ImageRgba8(rgba);
+    }
+    
+    // 强制输出 RGB（拍平之后不再有透明度）
+    if config.force_rgb.unwrap_or(false) {
+        *img = DynamicImage::ImageRgb8(flatten_alpha(&img.to_rgba8(), flatten_background(config)?));
+    }
+    
+    // 预乘 alpha 放在最后且只在这里执行一次，避免重复预乘；没有 alpha 通道时无需处理
+    if config.premultiply_output.unwrap_or(false) && img.color().has_alpha() {
+        let mut rgba = img.to_rgba8();
+        premultiply_alpha(&mut rgba);
+        *img = DynamicImage::ImageRgba8(rgba);
+    }
+    
+    Ok(())
+}
+
+// 添加文字水印（现在使用客户端渲染的图片）
+fn add_text_watermark(
+    img: &mut DynamicImage,
+    config: &WatermarkConfig,
+) -> Result<Vec<WatermarkPlacement>, WatermarkError> {
+    // 图片数据（客户端渲染的文字图片）或原生渲染的 text 由 validate_config 检查
+    apply_watermark(img, config)
+}
+
+// 添加图片水印
+fn add_image_watermark(
+    img: &mut DynamicImage,
+    config: &WatermarkConfig,
+) -> Result<Vec<WatermarkPlacement>, WatermarkError> {
+    apply_watermark(img, config)
+}
+
+// 生成与栅格化结果位置一致的 SVG 水印片段（<g> 元素），水印以未旋转的 PNG 嵌入并通过 transform 旋转
+fn render_watermark_svg(image_width: u32, image_height: u32, config: &WatermarkConfig) -> Result<String, WatermarkError> {
+    validate_config(config).map_err(|e| WatermarkError::with_code(ErrorCode::InvalidConfig, e))?;
+    let config = &*resolve_relative_config(config, image_width, image_height);
+    if config.watermark_type == WatermarkType::Invisible {
+        return Err(WatermarkError::with_code(ErrorCode::InvalidConfig, "Invisible watermarks have no SVG representation"));
+    }
+    if config.border_watermark.is_some() {
+        return Err(WatermarkError::with_code(ErrorCode::InvalidConfig, "border_watermark is not supported in SVG output"));
+    }
+    if config.self_watermark.is_some() {
+        return Err(WatermarkError::with_code(ErrorCode::InvalidConfig, "self_watermark is not supported in SVG output"));
+    }
+    if config.subject_tile.is_some() {
+        return Err(WatermarkError::with_code(ErrorCode::InvalidConfig, "subject_tile requires pixel data and is not supported in SVG output"));
+    }
+    if config.tile_variance_threshold.is_some() {
+        return Err(WatermarkError::with_code(ErrorCode::InvalidConfig, "tile_variance_threshold requires pixel data and is not supported in SVG output"));
+    }
+    if config.corner_opacities.is_some() {
+        return Err(WatermarkError::with_code(ErrorCode::InvalidConfig, "corner_opacities is not supported in SVG output"));
+    }
+    if config.gradient.as_deref().is_some_and(|g| g != "none") {
+        return Err(WatermarkError::with_code(ErrorCode::InvalidConfig, "gradient is not supported in SVG output"));
+    }
+    if config.shadow.unwrap_or(false) {
+        return Err(WatermarkError::with_code(ErrorCode::InvalidConfig, "shadow is not supported in SVG output"));
+    }
+    if config.tile_alternate_image_data.is_some() {
+        return Err(WatermarkError::with_code(ErrorCode::InvalidConfig, "tile_alternate_image_data is not supported in SVG output"));
+    }
+    if config.opacity_map_data.is_some() {
+        return Err(WatermarkError::with_code(ErrorCode::InvalidConfig, "opacity_map_data is not supported in SVG output"));
+    }
+    if config.subpixel_offset.is_some() {
+        return Err(WatermarkError::with_code(ErrorCode::InvalidConfig, "subpixel_offset is not supported in SVG output"));
+    }
+    if config.auto_contrast.unwrap_or(false) {
+        return Err(WatermarkError::with_code(ErrorCode::InvalidConfig, "auto_contrast requires pixel data and is not supported in SVG output"));
+    }
+    if config.rotation_jitter.is_some_and(|jitter| jitter > 0.0) {
+        return Err(WatermarkError::with_code(ErrorCode::InvalidConfig, "rotation_jitter is not supported in SVG output"));
+    }
+    
+    // SVG 通过 transform 旋转，要求旋转是最后一个变换步骤
+    let order = transform_order(config)?;
+    let (last, steps) = order.split_last().ok_or("transform_order is empty")?;
+    if *last != "rotate" {
+        return Err(WatermarkError::with_code(ErrorCode::InvalidConfig, "transform_order must end with 'rotate' for SVG output"));
+    }
+    let watermark_img = load_and_transform_watermark(config, steps)?;
+    let (width, height) = watermark_img.dimensions();
+    let rotate = config.rotate.unwrap_or(0.0);
+    // 平铺间距和定位都基于旋转后的外接矩形，与栅格化路径一致
+    let rotated = rotate_image(&watermark_img, rotate, config.fast.unwrap_or(false)).to_rgba8();
+    let (wm_width, wm_height) = rotated.dimensions();
+    
+    let positions = if config.tile.unwrap_or(false) || config.coverage_percent.is_some() {
+        let mut positions: Vec<_> = tile_grid(config, &rotated, image_width, image_height)?
+            .into_iter()
+            .map(|(_, _, x, y)| (x, y))
+            .collect();
+        if config.tile_ensure_full.unwrap_or(false) {
+            positions.extend(full_tile_fallback(&positions, image_width, image_height, wm_width, wm_height));
+        }
+        positions
+    } else {
+        let (x, y) = bounded_single_position(config, image_width, image_height, wm_width, wm_height)?;
+        vec![(x as i64, y as i64)]
+    };
+    
+    let mut watermark_rgba = watermark_img.to_rgba8();
+    apply_color_adjustments(&mut watermark_rgba, config)?;
+    let mut png = Cursor::new(Vec::new());
+    watermark_rgba.write_to(&mut png, image::ImageOutputFormat::Png)
+        .map_err(|e| format!("Failed to encode watermark: {}", e))?;
+    
+    // 水印在外接矩形内居中，绕外接矩形中心旋转
+    let mut svg = format!("<g opacity=\"{}\"", config.transparency.unwrap_or(0.5));
+    if let Some(polygon) = &config.clip_polygon {
+        let points: Vec<String> = polygon.iter().map(|p| format!("{},{}", p[0], p[1])).collect();
+        svg.push_str(&format!(
+            "><defs><clipPath id=\"fast-watermark-clip\"><polygon points=\"{}\"/></clipPath></defs><g clip-path=\"url(#fast-watermark-clip)\"",
+            points.join(" ")
+        ));
+    }
+    svg.push_str(&format!(
+        "><defs><image id=\"fast-watermark\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" transform=\"rotate({} {} {})\" href=\"data:image/png;base64,{}\"/></defs>",
+        (wm_width as f32 - width as f32) / 2.0,
+        (wm_height as f32 - height as f32) / 2.0,
+        width,
+        height,
+        rotate,
+        wm_width as f32 / 2.0,
+        wm_height as f32 / 2.0,
+        STANDARD.encode(png.into_inner()),
+    ));
+    for (x, y) in positions {
+        svg.push_str(&format!("<use href=\"#fast-watermark\" x=\"{}\" y=\"{}\"/>", x, y));
+    }
+    if config.clip_polygon.is_some() {
+        svg.push_str("</g>");
+    }
+    svg.push_str("</g>");
+    Ok(svg)
+}
+
+// 在两套配置之间插值：数值字段按 t 线性插值（两侧都设置时），其余字段 t < 0.5 取 a，否则取 b
+// t 截断到 [0, 1]，不做外推；非有限的 t 由调用方拒绝
+pub fn interpolate_config(a: &WatermarkConfig, b: &WatermarkConfig, t: f32) -> WatermarkConfig {
+    fn lerp(a: f32, b: f32, t: f32) -> f32 {
+        a + (b - a) * t
+    }
+    fn lerp_f32(a: Option<f32>, b: Option<f32>, base: Option<f32>, t: f32) -> Option<f32> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(lerp(a, b, t)),
+            _ => base,
+        }
+    }
+    fn lerp_i32(a: Option<i32>, b: Option<i32>, base: Option<i32>, t: f32) -> Option<i32> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(lerp(a as f32, b as f32, t).round() as i32),
+            _ => base,
+        }
+    }
+    fn lerp_u32(a: Option<u32>, b: Option<u32>, base: Option<u32>, t: f32) -> Option<u32> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(lerp(a as f32, b as f32, t).round().max(1.0) as u32),
+            _ => base,
+        }
+    }
+    
+    let t = t.clamp(0.0, 1.0);
+    let base = if t < 0.5 { a } else { b };
+    WatermarkConfig {
+        transparency: lerp_f32(a.transparency, b.transparency, base.transparency, t),
+        rotate: lerp_f32(a.rotate, b.rotate, base.rotate, t),
+        x_offset: lerp_i32(a.x_offset, b.x_offset, base.x_offset, t),
+        y_offset: lerp_i32(a.y_offset, b.y_offset, base.y_offset, t),
+        width: lerp_u32(a.width, b.width, base.width, t),
+        height: lerp_u32(a.height, b.height, base.height, t),
+        coverage_percent: lerp_f32(a.coverage_percent, b.coverage_percent, base.coverage_percent, t),
+        corner_opacities: match (a.corner_opacities, b.corner_opacities) {
+            (Some(a), Some(b)) => Some(std::array::from_fn(|i| lerp(a[i], b[i], t))),
+            _ => base.corner_opacities,
+        },
+        ..base.clone()
+    }
+}
+
+// 拒绝宽或高为 0 的图片（部分损坏文件会解码出 0x0 图片，后续计算会除零或越界）
+fn check_image_dimensions(img: &DynamicImage) -> Result<(), String> {
+    let (width, height) = img.dimensions();
+    if width == 0 || height == 0 {
+        return Err(format!("Image has zero width or height ({}x{})", width, height));
+    }
+    // 叠加时按 RGBA8 展开，WASM 的 usize 只有 32 位，超大全景图的字节偏移可能回绕
+    if rgba_buffer_len(width, height).is_none() {
+        return Err(format!(
+            "Image is too large ({}x{}): its RGBA buffer exceeds the {}-bit address space",
+            width, height, usize::BITS
+        ));
+    }
+    Ok(())
+}
+
+// 未设置 max_pixels 时的底图像素数上限（5000 万像素，RGBA8 约 200MB）
+const DEFAULT_MAX_PIXELS: u64 = 50_000_000;
+
+// 检查底图像素数是否超出 max_pixels，按 u64 计算避免宽 x 高在 u32 下溢出
+fn check_pixel_limit(width: u32, height: u32, config: &WatermarkConfig) -> Result<(), String> {
+    let pixels = width as u64 * height as u64;
+    let max_pixels = config.max_pixels.unwrap_or(DEFAULT_MAX_PIXELS);
+    if pixels > max_pixels {
+        return Err(format!(
+            "Image is too large ({}x{} = {} pixels): exceeds max_pixels ({})",
+            width, height, pixels, max_pixels
+        ));
+    }
+    Ok(())
+}
+
+// RGBA8 像素缓冲区的字节数，超出 isize::MAX（切片长度上限）时返回 None
+fn rgba_buffer_len(width: u32, height: u32) -> Option<usize> {
+    let len = (width as u64).checked_mul(height as u64)?.checked_mul(4)?;
+    usize::try_from(len).ok().filter(|&len| len <= isize::MAX as usize)
+}
+
+// 解码底图、添加水印并完成后处理，返回待编码的图片
+// 解码底图，按 EXIF 方向摆正（image 解码时不处理 EXIF）
+fn load_base_image(image_data: &[u8], config: &WatermarkConfig) -> Result<DynamicImage, WatermarkError> {
+    // 检查输入数据
+    if image_data.is_empty() {
+        return Err(WatermarkError::with_code(ErrorCode::DecodeFailed, "Image data is empty"));
+    }
+    let decode_failed = |e: String| WatermarkError::with_code(ErrorCode::DecodeFailed, e);
+    
+    // HDR 输入按浮点解码并色调映射为 8 位
+    #[cfg(feature = "hdr")]
+    if let Some(img) = load_hdr_image(image_data).map_err(decode_failed)? {
+        check_image_dimensions(&img).map_err(decode_failed)?;
+        check_pixel_limit(img.width(), img.height(), config).map_err(decode_failed)?;
+        return Ok(img);
+    }
+    
+    // 加载图片
+    let img = image::load_from_memory(image_data).map_err(|e| WatermarkError::with_code(
+        decode_error_code(image_data),
+        format!("Failed to load image: {}", e),
+    ))?;
+    check_image_dimensions(&img).map_err(decode_failed)?;
+    check_pixel_limit(img.width(), img.height(), config).map_err(decode_failed)?;
+    
+    if !config.respect_exif_orientation.unwrap_or(true) {
+        return Ok(img);
+    }
+    Ok(match jpeg_exif_segment(image_data).and_then(|segment| exif_orientation(&image_data[segment])) {
+        Some((orientation, _)) => apply_exif_orientation(img, orientation),
+        None => img,
+    })
+}
+
+// JPEG 中 EXIF APP1 段（含 FF E1 标记和长度）在文件中的范围，非 JPEG 或没有 EXIF 时返回 None
+fn jpeg_exif_segment(data: &[u8]) -> Option<std::ops::Range<usize>> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut pos = 2;
+    while pos + 4 <= data.len() && data[pos] == 0xFF {
+        let marker = data[pos + 1];
+        // 到达图像数据（SOS）或结束（EOI）后不再有元数据段
+        if marker == 0xDA || marker == 0xD9 {
+            return None;
+        }
+        let end = pos + 2 + u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        if end > data.len() {
+            return None;
+        }
+        if marker == 0xE1 && data[pos + 4..end].starts_with(b"Exif\0\0") {
+            return Some(pos..end);
+        }
+        pos = end;
+    }
+    None
+}
+
+// 在 APP1 段中查找 IFD0 的 Orientation 标签（0x0112），返回方向值及其在段内的字节位置
+fn exif_orientation(segment: &[u8]) -> Option<(u16, usize)> {
+    // 段结构：FF E1、2 字节长度、"Exif\0\0"，之后是 TIFF 头
+    const TIFF_START: usize = 10;
+    let tiff = segment.get(TIFF_START..)?;
+    let little_endian = match tiff.get(..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let read_u16 = |at: usize| -> Option<u16> {
+        let bytes = [*tiff.get(at)?, *tiff.get(at + 1)?];
+        Some(if little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+    };
+    let read_u32 = |at: usize| -> Option<u32> {
+        let bytes = [*tiff.get(at)?, *tiff.get(at + 1)?, *tiff.get(at + 2)?, *tiff.get(at + 3)?];
+        Some(if little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    };
+    
+    let ifd = read_u32(4)? as usize;
+    for index in 0..read_u16(ifd)? as usize {
+        let entry = ifd + 2 + index * 12;
+        // 类型 3 为 SHORT，值直接存放在条目的第 8-9 字节
+        if read_u16(entry)? == 0x0112 && read_u16(entry + 2)? == 3 {
+            let value = read_u16(entry + 8)?;
+            return (1..=8).contains(&value).then_some((value, TIFF_START + entry + 8));
+        }
+    }
+    None
+}
+
+// 按 EXIF 方向值（1-8）把图片变换为正常朝向
+fn apply_exif_orientation(img: DynamicImage, orientation: u16) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    }
+}
+
+// 把底图的 EXIF 段复制到输出的 JPEG 中（插在 SOI 和 JFIF APP0 之后），方向重置为 1，避免查看器再次旋转
+fn attach_source_exif(jpeg: Vec<u8>, image_data: &[u8]) -> Vec<u8> {
+    let Some(segment) = jpeg_exif_segment(image_data) else {
+        return jpeg;
+    };
+    let mut exif = image_data[segment].to_vec();
+    if let Some((_, offset)) = exif_orientation(&exif) {
+        let little_endian = &exif[10..12] == b"II";
+        let normal: [u8; 2] = if little_endian { 1u16.to_le_bytes() } else { 1u16.to_be_bytes() };
+        exif[offset..offset + 2].copy_from_slice(&normal);
+    }
+    
+    let mut insert_at = 2;
+    if jpeg.get(2..4) == Some(&[0xFF, 0xE0]) {
+        if let Some(len) = jpeg.get(4..6).map(|len| u16::from_be_bytes([len[0], len[1]]) as usize) {
+            insert_at = (4 + len).min(jpeg.len());
+        }
+    }
+    let mut output = Vec::with_capacity(jpeg.len() + exif.len());
+    output.extend_from_slice(&jpeg[..insert_at]);
+    output.extend_from_slice(&exif);
+    output.extend_from_slice(&jpeg[insert_at..]);
+    output
+}
+
+// 解码 Radiance HDR / OpenEXR 输入并色调映射，其他格式返回 None
+// （image 默认的 HDR 解码会直接截断到 8 位，丢失高光细节，因此单独按浮点读取）
+#[cfg(feature = "hdr")]
+fn load_hdr_image(image_data: &[u8]) -> Result<Option<DynamicImage>, String> {
+    let hdr = match image::guess_format(image_data) {
+        Ok(image::ImageFormat::Hdr) => {
+            let decoder = image::codecs::hdr::HdrDecoder::new(Cursor::new(image_data))
+                .map_err(|e| format!("Failed to load image: {}", e))?;
+            let metadata = decoder.metadata();
+            let pixels = decoder.read_image_hdr()
+                .map_err(|e| format!("Failed to load image: {}", e))?;
+            let raw = pixels.iter().flat_map(|p| p.0).collect();
+            let buffer = image::Rgb32FImage::from_raw(metadata.width, metadata.height, raw)
+                .ok_or("Failed to load image: HDR pixel data does not match its dimensions")?;
+            DynamicImage::ImageRgb32F(buffer)
+        }
+        Ok(image::ImageFormat::OpenExr) => image::load_from_memory(image_data)
+            .map_err(|e| format!("Failed to load image: {}", e))?,
+        _ => return Ok(None),
+    };
+    Ok(Some(tone_map_hdr(&hdr)))
+}
+
+// Reinhard 色调映射：线性浮点颜色 c 映射为 c / (1 + c)，再做 sRGB 伽马编码到 8 位
+#[cfg(feature = "hdr")]
+fn tone_map_hdr(img: &DynamicImage) -> DynamicImage {
+    let hdr = img.to_rgba32f();
+    let map = |c: f32| {
+        let c = c.max(0.0);
+        let ldr = (c / (1.0 + c)).powf(1.0 / 2.2);
+        (ldr * 255.0).round().clamp(0.0, 255.0) as u8
+    };
+    let ldr = RgbaImage::from_fn(hdr.width(), hdr.height(), |x, y| {
+        let p = hdr.get_pixel(x, y);
+        image::Rgba([map(p[0]), map(p[1]), map(p[2]), (p[3].clamp(0.0, 1.0) * 255.0).round() as u8])
+    });
+    DynamicImage::ImageRgba8(ldr)
+}
+
+// 根据类型添加水印，返回每个水印的叠加区域
+fn add_watermark_by_type(img: &mut DynamicImage, config: &WatermarkConfig) -> Result<Vec<WatermarkPlacement>, WatermarkError> {
+    match config.watermark_type {
+        WatermarkType::Text => {
+            add_text_watermark(img, config)
+                .map_err(|e| e.context("Failed to add text watermark"))
+        }
+        WatermarkType::Image => {
+            add_image_watermark(img, config)
+                .map_err(|e| e.context("Failed to add image watermark"))
+        }
+        WatermarkType::Invisible => add_invisible_watermark(img, config).map(|()| Vec::new()),
+    }
+}
+
+// 添加不可见水印（不占用可见区域，没有叠加位置）
+fn add_invisible_watermark(img: &mut DynamicImage, config: &WatermarkConfig) -> Result<(), WatermarkError> {
+    validate_config(config).map_err(|e| WatermarkError::with_code(ErrorCode::InvalidConfig, e))?;
+    embed_invisible_watermark(img, config.payload.as_deref().unwrap_or_default())
+        .map_err(|e| WatermarkError::from(e).context("Failed to add invisible watermark"))
+}
+
+fn render_watermarked_image(image_data: &[u8], config: &WatermarkConfig) -> Result<DynamicImage, WatermarkError> {
+    Ok(render_watermarked_image_with_placements(image_data, config)?.0)
+}
+
+// 同 render_watermarked_image，同时返回每个水印的叠加区域
+fn render_watermarked_image_with_placements(
+    image_data: &[u8],
+    config: &WatermarkConfig,
+) -> Result<(DynamicImage, Vec<WatermarkPlacement>), WatermarkError> {
+    let mut img = load_base_image(image_data, config)?;
+    
+    // 差异图模式需要保留原图
+    let original = config.diff_output.unwrap_or(false).then(|| img.to_rgba8());
+    
+    let placements = add_watermark_by_type(&mut img, config)?;
+    
+    Ok((finish_watermarked_image(img, original.as_ref(), config)?, placements))
+}
+
+// 叠加水印之后的输出处理：差异图与输出前后处理
+fn finish_watermarked_image(mut img: DynamicImage, original: Option<&RgbaImage>, config: &WatermarkConfig) -> Result<DynamicImage, WatermarkError> {
+    if let Some(original) = original {
+        let amplification = config.diff_amplification.unwrap_or(10.0);
+        img = DynamicImage::ImageRgba8(difference_image(original, &img.to_rgba8(), amplification));
+    }
+    
+    // 输出前后处理
+    postprocess_output(&mut img, config)
+        .map_err(|e| format!("Failed to post-process image: {}", e))?;
+    
+    Ok(img)
+}
+
+// 不透明度对比：底图只解码一次、水印只准备一次，按 transparency_sweep 逐个改变叠加不透明度
+fn render_transparency_sweep(image_data: &[u8], config: &WatermarkConfig) -> Result<Vec<(DynamicImage, Vec<WatermarkPlacement>)>, WatermarkError> {
+    let levels = config.transparency_sweep.as_deref()
+        .filter(|levels| !levels.is_empty())
+        .ok_or_else(|| WatermarkError::with_code(ErrorCode::InvalidConfig, "transparency_sweep is required for add_watermark_sweep"))?;
+    validate_config(config).map_err(|e| WatermarkError::with_code(ErrorCode::InvalidConfig, e))?;
+    
+    let base = load_base_image(image_data, config)?;
+    let config = &*resolve_relative_config(config, base.width(), base.height());
+    let original = config.diff_output.unwrap_or(false).then(|| base.to_rgba8());
+    let watermark_rgba = prepare_watermark(&base, config)?;
+    
+    levels.iter().map(|&level| {
+        // 自水印的不透明度来自 strength，两者一起替换
+        let level_config = WatermarkConfig {
+            transparency: Some(level),
+            self_watermark: config.self_watermark.clone().map(|self_watermark| SelfWatermarkConfig {
+                strength: Some(level),
+                ..self_watermark
+            }),
+            ..config.clone()
+        };
+        let mut img = base.clone();
+        let placements = composite_watermark(&mut img, &level_config, &watermark_rgba)?;
+        Ok((finish_watermarked_image(img, original.as_ref(), &level_config)?, placements))
+    }).collect()
+}
+
+// 校验配置并准备可在多张底图间复用的水印
+// 自水印和按百分比指定尺寸的水印依赖底图，返回 None，由 render_with_shared_watermark 逐张准备
+fn prepare_shared_watermark(config: &WatermarkConfig) -> Result<Option<RgbaImage>, WatermarkError> {
+    validate_config(config).map_err(|e| WatermarkError::with_code(ErrorCode::InvalidConfig, e))?;
+    
+    let per_image = config.self_watermark.is_some() || config.width_pct.is_some() || config.height_pct.is_some()
+        || config.watermark_type == WatermarkType::Invisible;
+    Ok(if per_image { None } else { Some(load_and_prepare_watermark(config)?) })
+}
+
+// 使用预先准备好的水印处理一张底图并编码，shared 为 None 时按这张底图准备水印
+fn render_with_shared_watermark(image_data: &[u8], config: &WatermarkConfig, shared: Option<&RgbaImage>) -> Result<WatermarkResult, WatermarkError> {
+    if is_animated_gif_request(image_data, config) {
+        return render_animated_gif(image_data, config, shared);
+    }
+    let img = load_base_image(image_data, config)?;
+    let (img, placements) = watermark_with_shared(img, config, shared)?;
+    Ok(WatermarkResult { placements, ..encode_watermark_result(&img, image_data, config)? })
+}
+
+// 给已解码的底图叠加水印并完成输出处理，shared 为 None 时按这张底图准备水印
+fn watermark_with_shared(
+    mut img: DynamicImage,
+    config: &WatermarkConfig,
+    shared: Option<&RgbaImage>,
+) -> Result<(DynamicImage, Vec<WatermarkPlacement>), WatermarkError> {
+    if config.watermark_type == WatermarkType::Invisible {
+        add_invisible_watermark(&mut img, config)?;
+        return Ok((img, Vec::new()));
+    }
+    let config = &*resolve_relative_config(config, img.width(), img.height());
+    let original = config.diff_output.unwrap_or(false).then(|| img.to_rgba8());
+    let watermark_rgba = match shared {
+        Some(watermark) => Cow::Borrowed(watermark),
+        None => Cow::Owned(prepare_watermark(&img, config)?),
+    };
+    let placements = composite_watermark(&mut img, config, &watermark_rgba)?;
+    Ok((finish_watermarked_image(img, original.as_ref(), config)?, placements))
+}
+
+// 设置了 animated 且输入是 GIF 时逐帧处理
+fn is_animated_gif_request(image_data: &[u8], config: &WatermarkConfig) -> bool {
+    config.animated.unwrap_or(false) && matches!(image::guess_format(image_data), Ok(image::ImageFormat::Gif))
+}
+
+// 动画 GIF：逐帧叠加同一个水印（只准备一次）后重新编码，保留每帧的延时
+// 解码得到的每一帧都是合成后的完整画布，叠加位置在各帧间一致；循环次数与源文件一致
+fn render_animated_gif(image_data: &[u8], config: &WatermarkConfig, shared: Option<&RgbaImage>) -> Result<WatermarkResult, WatermarkError> {
+    use image::{AnimationDecoder, ImageDecoder};
+    
+    let decode_failed = |e: image::ImageError| WatermarkError::with_code(ErrorCode::DecodeFailed, format!("Failed to load image: {}", e));
+    let decoder = image::codecs::gif::GifDecoder::new(Cursor::new(image_data)).map_err(decode_failed)?;
+    let (width, height) = decoder.dimensions();
+    check_pixel_limit(width, height, config).map_err(|e| WatermarkError::with_code(ErrorCode::DecodeFailed, e))?;
+    let frames = decoder.into_frames().collect_frames().map_err(decode_failed)?;
+    let repeat = gif_repeat(image_data).map_err(|e| WatermarkError::with_code(ErrorCode::DecodeFailed, e))?;
+    let selector = match &config.frame_selector {
+        Some(selector) => FrameSelector::parse(selector).map_err(|e| WatermarkError::with_code(ErrorCode::InvalidConfig, e))?,
+        None => FrameSelector::All,
+    };
+    
+    let mut output = Vec::with_capacity(frames.len());
+    let mut placements = Vec::new();
+    for (index, frame) in frames.into_iter().enumerate() {
+        // 未选中的帧原样保留，不做水印和后处理
+        if !selector.selects(index) {
+            output.push(frame);
+            continue;
+        }
+        let delay = frame.delay();
+        let (img, frame_placements) = watermark_with_shared(DynamicImage::ImageRgba8(frame.into_buffer()), config, shared)?;
+        // 位置按方差筛选等依赖画面内容时各帧可能不同，记录所有帧出现过的区域
+        for placement in frame_placements {
+            if !placements.contains(&placement) {
+                placements.push(placement);
+            }
+        }
+        output.push(image::Frame::from_parts(img.to_rgba8(), 0, 0, delay));
+    }
+    
+    let encode_failed = |e: image::ImageError| WatermarkError::with_code(ErrorCode::EncodeFailed, format!("Failed to encode image: {}", e));
+    let mut bytes = Vec::new();
+    {
+        // 每帧单独量化调色板，帧数多时使用较快的量化速度
+        let mut encoder = image::codecs::gif::GifEncoder::new_with_speed(&mut bytes, 10);
+        if let Some(repeat) = repeat {
+            encoder.set_repeat(repeat).map_err(encode_failed)?;
+        }
+        encoder.encode_frames(output).map_err(encode_failed)?;
+    }
+    
+    Ok(WatermarkResult {
+        bytes,
+        width,
+        height,
+        format: "gif".to_string(),
+        suggested_filename: suggested_filename(config.source_name.as_deref(), "gif"),
+        placements,
+    })
+}
+
+// 源 GIF 的循环次数（NETSCAPE2.0 扩展），没有该扩展（只播放一次）时返回 None，输出同样不写该扩展
+fn gif_repeat(image_data: &[u8]) -> Result<Option<image::codecs::gif::Repeat>, String> {
+    let decoder = gif::DecodeOptions::new()
+        .read_info(image_data)
+        .map_err(|e| format!("Failed to load image: {}", e))?;
+    Ok(match decoder.repeat() {
+        gif::Repeat::Infinite => Some(image::codecs::gif::Repeat::Infinite),
+        // 没有循环扩展时 gif 报告默认值 Finite(0)（扩展中的 0 表示无限循环，已报告为 Infinite）
+        gif::Repeat::Finite(0) => None,
+        gif::Repeat::Finite(count) => Some(image::codecs::gif::Repeat::Finite(count)),
+    })
+}
+
+// 批量添加水印：配置只校验一次，水印只解码、准备一次并复用到每张底图
+// 单张图片失败只记录在对应位置，不影响其他图片
+// 每张图片处理完（无论成功与否）后调用 on_progress(序号, 总数)
+fn render_watermark_batch(
+    images: &[Vec<u8>],
+    config: &WatermarkConfig,
+    mut on_progress: impl FnMut(usize, usize),
+) -> Result<Vec<Result<WatermarkResult, WatermarkError>>, WatermarkError> {
+    let shared = prepare_shared_watermark(config)?;
+    
+    Ok(images.iter()
+        .enumerate()
+        .map(|(index, image_data)| {
+            let result = render_with_shared_watermark(image_data, config, shared.as_ref());
+            on_progress(index, images.len());
+            result
+        })
+        .collect())
+}
+
+// 多水印：只解码一次底图，按 z_index 从小到大依次叠加（相同层级保持数组顺序）
+// 输出相关参数（autocrop、force_rgb 等）取自数组中的第一个配置
+fn render_watermark_layers(image_data: &[u8], configs: &[WatermarkConfig]) -> Result<DynamicImage, WatermarkError> {
+    let first = configs.first()
+        .ok_or_else(|| WatermarkError::with_code(ErrorCode::InvalidConfig, "At least one watermark config is required"))?;
+    // 先校验全部配置再解码底图，避免前面的水印已经叠加完才发现后面的配置无效
+    for (index, config) in configs.iter().enumerate() {
+        validate_config(config)
+            .map_err(|e| WatermarkError::with_code(ErrorCode::InvalidConfig, format!("Watermark {}: {}", index, e)))?;
+    }
+    // 输出参数取自第一个配置，不可见水印必须放在第一个才能保证输出为 PNG 且不再改动像素
+    let invisible = configs.iter().filter(|config| config.watermark_type == WatermarkType::Invisible).count();
+    if invisible > 0 && (invisible > 1 || first.watermark_type != WatermarkType::Invisible) {
+        return Err(WatermarkError::with_code(
+            ErrorCode::InvalidConfig,
+            "Only one invisible watermark is allowed and it must be the first config",
+        ));
+    }
+    let mut img = load_base_image(image_data, first)?;
+    
+    // 不可见水印无论 z_index 如何都最后嵌入，避免被其他水印覆盖
+    let mut order: Vec<usize> = (0..configs.len()).collect();
+    order.sort_by_key(|&index| (configs[index].watermark_type == WatermarkType::Invisible, configs[index].z_index.unwrap_or(0)));
+    for index in order {
+        add_watermark_by_type(&mut img, &configs[index])
+            .map_err(|e| e.context(format_args!("Watermark {}", index)))?;
+    }
+    
+    postprocess_output(&mut img, first)
+        .map_err(|e| format!("Failed to post-process image: {}", e))?;
+    
+    Ok(img)
+}
+
+// PNG 超出字节上限时逐步减少调色板颜色数重新编码，直到满足上限
+fn fit_png_size(img: &RgbaImage, encoded: Vec<u8>, max_bytes: usize) -> Result<Vec<u8>, String> {
+    if encoded.len() <= max_bytes {
+        return Ok(encoded);
+    }
+    
+    #[cfg(feature = "quantize")]
+    for colors in [256, 128, 64, 32, 16, 8, 4, 2] {
+        let quantized = quantize_png(img, colors)?;
+        if quantized.len() <= max_bytes {
+            return Ok(quantized);
+        }
+    }
+    
+    #[cfg(not(feature = "quantize"))]
+    let _ = img;
+    
+    Err(format!(
+        "Encoded PNG is {} bytes, exceeding max_output_bytes {}{}",
+        encoded.len(),
+        max_bytes,
+        if cfg!(feature = "quantize") { " even with a 2-color palette" } else { " (enable the 'quantize' feature to reduce PNG size)" },
+    ))
+}
+
+// 使用 NeuQuant 将图片量化为指定颜色数，编码为带透明度表的调色板 PNG
+#[cfg(feature = "quantize")]
+fn quantize_png(img: &RgbaImage, colors: usize) -> Result<Vec<u8>, String> {
+    let quantizer = color_quant::NeuQuant::new(10, colors, img.as_raw());
+    let indices: Vec<u8> = img.as_raw().chunks_exact(4).map(|p| quantizer.index_of(p) as u8).collect();
+    
+    let palette_rgba = quantizer.color_map_rgba();
+    let palette: Vec<u8> = palette_rgba.chunks_exact(4).flat_map(|c| [c[0], c[1], c[2]]).collect();
+    let alphas: Vec<u8> = palette_rgba.chunks_exact(4).map(|c| c[3]).collect();
+    
+    let mut buffer = Vec::new();
+    let mut encoder = png::Encoder::new(&mut buffer, img.width(), img.height());
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(palette);
+    encoder.set_trns(alphas);
+    let mut writer = encoder.write_header().map_err(|e| format!("Failed to encode image: {}", e))?;
+    writer.write_image_data(&indices).map_err(|e| format!("Failed to encode image: {}", e))?;
+    writer.finish().map_err(|e| format!("Failed to encode image: {}", e))?;
+    Ok(buffer)
+}
+
+// 水印处理核心流程：解码、添加水印、后处理、编码
+fn process_watermark(image_data: &[u8], config: &WatermarkConfig) -> Result<WatermarkResult, WatermarkError> {
+    if is_animated_gif_request(image_data, config) {
+        return render_animated_gif(image_data, config, prepare_shared_watermark(config)?.as_ref());
+    }
+    
+    let (img, placements) = render_watermarked_image_with_placements(image_data, config)?;
+    
+    Ok(WatermarkResult { placements, ..encode_watermark_result(&img, image_data, config)? })
+}
+
+// 按输出格式编码处理结果并附带元信息
+fn encode_watermark_result(img: &DynamicImage, image_data: &[u8], config: &WatermarkConfig) -> Result<WatermarkResult, WatermarkError> {
+    let result = encode_as_format(img, output_format(config, image_data), config)?;
+    Ok(with_source_metadata(result, image_data, config))
+}
+
+// 按 preserve_exif 把底图的 EXIF 附加到 JPEG 输出
+fn with_source_metadata(mut result: WatermarkResult, image_data: &[u8], config: &WatermarkConfig) -> WatermarkResult {
+    if config.preserve_exif.unwrap_or(false) && result.format == "jpeg" {
+        result.bytes = attach_source_exif(result.bytes, image_data);
+    }
+    result
+}
+
+// 输出格式：优先使用 output_format，未设置时沿用输入图片的格式（上传 JPEG 仍得到 JPEG）
+// 无法识别或不支持编码的输入格式（如 ICO、HDR）输出 PNG
+fn output_format<'a>(config: &'a WatermarkConfig, image_data: &[u8]) -> &'a str {
+    if let Some(format) = &config.output_format {
+        return format;
+    }
+    // 不可见水印只能保存在无损的 PNG 中
+    if config.watermark_type == WatermarkType::Invisible {
+        return "png";
+    }
+    match image::guess_format(image_data) {
+        Ok(image::ImageFormat::Jpeg) => "jpeg",
+        Ok(image::ImageFormat::WebP) => "webp",
+        Ok(image::ImageFormat::Gif) => "gif",
+        Ok(image::ImageFormat::Bmp) => "bmp",
+        _ => "png",
+    }
+}
+
+// 按指定格式编码处理结果并附带元信息
+fn encode_as_format(img: &DynamicImage, format: &str, config: &WatermarkConfig) -> Result<WatermarkResult, WatermarkError> {
+    if !OUTPUT_FORMATS.contains(&format) {
+        return Err(WatermarkError::with_code(
+            ErrorCode::UnsupportedFormat,
+            format!("Unsupported output format '{}'. Must be one of: {}", format, OUTPUT_FORMATS.join(", ")),
+        ));
+    }
+    let bytes = encode_bytes(img, format, config)
+        .map_err(|e| WatermarkError::with_code(ErrorCode::EncodeFailed, e))?;
+    
+    Ok(WatermarkResult {
+        bytes,
+        width: img.width(),
+        height: img.height(),
+        format: format.to_string(),
+        suggested_filename: suggested_filename(config.source_name.as_deref(), format),
+        placements: Vec::new(),
+    })
+}
+
+// 所有像素的 alpha 都是最大值时返回去掉 alpha 通道的副本（保持位深），否则返回 None
+fn without_opaque_alpha(img: &DynamicImage) -> Option<DynamicImage> {
+    match img {
+        DynamicImage::ImageRgba8(rgba) if rgba.pixels().all(|p| p[3] == u8::MAX) => Some(DynamicImage::ImageRgb8(img.to_rgb8())),
+        DynamicImage::ImageRgba16(rgba) if rgba.pixels().all(|p| p[3] == u16::MAX) => Some(DynamicImage::ImageRgb16(img.to_rgb16())),
+        DynamicImage::ImageLumaA8(luma) if luma.pixels().all(|p| p[1] == u8::MAX) => Some(DynamicImage::ImageLuma8(img.to_luma8())),
+        DynamicImage::ImageLumaA16(luma) if luma.pixels().all(|p| p[1] == u16::MAX) => Some(DynamicImage::ImageLuma16(img.to_luma16())),
+        _ => None,
+    }
+}
+
+// Adam7 七遍扫描的起点与步长：(x0, y0, dx, dy)
+const ADAM7_PASSES: [(u32, u32, u32, u32); 7] = [
+    (0, 0, 8, 8), (4, 0, 8, 8), (0, 4, 4, 8), (2, 0, 4, 4), (0, 2, 2, 4), (1, 0, 2, 2), (0, 1, 1, 2),
+];
+
+// 编码为 Adam7 隔行 PNG：灰度扩展为 RGB，高位深图片写为 16 位，每行使用 Sub 过滤
+fn encode_interlaced_png(img: &DynamicImage) -> Vec<u8> {
+    let (width, height) = img.dimensions();
+    let sixteen_bit = img.color().bytes_per_pixel() > img.color().channel_count();
+    let has_alpha = img.color().has_alpha();
+    // 像素数据统一为大端字节序（PNG 要求）
+    let (color_type, raw): (u8, Vec<u8>) = match (sixteen_bit, has_alpha) {
+        (false, false) => (2, img.to_rgb8().into_raw()),
+        (false, true) => (6, img.to_rgba8().into_raw()),
+        (true, false) => (2, img.to_rgb16().into_raw().iter().flat_map(|v| v.to_be_bytes()).collect()),
+        (true, true) => (6, img.to_rgba16().into_raw().iter().flat_map(|v| v.to_be_bytes()).collect()),
+    };
+    let bytes_per_pixel = (if has_alpha { 4 } else { 3 }) * if sixteen_bit { 2 } else { 1 };
+    let row_len = width as usize * bytes_per_pixel;
+    
+    let mut filtered = Vec::with_capacity(raw.len() + height as usize * 2);
+    let mut line = Vec::with_capacity(row_len);
+    for (x0, y0, dx, dy) in ADAM7_PASSES {
+        // 宽或高为 0 的扫描遍完全省略（不写过滤字节）
+        if x0 >= width || y0 >= height {
+            continue;
+        }
+        for y in (y0..height).step_by(dy as usize) {
+            line.clear();
+            let row = &raw[y as usize * row_len..(y as usize + 1) * row_len];
+            for x in (x0..width).step_by(dx as usize) {
+                let start = x as usize * bytes_per_pixel;
+                line.extend_from_slice(&row[start..start + bytes_per_pixel]);
+            }
+            filtered.push(1);
+            filtered.extend(line.iter().enumerate().map(|(i, &byte)| {
+                byte.wrapping_sub(if i >= bytes_per_pixel { line[i - bytes_per_pixel] } else { 0 })
+            }));
+        }
+    }
+    
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 位深、颜色类型、压缩方法、过滤方法、隔行方式（1 = Adam7）
+    header.extend_from_slice(&[if sixteen_bit { 16 } else { 8 }, color_type, 0, 0, 1]);
+    
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_png_chunk(&mut png, b"IHDR", &header);
+    write_png_chunk(&mut png, b"IDAT", &miniz_oxide::deflate::compress_to_vec_zlib(&filtered, 6));
+    write_png_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+// 写入一个 PNG 数据块：长度、类型、数据、CRC（覆盖类型和数据）
+fn write_png_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+    out.extend_from_slice(&crc.finalize().to_be_bytes());
+}
+
+// 按指定格式编码图片（format 已确认属于 OUTPUT_FORMATS）
+fn encode_bytes(img: &DynamicImage, format: &str, config: &WatermarkConfig) -> Result<Vec<u8>, String> {
+    let encode_error = |e: image::ImageError| format!("Failed to encode image: {}", e);
+    let bytes = match format {
+        "png" => {
+            // 编码为PNG（预分配缓冲区以减少重新分配）
+            let (width, height) = img.dimensions();
+            // 预估 PNG 编码后的大小：width * height * 4 (RGBA) + 头部开销（按 u64 计算，u32 相乘在约 10 亿像素时溢出）
+            let estimated_size = rgba_buffer_len(width, height).unwrap_or(0).saturating_add(1024);
+            let mut buffer = Vec::with_capacity(estimated_size);
+            // 完全不透明时不写 alpha 通道，PNG 体积更小
+            let opaque = without_opaque_alpha(img);
+            if config.png_interlace == Some(true) {
+                buffer = encode_interlaced_png(opaque.as_ref().unwrap_or(img));
+            } else {
+                opaque.as_ref().unwrap_or(img).write_to(&mut Cursor::new(&mut buffer), image::ImageFormat::Png)
+                    .map_err(encode_error)?;
+            }
+            
+            match config.max_output_bytes {
+                Some(max_bytes) => fit_png_size(&img.to_rgba8(), buffer, max_bytes as usize)?,
+                None => buffer,
+            }
+        }
+        "jpeg" => {
+            let subsampling = config.jpeg_chroma_subsampling.as_deref()
+                .map(ChromaSubsampling::parse)
+                .transpose()?;
+            let quality = config.quality.unwrap_or(DEFAULT_JPEG_QUALITY);
+            let background = flatten_background(config)?;
+            encode_jpeg(&flatten_alpha(&img.to_rgba8(), background), quality, subsampling)?
+        }
+        "webp" => {
+            // image 内置的 WebP 编码器只支持无损模式，quality 不生效
+            let rgba = img.to_rgba8();
+            let mut buffer = Vec::new();
+            image::codecs::webp::WebPEncoder::new_lossless(&mut buffer)
+                .write_image(rgba.as_raw(), rgba.width(), rgba.height(), image::ColorType::Rgba8)
+                .map_err(encode_error)?;
+            buffer
+        }
+        "gif" => {
+            let mut buffer = Vec::new();
+            image::codecs::gif::GifEncoder::new(&mut buffer)
+                .encode_frame(image::Frame::new(img.to_rgba8()))
+                .map_err(encode_error)?;
+            buffer
+        }
+        "bmp" => {
+            let rgba = img.to_rgba8();
+            let mut buffer = Vec::new();
+            image::codecs::bmp::BmpEncoder::new(&mut buffer)
+                .write_image(rgba.as_raw(), rgba.width(), rgba.height(), image::ColorType::Rgba8)
+                .map_err(encode_error)?;
+            buffer
+        }
+        _ => return Err(format!("Unsupported output format '{}'. Must be one of: {}", format, OUTPUT_FORMATS.join(", "))),
+    };
+    
+    Ok(bytes)
+}
+
+// 编码 JPEG：指定色度抽样时使用 jpeg-encoder，否则使用 image 内置编码器
+fn encode_jpeg(rgb: &image::RgbImage, quality: u8, chroma_subsampling: Option<ChromaSubsampling>) -> Result<Vec<u8>, String> {
+    // 超出范围的质量值截断到 1-100
+    let quality = quality.clamp(1, 100);
+    let mut buffer = Vec::new();
+    
+    match chroma_subsampling {
+        Some(subsampling) => {
+            let (width, height) = match (u16::try_from(rgb.width()), u16::try_from(rgb.height())) {
+                (Ok(width), Ok(height)) => (width, height),
+                _ => return Err("JPEG output is limited to 65535x65535 pixels".to_string()),
+            };
+            let mut encoder = jpeg_encoder::Encoder::new(&mut buffer, quality);
+            encoder.set_sampling_factor(subsampling.sampling_factor());
+            encoder.encode(rgb.as_raw(), width, height, jpeg_encoder::ColorType::Rgb)
+                .map_err(|e| format!("Failed to encode image: {}", e))?;
+        }
+        None => {
+            image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buffer, quality)
+                .write_image(rgb.as_raw(), rgb.width(), rgb.height(), image::ColorType::Rgb8)
+                .map_err(|e| format!("Failed to encode image: {}", e))?;
+        }
+    }
+    
+    Ok(buffer)
+}
+
+// 输出编码器（仅原生环境）：允许调用方接入自定义的输出格式
+#[cfg(not(target_arch = "wasm32"))]
+pub trait OutputEncoder {
+    fn encode(&self, img: &RgbaImage) -> Result<Vec<u8>, WatermarkError>;
+}
+
+// 内置 PNG 编码器
+#[cfg(not(target_arch = "wasm32"))]
+pub struct PngOutputEncoder {
+    // 丢弃 alpha 通道，以 background_color 拍平后输出 RGB PNG
+    pub force_rgb: bool,
+    // force_rgb 拍平时使用的背景色
+    pub background_color: [u8; 3],
+    // 输出字节数上限，超出时（启用 quantize 特性）量化为调色板 PNG
+    pub max_output_bytes: Option<usize>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for PngOutputEncoder {
+    fn default() -> Self {
+        Self { force_rgb: false, background_color: [255, 255, 255], max_output_bytes: None }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl PngOutputEncoder {
+    // 根据水印配置中的输出参数创建编码器
+    pub fn from_config(config: &WatermarkConfig) -> Result<Self, WatermarkError> {
+        Ok(Self {
+            force_rgb: config.force_rgb.unwrap_or(false),
+            background_color: flatten_background(config).map_err(WatermarkError::new)?,
+            max_output_bytes: config.max_output_bytes.map(|max| max as usize),
+        })
+    }
+    
+    // 直接编码写入 writer（不检查 max_output_bytes）；完全不透明的图片同样输出 RGB PNG
+    pub fn write_to<W: std::io::Write>(&self, img: &RgbaImage, writer: W) -> Result<(), WatermarkError> {
+        let encoder = image::codecs::png::PngEncoder::new(writer);
+        let result = if self.force_rgb || img.pixels().all(|p| p[3] == u8::MAX) {
+            let rgb = flatten_alpha(img, self.background_color);
+            encoder.write_image(rgb.as_raw(), rgb.width(), rgb.height(), image::ColorType::Rgb8)
+        } else {
+            encoder.write_image(img.as_raw(), img.width(), img.height(), image::ColorType::Rgba8)
+        };
+        result.map_err(|e| WatermarkError::new(format!("Failed to encode image: {}", e)))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl OutputEncoder for PngOutputEncoder {
+    fn encode(&self, img: &RgbaImage) -> Result<Vec<u8>, WatermarkError> {
+        let mut buffer = Vec::with_capacity(img.as_raw().len() + 1024);
+        self.write_to(img, &mut buffer)?;
+        
+        match self.max_output_bytes {
+            Some(max_bytes) => {
+                let source = if self.force_rgb {
+                    DynamicImage::ImageRgb8(flatten_alpha(img, self.background_color)).to_rgba8()
+                } else {
+                    img.clone()
+                };
+                fit_png_size(&source, buffer, max_bytes).map_err(WatermarkError::new)
+            }
+            None => Ok(buffer),
+        }
+    }
+}
+
+// 内置 JPEG 编码器（JPEG 不支持透明度，编码前以背景色拍平 alpha）
+#[cfg(not(target_arch = "wasm32"))]
+pub struct JpegOutputEncoder {
+    pub quality: u8,
+    // 色度抽样，None 时使用编码器默认值
+    pub chroma_subsampling: Option<ChromaSubsampling>,
+    // 拍平 alpha 时使用的背景色
+    pub background_color: [u8; 3],
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for JpegOutputEncoder {
+    fn default() -> Self {
+        Self { quality: DEFAULT_JPEG_QUALITY, chroma_subsampling: None, background_color: [255, 255, 255] }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl JpegOutputEncoder {
+    // 根据水印配置中的 JPEG 参数创建编码器
+    pub fn from_config(config: &WatermarkConfig) -> Result<Self, WatermarkError> {
+        let chroma_subsampling = config.jpeg_chroma_subsampling.as_deref()
+            .map(ChromaSubsampling::parse)
+            .transpose()
+            .map_err(WatermarkError::new)?;
+        Ok(Self {
+            quality: config.quality.unwrap_or(DEFAULT_JPEG_QUALITY),
+            chroma_subsampling,
+            background_color: flatten_background(config).map_err(WatermarkError::new)?,
+        })
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl OutputEncoder for JpegOutputEncoder {
+    fn encode(&self, img: &RgbaImage) -> Result<Vec<u8>, WatermarkError> {
+        let rgb = flatten_alpha(img, self.background_color);
+        encode_jpeg(&rgb, self.quality, self.chroma_subsampling).map_err(WatermarkError::new)
+    }
+}
+
+// 原生入口：与 WASM 导出的 add_watermark 处理流程和输出完全一致（输出格式默认沿用输入格式）
+// 日志在原生环境输出到 stderr，可在宿主机的 #[test] 中直接调用
+#[cfg(not(target_arch = "wasm32"))]
+pub fn add_watermark_native(image_data: &[u8], config: WatermarkConfig) -> Result<Vec<u8>, String> {
+    Ok(process_watermark(image_data, &config)?.bytes)
+}
+
+// 原生入口：添加水印并编码为 PNG
+#[cfg(not(target_arch = "wasm32"))]
+pub fn watermark_image(image_data: &[u8], config: &WatermarkConfig) -> Result<Vec<u8>, WatermarkError> {
+    watermark_image_with_encoder(image_data, config, &PngOutputEncoder::from_config(config)?)
+}
+
+// 原生入口：添加水印并使用指定的编码器输出
+#[cfg(not(target_arch = "wasm32"))]
+pub fn watermark_image_with_encoder(
+    image_data: &[u8],
+    config: &WatermarkConfig,
+    encoder: &dyn OutputEncoder,
+) -> Result<Vec<u8>, WatermarkError> {
+    let img = render_watermarked_image(image_data, config)?;
+    encoder.encode(&img.to_rgba8())
+}
+
+// 原生入口：添加水印并将 PNG 直接流式写入 writer，不在内存中保留完整的编码结果
+// 设置 max_output_bytes 时需要先得到完整编码结果判断大小，此时退化为缓冲后再写入
+#[cfg(not(target_arch = "wasm32"))]
+pub fn watermark_to_writer<W: std::io::Write>(
+    image_data: &[u8],
+    config: &WatermarkConfig,
+    mut writer: W,
+) -> Result<(), WatermarkError> {
+    if config.max_output_bytes.is_some() {
+        let bytes = watermark_image(image_data, config)?;
+        return writer.write_all(&bytes)
+            .map_err(|e| WatermarkError::new(format!("Failed to write image: {}", e)));
+    }
+    
+    let img = render_watermarked_image(image_data, config)?;
+    PngOutputEncoder::from_config(config)?.write_to(&img.to_rgba8(), writer)
+}
+
+// 原生批处理：按文件名模板解析水印参数，如模板 "{name}_t{transparency}_r{rotate}" 解析 "logo_t50_r30.png"
+// 支持的占位符：{transparency}（百分比 0-100）、{rotate}、{x_offset}、{y_offset}、{position}，{name} 匹配任意内容并忽略
+// 返回的配置未设置 image_data，由调用方填入水印数据
+#[cfg(not(target_arch = "wasm32"))]
+pub fn config_from_filename(filename: &str, pattern: &str) -> Result<WatermarkConfig, WatermarkError> {
+    let stem = std::path::Path::new(filename)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| WatermarkError::new(format!("Invalid filename '{}'", filename)))?;
+    let mismatch = || WatermarkError::new(format!("Filename '{}' does not match pattern '{}'", filename, pattern));
+    
+    let mut config = WatermarkConfig {
+        watermark_type: WatermarkType::Image,
+        ..WatermarkConfig::default()
+    };
+    let mut rest = stem;
+    let mut remaining_pattern = pattern;
+    
+    while !remaining_pattern.is_empty() {
+        match remaining_pattern.strip_prefix('{') {
+            Some(after_brace) => {
+                let (field, after) = after_brace.split_once('}')
+                    .ok_or_else(|| WatermarkError::new(format!("Unclosed placeholder in pattern '{}'", pattern)))?;
+                // 占位符取值到下一段字面量第一次出现处（模板末尾则取到结尾）
+                let literal = after.split('{').next().unwrap_or("");
+                if literal.is_empty() && !after.is_empty() {
+                    return Err(WatermarkError::new(format!("Adjacent placeholders in pattern '{}' are ambiguous", pattern)));
+                }
+                let end = if literal.is_empty() { rest.len() } else { rest.find(literal).ok_or_else(mismatch)? };
+                let value = &rest[..end];
+                if value.is_empty() {
+                    return Err(mismatch());
+                }
+                
+                let invalid = |e: String| WatermarkError::new(format!("Filename '{}': {}", filename, e));
+                match field {
+                    "name" => {}
+                    "transparency" => {
+                        let percent: f32 = parse_query_value(field, value).map_err(invalid)?;
+                        config.transparency = Some(percent / 100.0);
+                    }
+                    "rotate" => config.rotate = Some(parse_query_value(field, value).map_err(invalid)?),
+                    "x_offset" => config.x_offset = Some(parse_query_value(field, value).map_err(invalid)?),
+                    "y_offset" => config.y_offset = Some(parse_query_value(field, value).map_err(invalid)?),
+                    "position" => config.position = Some(value.to_string()),
+                    _ => return Err(WatermarkError::new(format!("Unknown placeholder '{{{}}}' in pattern '{}'", field, pattern))),
+                }
+                
+                rest = &rest[end..];
+                remaining_pattern = after;
+            }
+            None => {
+                let literal_end = remaining_pattern.find('{').unwrap_or(remaining_pattern.len());
+                let literal = &remaining_pattern[..literal_end];
+                rest = rest.strip_prefix(literal).ok_or_else(mismatch)?;
+                remaining_pattern = &remaining_pattern[literal_end..];
+            }
+        }
+    }
+    
+    if !rest.is_empty() {
+        return Err(mismatch());
+    }
+    Ok(config)
+}
+
+// 解析 JS 传入的配置，失败时返回 InvalidConfig 错误
+fn parse_config<T: serde::de::DeserializeOwned>(config_js: JsValue) -> Result<T, WatermarkError> {
+    serde_wasm_bindgen::from_value(config_js)
+        .map_err(|e| WatermarkError::with_code(ErrorCode::InvalidConfig, format!("Failed to parse config: {}", e)))
+}
+
+// WASM导出函数：添加水印
+#[wasm_bindgen]
+pub fn add_watermark(
+    image_data: &[u8],
+    config_js: JsValue,
+) -> Result<Vec<u8>, WatermarkError> {
+    // 解析配置
+    let config: WatermarkConfig = parse_config(config_js)?;
+    
+    Ok(process_watermark(image_data, &config)?.bytes)
+}
+
+// WASM导出函数：添加水印并返回带元信息的结果
+#[wasm_bindgen]
+pub fn add_watermark_ex(
+    image_data: &[u8],
+    config_js: JsValue,
+) -> Result<WatermarkResult, WatermarkError> {
+    // 解析配置
+    let config: WatermarkConfig = parse_config(config_js)?;
+    
+    process_watermark(image_data, &config)
+}
+
+// 百分号解码查询字符串中的键或值（'+' 视为空格）
+fn percent_decode(input: &str) -> Result<String, String> {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let hex = input.get(i + 1..i + 3)
+                    .filter(|hex| hex.chars().all(|c| c.is_ascii_hexdigit()))
+                    .ok_or_else(|| format!("Invalid percent-encoding in '{}'", input))?;
+                decoded.push(u8::from_str_radix(hex, 16).map_err(|e| e.to_string())?);
+                i += 2;
+            }
+            b => decoded.push(b),
+        }
+        i += 1;
+    }
+    
+    String::from_utf8(decoded).map_err(|_| format!("Invalid UTF-8 in '{}'", input))
+}
+
+// 解析查询字符串中的单个值
+fn parse_query_value<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String>
+where
+    T::Err: std::fmt::Display,
+{
+    value.trim().parse::<T>()
+        .map_err(|e| format!("Invalid value '{}' for '{}': {}", value, key, e))
+}
+
+// 解析查询字符串中的布尔值（true/false/1/0）
+fn parse_query_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" => Ok(true),
+        "false" | "0" | "no" => Ok(false),
+        _ => Err(format!("Invalid value '{}' for '{}': expected true or false", value, key)),
+    }
+}
+
+// 解析查询字符串中逗号分隔的定长数组，如 "255,255,255"
+fn parse_query_array<T: std::str::FromStr + Copy + Default, const N: usize>(key: &str, value: &str) -> Result<[T; N], String>
+where
+    T::Err: std::fmt::Display,
+{
+    let parts: Vec<&str> = value.split(',').collect();
+    if parts.len() != N {
+        return Err(format!("Invalid value '{}' for '{}': expected {} comma-separated numbers", value, key, N));
+    }
+    
+    let mut result = [T::default(); N];
+    for (slot, part) in result.iter_mut().zip(parts) {
+        *slot = parse_query_value(key, part)?;
+    }
+    Ok(result)
+}
+
+// 将 URL 查询字符串（如 "transparency=0.5&rotate=30&anchor=bottom-right"）解析为水印配置
+fn parse_query_config(query: &str, watermark_b64: &str) -> Result<WatermarkConfig, String> {
+    let mut config = WatermarkConfig {
+        watermark_type: WatermarkType::Image,
+        image_data: Some(watermark_b64.to_string()),
+        ..WatermarkConfig::default()
+    };
+    let mut x_offset_set = false;
+    let mut y_offset_set = false;
+    
+    for pair in query.trim_start_matches('?').split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let key = percent_decode(key)?;
+        let value = percent_decode(value)?;
         
-        result.clamp(0.0, 255.0) as u8
+        match key.as_str() {
+            "type" => config.watermark_type = WatermarkType::parse(&value)?,
+            "transparency" => config.transparency = Some(parse_query_value(&key, &value)?),
+            "rotate" => config.rotate = Some(parse_query_value(&key, &value)?),
+            "x_offset" => {
+                config.x_offset = Some(parse_query_value(&key, &value)?);
+                x_offset_set = true;
+            }
+            "y_offset" => {
+                config.y_offset = Some(parse_query_value(&key, &value)?);
+                y_offset_set = true;
+            }
+            "position" | "anchor" => config.position = Some(value),
+            "tile" => config.tile = Some(parse_query_bool(&key, &value)?),
+            "coverage_percent" => config.coverage_percent = Some(parse_query_value(&key, &value)?),
+            "tile_variance_threshold" => config.tile_variance_threshold = Some(parse_query_value(&key, &value)?),
+            "tile_variance_polarity" => config.tile_variance_polarity = Some(value),
+            "width" => config.width = Some(parse_query_value(&key, &value)?),
+            "height" => config.height = Some(parse_query_value(&key, &value)?),
+            "color_key" => config.color_key = Some(parse_query_array(&key, &value)?),
+            "color_key_tolerance" => config.color_key_tolerance = Some(parse_query_value(&key, &value)?),
+            "corner_opacities" => config.corner_opacities = Some(parse_query_array(&key, &value)?),
+            "source_name" => config.source_name = Some(value),
+            "edge_matte_color" => config.edge_matte_color = Some(value),
+            "edge_matte_strength" => config.edge_matte_strength = Some(parse_query_value(&key, &value)?),
+            _ => return Err(format!("Unknown query parameter '{}'", key)),
+        }
+    }
+    
+    // 使用锚点时，未显式给出的偏移量按 0 处理
+    if config.position.is_some() {
+        if !x_offset_set {
+            config.x_offset = None;
+        }
+        if !y_offset_set {
+            config.y_offset = None;
+        }
+    }
+    
+    Ok(config)
+}
+
+// WASM导出函数：按 transparency_sweep 中的每个不透明度分别输出水印结果（PNG）
+#[wasm_bindgen]
+pub fn add_watermark_sweep(
+    image_data: &[u8],
+    config_js: JsValue,
+) -> Result<Vec<WatermarkResult>, WatermarkError> {
+    let config: WatermarkConfig = parse_config(config_js)?;
+    
+    render_transparency_sweep(image_data, &config)?
+        .into_iter()
+        .map(|(img, placements)| Ok(WatermarkResult { placements, ..encode_watermark_result(&img, image_data, &config)? }))
+        .collect()
+}
+
+// WASM导出函数：添加水印并返回 data URL（MIME 与输出格式一致），可直接赋给 <img> 的 src
+#[wasm_bindgen]
+pub fn add_watermark_data_url(
+    image_data: &[u8],
+    config_js: JsValue,
+) -> Result<String, WatermarkError> {
+    let config: WatermarkConfig = parse_config(config_js)?;
+    
+    let result = process_watermark(image_data, &config)?;
+    Ok(format!("data:image/{};base64,{}", result.format, STANDARD.encode(&result.bytes)))
+}
+
+// WASM导出函数：添加一次水印，按 output_formats 分别编码为多种格式
+#[wasm_bindgen]
+pub fn add_watermark_formats(
+    image_data: &[u8],
+    config_js: JsValue,
+) -> Result<Vec<WatermarkResult>, WatermarkError> {
+    let config: WatermarkConfig = parse_config(config_js)?;
+    
+    render_output_formats(image_data, &config)
+}
+
+// 添加一次水印，按 output_formats 中的每种格式分别编码
+fn render_output_formats(image_data: &[u8], config: &WatermarkConfig) -> Result<Vec<WatermarkResult>, WatermarkError> {
+    let formats = config.output_formats.as_deref()
+        .filter(|formats| !formats.is_empty())
+        .ok_or_else(|| WatermarkError::with_code(ErrorCode::InvalidConfig, "output_formats is required for add_watermark_formats"))?;
+    
+    let (img, placements) = render_watermarked_image_with_placements(image_data, config)?;
+    formats.iter()
+        .map(|format| Ok(WatermarkResult {
+            placements: placements.clone(),
+            ..with_source_metadata(encode_as_format(&img, format, config)?, image_data, config)
+        }))
+        .collect()
+}
+
+// WASM导出函数：批量添加水印，配置只解析一次、水印只准备一次
+// 返回与输入等长的数组：成功的位置是编码后的图片（Uint8Array），失败的位置是对应的 WatermarkError，不会中断整批
+#[wasm_bindgen]
+pub fn add_watermark_batch(
+    images: Vec<js_sys::Uint8Array>,
+    config_js: JsValue,
+) -> Result<Vec<JsValue>, WatermarkError> {
+    let config: WatermarkConfig = parse_config(config_js)?;
+    
+    let images: Vec<Vec<u8>> = images.iter().map(|image| image.to_vec()).collect();
+    let results = render_watermark_batch(&images, &config, |_, _| {})?;
+    Ok(batch_results_to_js(results))
+}
+
+// WASM导出函数：与 add_watermark_batch 相同，每张图片处理完后调用 callback(index, total)
+// index 为刚处理完的图片序号（从 0 开始）；回调抛出的异常只记录日志，不会中断整批
+#[wasm_bindgen]
+pub fn add_watermark_batch_with_progress(
+    images: Vec<js_sys::Uint8Array>,
+    config_js: JsValue,
+    callback: &js_sys::Function,
+) -> Result<Vec<JsValue>, WatermarkError> {
+    let config: WatermarkConfig = parse_config(config_js)?;
+    
+    let images: Vec<Vec<u8>> = images.iter().map(|image| image.to_vec()).collect();
+    let results = render_watermark_batch(&images, &config, |index, total| {
+        let called = callback.call2(&JsValue::NULL, &JsValue::from(index as u32), &JsValue::from(total as u32));
+        if let Err(e) = called {
+            log_at(LOG_ERROR, || format!("进度回调出错（已忽略）: {:?}", e));
+        }
+    })?;
+    Ok(batch_results_to_js(results))
+}
+
+// 批处理结果转换为 JS 数组项：成功为 Uint8Array，失败为 WatermarkError
+fn batch_results_to_js(results: Vec<Result<WatermarkResult, WatermarkError>>) -> Vec<JsValue> {
+    results.into_iter().map(|result| match result {
+        Ok(result) => js_sys::Uint8Array::from(result.bytes.as_slice()).into(),
+        Err(e) => e.into(),
+    }).collect()
+}
+
+// 可复用的水印处理器：构造时解析、校验配置并准备一次水印，之后每次 apply 只处理底图
+// 自水印和 width_pct/height_pct 依赖底图尺寸，这类配置仍在每次 apply 时按底图准备水印
+#[wasm_bindgen]
+pub struct WatermarkProcessor {
+    config: WatermarkConfig,
+    watermark: Option<RgbaImage>,
+}
+
+#[wasm_bindgen]
+impl WatermarkProcessor {
+    #[wasm_bindgen(constructor)]
+    pub fn new(config_js: JsValue) -> Result<WatermarkProcessor, WatermarkError> {
+        let config: WatermarkConfig = parse_config(config_js)?;
+        let watermark = prepare_shared_watermark(&config)?;
+        Ok(Self { config, watermark })
+    }
+    
+    // 为一张底图添加水印，输出与 add_watermark 相同
+    pub fn apply(&self, image_data: &[u8]) -> Result<Vec<u8>, WatermarkError> {
+        Ok(render_with_shared_watermark(image_data, &self.config, self.watermark.as_ref())?.bytes)
+    }
+}
+
+// WASM导出函数：添加水印后按 tile_output 切成网格，分别编码为 PNG（右侧和底部的块可能小于设定尺寸）
+#[wasm_bindgen]
+pub fn add_watermark_tiles(
+    image_data: &[u8],
+    config_js: JsValue,
+) -> Result<Vec<WatermarkTile>, WatermarkError> {
+    let config: WatermarkConfig = parse_config(config_js)?;
+    let [tile_width, tile_height] = config.tile_output
+        .ok_or_else(|| WatermarkError::with_code(ErrorCode::InvalidConfig, "tile_output is required for add_watermark_tiles"))?;
+    
+    let img = render_watermarked_image(image_data, &config)?;
+    split_into_tiles(&img, (tile_width, tile_height), &config)
+}
+
+// 按行优先顺序把图片切成网格，每块无损编码为 PNG（忽略 output_format，不附带 EXIF），便于拼接还原
+fn split_into_tiles(img: &DynamicImage, tile_size: (u32, u32), config: &WatermarkConfig) -> Result<Vec<WatermarkTile>, WatermarkError> {
+    let (tile_width, tile_height) = tile_size;
+    let (width, height) = img.dimensions();
+    let mut tiles = Vec::new();
+    for (row, y) in (0..height).step_by(tile_height as usize).enumerate() {
+        for (col, x) in (0..width).step_by(tile_width as usize).enumerate() {
+            let tile = img.crop_imm(x, y, tile_width.min(width - x), tile_height.min(height - y));
+            tiles.push(WatermarkTile {
+                col: col as u32,
+                row: row as u32,
+                x,
+                y,
+                width: tile.width(),
+                height: tile.height(),
+                bytes: encode_as_format(&tile, "png", config)?.bytes,
+            });
+        }
+    }
+    Ok(tiles)
+}
+
+// WASM导出函数：一次叠加多个水印（配置数组），底图只解码和编码一次
+#[wasm_bindgen]
+pub fn add_watermarks(
+    image_data: &[u8],
+    configs_js: JsValue,
+) -> Result<Vec<u8>, WatermarkError> {
+    let configs: Vec<WatermarkConfig> = parse_config(configs_js)?;
+    
+    let img = render_watermark_layers(image_data, &configs)?;
+    Ok(encode_watermark_result(&img, image_data, &configs[0])?.bytes)
+}
+
+// WASM导出函数：水印图片以原始字节单独传入，省去 base64 编码和解码；config 中的 image_data 可以省略
+#[wasm_bindgen]
+pub fn add_watermark_raw(
+    image_data: &[u8],
+    watermark_bytes: &[u8],
+    config_js: JsValue,
+) -> Result<Vec<u8>, WatermarkError> {
+    let config = WatermarkConfig {
+        watermark_bytes: Some(watermark_bytes.to_vec()),
+        ..parse_config(config_js)?
     };
     
-    [
-        interpolate(p00[0], p10[0], p01[0], p11[0]),
-        interpolate(p00[1], p10[1], p01[1], p11[1]),
-        interpolate(p00[2], p10[2], p01[2], p11[2]),
-        interpolate(p00[3], p10[3], p01[3], p11[3]),
-    ]
+    Ok(process_watermark(image_data, &config)?.bytes)
 }
 
-// 旋转图片（使用双线性插值，提高清晰度）
-fn rotate_image(img: &DynamicImage, angle_degrees: f32) -> DynamicImage {
-    if angle_degrees == 0.0 {
-        return img.clone();
+// WASM导出函数：使用 JSON 字符串形式的配置添加水印，省去 JSON → JS 对象 → JsValue 的转换
+#[wasm_bindgen]
+pub fn add_watermark_json(
+    image_data: &[u8],
+    config_json: &str,
+) -> Result<Vec<u8>, WatermarkError> {
+    // serde_json 的错误信息自带行号和列号，如 "expected `,` or `}` at line 1 column 12"
+    let config: WatermarkConfig = serde_json::from_str(config_json)
+        .map_err(|e| WatermarkError::with_code(ErrorCode::InvalidConfig, format!("Failed to parse config: {}", e)))?;
+    
+    Ok(process_watermark(image_data, &config)?.bytes)
+}
+
+// WASM导出函数：使用查询字符串形式的配置添加水印
+#[wasm_bindgen]
+pub fn add_watermark_qs(
+    image_data: &[u8],
+    query: &str,
+    watermark_b64: &str,
+) -> Result<Vec<u8>, WatermarkError> {
+    let config = parse_query_config(query, watermark_b64)
+        .map_err(|e| WatermarkError::with_code(ErrorCode::InvalidConfig, format!("Failed to parse config: {}", e)))?;
+    
+    Ok(process_watermark(image_data, &config)?.bytes)
+}
+
+// WASM导出函数：最常见场景的简化接口（图片水印 + 不透明度 + 锚点），锚点默认为右下角
+#[wasm_bindgen]
+pub fn add_watermark_simple(
+    image_data: &[u8],
+    watermark_b64: &str,
+    transparency: f32,
+    anchor: Option<String>,
+) -> Result<Vec<u8>, WatermarkError> {
+    let config = WatermarkConfig {
+        watermark_type: WatermarkType::Image,
+        image_data: Some(watermark_b64.to_string()),
+        transparency: Some(transparency),
+        position: Some(anchor.unwrap_or_else(|| "bottom-right".to_string())),
+        x_offset: None,
+        y_offset: None,
+        ..WatermarkConfig::default()
+    };
+    
+    Ok(process_watermark(image_data, &config)?.bytes)
+}
+
+// WASM导出函数：返回水印的 SVG 覆盖层片段，定位与旋转和栅格化结果一致，可用于不栅格化的实时预览
+#[wasm_bindgen]
+pub fn watermark_svg(image_w: u32, image_h: u32, config_js: JsValue) -> Result<String, WatermarkError> {
+    let config: WatermarkConfig = parse_config(config_js)?;
+    
+    render_watermark_svg(image_w, image_h, &config)
+}
+
+// WASM导出函数：按因子 t 在两套水印配置之间插值，用于调参界面的样式过渡滑块
+#[wasm_bindgen]
+pub fn lerp_config(a_js: JsValue, b_js: JsValue, t: f32) -> Result<JsValue, WatermarkError> {
+    if !t.is_finite() {
+        return Err(WatermarkError::with_code(ErrorCode::InvalidConfig, format!("t must be a finite number, got {}", t)));
+    }
+    let a: WatermarkConfig = parse_config(a_js)?;
+    let b: WatermarkConfig = parse_config(b_js)?;
+    
+    serde_wasm_bindgen::to_value(&interpolate_config(&a, &b, t))
+        .map_err(|e| WatermarkError::new(format!("Failed to serialize config: {}", e)))
+}
+
+// WASM导出函数：读取 type 为 "invisible" 时嵌入的 payload
+// 图片经过 JPEG 等有损压缩、缩放或裁剪后水印会被破坏，此时返回 DecodeFailed
+#[wasm_bindgen]
+pub fn extract_watermark(image_data: &[u8]) -> Result<String, WatermarkError> {
+    let img = image::load_from_memory(image_data).map_err(|e| WatermarkError::with_code(
+        decode_error_code(image_data),
+        format!("Failed to load image: {}", e),
+    ))?;
+    extract_invisible_watermark(&img.to_rgba8())
+        .map_err(|e| WatermarkError::with_code(ErrorCode::DecodeFailed, e))
+}
+
+// WASM导出函数：只读取文件头探测图片的尺寸、格式和是否带透明通道，不解码像素
+// 无法识别或不支持的格式返回 UnsupportedFormat，文件头损坏返回 DecodeFailed
+#[wasm_bindgen]
+pub fn probe_image(image_data: &[u8]) -> Result<JsValue, WatermarkError> {
+    let info = probe_image_info(image_data)?;
+    serde_wasm_bindgen::to_value(&info)
+        .map_err(|e| WatermarkError::new(format!("Failed to serialize image info: {}", e)))
+}
+
+// WASM导出函数：预加载水印图片，之后可在配置中通过 watermark_key 引用
+#[wasm_bindgen]
+pub fn preload_watermark(key: &str, image_b64: &str) -> Result<(), WatermarkError> {
+    let watermark_img = decode_watermark_image(image_b64)
+        .map_err(|e| e.context(format_args!("Failed to preload watermark '{}'", key)))?;
+    
+    WATERMARK_REGISTRY.with(|registry| {
+        registry.borrow_mut().insert(key.to_string(), watermark_img);
+    });
+    
+    Ok(())
+}
+
+// WASM导出函数：清空所有预加载的水印
+#[wasm_bindgen]
+pub fn clear_watermarks() {
+    WATERMARK_REGISTRY.with(|registry| registry.borrow_mut().clear());
+}
+
+// WASM导出函数：添加水印（异步接口）
+// 与 add_watermark 依次调用相同的处理步骤，输出逐字节一致；解码、叠加水印、后处理、编码之间让出事件循环，
+// 主线程可以在步骤之间响应输入和重绘（单个步骤内部仍是同步的，完全不阻塞需使用 Worker）
+#[wasm_bindgen]
+pub async fn add_watermark_async(
+    image_data: Vec<u8>,
+    config_js: JsValue,
+) -> Result<Vec<u8>, WatermarkError> {
+    let config: WatermarkConfig = parse_config(config_js)?;
+    
+    Ok(process_watermark_in_steps(&image_data, &config, yield_to_event_loop).await?.bytes)
+}
+
+// 与 process_watermark 相同的处理流程，每个耗时步骤之后等待 pause()（异步接口在这里让出事件循环）
+// 动画 GIF 与 process_watermark 走同一个逐帧分支，保证两个接口的输出一致
+async fn process_watermark_in_steps<F: std::future::Future<Output = ()>>(
+    image_data: &[u8],
+    config: &WatermarkConfig,
+    mut pause: impl FnMut() -> F,
+) -> Result<WatermarkResult, WatermarkError> {
+    if is_animated_gif_request(image_data, config) {
+        pause().await;
+        return process_watermark(image_data, config);
+    }
+    
+    let mut img = load_base_image(image_data, config)?;
+    let original = config.diff_output.unwrap_or(false).then(|| img.to_rgba8());
+    pause().await;
+    
+    let placements = add_watermark_by_type(&mut img, config)?;
+    pause().await;
+    
+    let img = finish_watermarked_image(img, original.as_ref(), config)?;
+    pause().await;
+    
+    Ok(WatermarkResult { placements, ..encode_watermark_result(&img, image_data, config)? })
+}
+
+// 通过 setTimeout(0) 等待一个宏任务，让浏览器有机会处理输入和重绘（Promise 微任务做不到这一点）
+// 窗口和 Worker 中都可用；没有 setTimeout 的环境直接继续
+async fn yield_to_event_loop() {
+    let promise = js_sys::Promise::new(&mut |resolve, _reject| {
+        let global = js_sys::global();
+        let set_timeout = js_sys::Reflect::get(&global, &JsValue::from_str("setTimeout"))
+            .ok()
+            .and_then(|f| f.dyn_into::<js_sys::Function>().ok());
+        let scheduled = set_timeout.is_some_and(|f| f.call2(&global, &resolve, &JsValue::from(0)).is_ok());
+        if !scheduled {
+            let _ = resolve.call0(&JsValue::NULL);
+        }
+    });
+    let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+}
+
+// 初始化函数
+#[wasm_bindgen(start)]
+pub fn init() {
+    #[cfg(feature = "console_error_panic_hook")]
+    set_once();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // 编码为 PNG 字节
+    fn png_bytes(img: &RgbaImage) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        DynamicImage::ImageRgba8(img.clone())
+            .write_to(&mut Cursor::new(&mut buffer), image::ImageOutputFormat::Png)
+            .unwrap();
+        buffer
+    }
+    
+    fn solid(width: u32, height: u32, color: [u8; 4]) -> RgbaImage {
+        RgbaImage::from_pixel(width, height, image::Rgba(color))
+    }
+    
+    fn decode(bytes: &[u8]) -> RgbaImage {
+        image::load_from_memory(bytes).unwrap().to_rgba8()
+    }
+    
+    // 完全不透明、位于左上角 (0, 0) 的图片水印配置
+    fn image_config(watermark: &RgbaImage) -> WatermarkConfig {
+        WatermarkConfig {
+            watermark_type: WatermarkType::Image,
+            watermark_bytes: Some(png_bytes(watermark)),
+            transparency: Some(1.0),
+            x_offset: Some(0),
+            y_offset: Some(0),
+            ..WatermarkConfig::default()
+        }
+    }
+    
+    // 添加水印，返回各水印实际叠加的区域
+    fn placements(base: &RgbaImage, config: &WatermarkConfig) -> Vec<WatermarkPlacement> {
+        process_watermark(&png_bytes(base), config).unwrap().placements
+    }
+    
+    // 添加水印并解码结果
+    fn render(base: &RgbaImage, config: WatermarkConfig) -> RgbaImage {
+        decode(&add_watermark_native(&png_bytes(base), config).unwrap())
+    }
+    
+    // 轮询 future 直到完成（测试中的 pause 都是立即就绪的）
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let mut context = std::task::Context::from_waker(std::task::Waker::noop());
+        loop {
+            if let std::task::Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+        }
+    }
+    
+    // 每帧纯色、延时 70ms 的动画 GIF
+    fn animated_gif(colors: &[[u8; 4]], repeat: Option<image::codecs::gif::Repeat>) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        {
+            let mut encoder = image::codecs::gif::GifEncoder::new(&mut buffer);
+            if let Some(repeat) = repeat {
+                encoder.set_repeat(repeat).unwrap();
+            }
+            for &color in colors {
+                let delay = image::Delay::from_numer_denom_ms(70, 1);
+                encoder.encode_frame(image::Frame::from_parts(solid(40, 30, color), 0, 0, delay)).unwrap();
+            }
+        }
+        buffer
+    }
+    
+    // 编码为 JPEG 并在 SOI 之后插入只含 Orientation 标签的 EXIF 段
+    fn jpeg_with_orientation(img: &RgbaImage, orientation: u16) -> Vec<u8> {
+        let mut jpeg = Vec::new();
+        DynamicImage::ImageRgba8(img.clone()).to_rgb8()
+            .write_to(&mut Cursor::new(&mut jpeg), image::ImageOutputFormat::Jpeg(95))
+            .unwrap();
+        let mut tiff = b"MM\0\x2a".to_vec();
+        tiff.extend_from_slice(&8u32.to_be_bytes());
+        tiff.extend_from_slice(&1u16.to_be_bytes());
+        tiff.extend_from_slice(&[0x01, 0x12, 0, 3, 0, 0, 0, 1]);
+        tiff.extend_from_slice(&orientation.to_be_bytes());
+        tiff.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+        let mut segment = vec![0xFF, 0xE1];
+        segment.extend_from_slice(&((2 + 6 + tiff.len()) as u16).to_be_bytes());
+        segment.extend_from_slice(b"Exif\0\0");
+        segment.extend_from_slice(&tiff);
+        jpeg.splice(2..2, segment);
+        jpeg
+    }
+    
+    fn gif_frames(bytes: &[u8]) -> Vec<image::Frame> {
+        use image::AnimationDecoder;
+        image::codecs::gif::GifDecoder::new(Cursor::new(bytes)).unwrap().into_frames().collect_frames().unwrap()
+    }
+    
+    #[test]
+    fn native_entry_point_composites_watermark() {
+        let out = render(&solid(20, 20, [255, 255, 255, 255]), image_config(&solid(4, 4, [255, 0, 0, 255])));
+        assert_eq!(out.get_pixel(1, 1).0, [255, 0, 0, 255]);
+        assert_eq!(out.get_pixel(10, 10).0, [255, 255, 255, 255]);
+    }
+    
+    #[test]
+    fn stepped_processing_keeps_gif_animation() {
+        let gif = animated_gif(&[[255, 0, 0, 255], [0, 0, 255, 255], [0, 255, 0, 255]], None);
+        let config = WatermarkConfig { animated: Some(true), ..image_config(&solid(8, 8, [255, 255, 255, 255])) };
+        let stepped = block_on(process_watermark_in_steps(&gif, &config, || std::future::ready(()))).unwrap();
+        assert_eq!(gif_frames(&stepped.bytes).len(), 3);
+        assert_eq!(stepped.bytes, add_watermark_native(&gif, config).unwrap());
+    }
+    
+    #[test]
+    fn animated_gif_keeps_loop_count() {
+        use image::codecs::gif::Repeat;
+        let config = WatermarkConfig { animated: Some(true), ..image_config(&solid(8, 8, [255, 255, 255, 255])) };
+        let frames = [[255, 0, 0, 255], [0, 0, 255, 255]];
+        for repeat in [Some(Repeat::Infinite), Some(Repeat::Finite(3)), None] {
+            let out = add_watermark_native(&animated_gif(&frames, repeat), config.clone()).unwrap();
+            // image 的 Repeat 没有实现 PartialEq，按 Debug 输出比较
+            assert_eq!(format!("{:?}", gif_repeat(&out).unwrap()), format!("{:?}", repeat));
+        }
+    }
+    
+    #[test]
+    fn frame_selector_marks_only_selected_frames() {
+        let colors = [[255, 0, 0, 255], [0, 0, 255, 255], [0, 255, 0, 255], [255, 255, 0, 255], [0, 255, 255, 255]];
+        let source = gif_frames(&animated_gif(&colors, None));
+        let config = WatermarkConfig {
+            animated: Some(true),
+            frame_selector: Some("every:2".to_string()),
+            ..image_config(&solid(8, 8, [255, 255, 255, 255]))
+        };
+        let out = gif_frames(&add_watermark_native(&animated_gif(&colors, None), config).unwrap());
+        let changed: Vec<bool> = source.iter().zip(&out).map(|(a, b)| a.buffer() != b.buffer()).collect();
+        assert_eq!(changed, [true, false, true, false, true]);
+    }
+    
+    #[test]
+    fn png_interlace_writes_adam7_with_same_pixels() {
+        // 奇数尺寸覆盖不完整的扫描遍，半透明像素走 RGBA 分支
+        let mut base = solid(13, 7, [255, 255, 255, 255]);
+        base.put_pixel(12, 6, image::Rgba([10, 20, 30, 128]));
+        let plain = add_watermark_native(&png_bytes(&base), image_config(&solid(4, 4, [255, 0, 0, 255]))).unwrap();
+        let config = WatermarkConfig { png_interlace: Some(true), ..image_config(&solid(4, 4, [255, 0, 0, 255])) };
+        let interlaced = add_watermark_native(&png_bytes(&base), config).unwrap();
+        // IHDR 数据中的隔行方式字节
+        assert_eq!((plain[28], interlaced[28]), (0, 1));
+        assert_eq!(decode(&interlaced), decode(&plain));
+    }
+    
+    #[test]
+    fn tile_blocks_draw_rows_times_cols_and_skip_offscreen() {
+        let watermark = solid(8, 8, [255, 0, 0, 255]);
+        let config = WatermarkConfig { tile: Some(true), tile_rows: Some(3), tile_cols: Some(4), ..image_config(&watermark) };
+        assert_eq!(tile_grid(&config, &watermark, 100, 100).unwrap().len(), 12);
+        
+        // 超大间距：块尺寸饱和而不溢出，只保留与图片相交的水印块
+        let config = WatermarkConfig {
+            tile_rows: Some(MAX_TILE_BLOCKS),
+            tile_cols: Some(MAX_TILE_BLOCKS),
+            tile_spacing_x: Some(u32::MAX / 2),
+            tile_spacing_y: Some(u32::MAX / 2),
+            position: Some("top-left".to_string()),
+            ..config
+        };
+        assert_eq!(tile_grid(&config, &watermark, 100, 100).unwrap(), [(0, 0, 0, 0)]);
+        
+        let config = WatermarkConfig { tile_rows: Some(MAX_TILE_BLOCKS + 1), ..config };
+        assert!(validate_config(&config).is_err());
+    }
+    
+    #[test]
+    fn drop_shadow_offsets_and_limits() {
+        let config = WatermarkConfig {
+            shadow: Some(true),
+            shadow_offset_x: Some(3),
+            shadow_offset_y: Some(2),
+            shadow_blur: Some(0.0),
+            ..image_config(&solid(4, 4, [255, 0, 0, 255]))
+        };
+        let out = render(&solid(20, 20, [255, 255, 255, 255]), config.clone());
+        assert_eq!(out.get_pixel(1, 1).0, [255, 0, 0, 255]);
+        assert_eq!(out.get_pixel(5, 4).0, [0, 0, 0, 255]);
+        assert_eq!(out.get_pixel(7, 6).0, [255, 255, 255, 255]);
+        
+        assert!(validate_config(&WatermarkConfig { shadow_blur: Some(100.5), ..config.clone() }).is_err());
+        assert!(validate_config(&WatermarkConfig { shadow_offset_y: Some(-1001), ..config }).is_err());
+    }
+    
+    #[test]
+    fn invisible_payload_round_trips_and_stays_last() {
+        let base = png_bytes(&solid(40, 40, [200, 150, 100, 255]));
+        let invisible = WatermarkConfig {
+            watermark_type: WatermarkType::Invisible,
+            payload: Some("order-42".to_string()),
+            ..WatermarkConfig::default()
+        };
+        let marked = add_watermark_native(&base, invisible.clone()).unwrap();
+        assert_eq!(extract_watermark(&marked).unwrap(), "order-42");
+        
+        // 可见水印的 z_index 更大、覆盖嵌入区域，不可见水印仍最后嵌入
+        let visible = WatermarkConfig { z_index: Some(5), ..image_config(&solid(40, 40, [255, 0, 0, 255])) };
+        let layers = [invisible.clone(), visible.clone()];
+        let img = render_watermark_layers(&base, &layers).unwrap();
+        let bytes = encode_watermark_result(&img, &base, &layers[0]).unwrap().bytes;
+        assert_eq!(extract_watermark(&bytes).unwrap(), "order-42");
+        
+        assert!(render_watermark_layers(&base, &[visible, invisible.clone()]).is_err());
+        assert!(validate_config(&WatermarkConfig { tile_output: Some([16, 16]), ..invisible }).is_err());
+    }
+    
+    #[test]
+    fn tiles_reassemble_into_full_image() {
+        let base = solid(23, 17, [255, 255, 255, 255]);
+        let config = WatermarkConfig {
+            output_format: Some("jpeg".to_string()),
+            tile_output: Some([8, 6]),
+            ..image_config(&solid(5, 5, [0, 0, 255, 200]))
+        };
+        let img = render_watermarked_image(&png_bytes(&base), &config).unwrap();
+        let tiles = split_into_tiles(&img, (8, 6), &config).unwrap();
+        assert_eq!(tiles.len(), 3 * 3);
+        
+        let mut assembled = RgbaImage::new(23, 17);
+        for tile in &tiles {
+            image::imageops::replace(&mut assembled, &decode(&tile.bytes), tile.x as i64, tile.y as i64);
+        }
+        assert_eq!(assembled, img.to_rgba8());
+    }
+    
+    #[test]
+    fn center_position_on_odd_and_even_images() {
+        // (图片宽高, 水印边长, 期望左上角)：多余的一个像素留在右侧/下方
+        for ((width, height), size, expected) in [((20, 14), 4, (8, 5)), ((21, 15), 5, (8, 5)), ((21, 15), 4, (8, 5)), ((20, 14), 5, (7, 4))] {
+            let config = WatermarkConfig { position: Some("center".to_string()), ..image_config(&solid(size, size, [255, 0, 0, 255])) };
+            let out = render(&solid(width, height, [255, 255, 255, 255]), config);
+            let red: Vec<(u32, u32)> = out.enumerate_pixels().filter(|(_, _, p)| p[1] == 0).map(|(x, y, _)| (x, y)).collect();
+            assert_eq!((red.len(), red[0]), ((size * size) as usize, expected), "{}x{} image, {}px watermark", width, height, size);
+        }
+    }
+    
+    #[test]
+    fn non_finite_floats_are_rejected() {
+        type SetFloat = fn(&mut WatermarkConfig, f32);
+        let setters: [(&str, SetFloat); 22] = [
+            ("transparency", |c, v| c.transparency = Some(v)),
+            ("font_size", |c, v| c.font_size = Some(v)),
+            ("x_offset_pct", |c, v| c.x_offset_pct = Some(v)),
+            ("y_offset_pct", |c, v| c.y_offset_pct = Some(v)),
+            ("width_pct", |c, v| c.width_pct = Some(v)),
+            ("height_pct", |c, v| c.height_pct = Some(v)),
+            ("rotate", |c, v| c.rotate = Some(v)),
+            ("edge_matte_strength", |c, v| c.edge_matte_strength = Some(v)),
+            ("coverage_percent", |c, v| c.coverage_percent = Some(v)),
+            ("tile_angle", |c, v| c.tile_angle = Some(v)),
+            ("tile_variance_threshold", |c, v| c.tile_variance_threshold = Some(v)),
+            ("diff_amplification", |c, v| c.diff_amplification = Some(v)),
+            ("ensure_visible_max", |c, v| c.ensure_visible_max = Some(v)),
+            ("shadow_blur", |c, v| c.shadow_blur = Some(v)),
+            ("jitter", |c, v| c.jitter = Some(v)),
+            ("rotation_jitter", |c, v| c.rotation_jitter = Some(v)),
+            ("corner_opacities", |c, v| c.corner_opacities = Some([1.0, v, 1.0, 1.0])),
+            ("subpixel_offset", |c, v| c.subpixel_offset = Some([0.0, v])),
+            ("transparency_sweep", |c, v| c.transparency_sweep = Some(vec![0.5, v])),
+            ("self_watermark.strength", |c, v| c.self_watermark = Some(SelfWatermarkConfig { strength: Some(v), scale: None })),
+            ("self_watermark.scale", |c, v| c.self_watermark = Some(SelfWatermarkConfig { strength: None, scale: Some(v) })),
+            ("label_background.opacity", |c, v| {
+                c.label_background = Some(LabelBgConfig { color: None, gradient: None, padding: None, corner_radius: None, opacity: Some(v) });
+            }),
+        ];
+        for (name, set) in setters {
+            for value in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+                let mut config = image_config(&solid(4, 4, [255, 0, 0, 255]));
+                set(&mut config, value);
+                assert!(validate_config(&config).is_err(), "{} = {} was accepted", name, value);
+            }
+        }
+    }
+    
+    #[test]
+    fn corrupt_watermark_uses_fallback() {
+        let config = WatermarkConfig {
+            watermark_bytes: None,
+            image_data: Some(STANDARD.encode(b"not an image")),
+            fallback_image_data: Some(STANDARD.encode(png_bytes(&solid(4, 4, [0, 0, 255, 255])))),
+            ..image_config(&solid(4, 4, [255, 0, 0, 255]))
+        };
+        let out = render(&solid(20, 20, [255, 255, 255, 255]), config.clone());
+        assert_eq!(out.get_pixel(1, 1).0, [0, 0, 255, 255]);
+        
+        let without_fallback = WatermarkConfig { fallback_image_data: None, ..config };
+        assert!(add_watermark_native(&png_bytes(&solid(20, 20, [255, 255, 255, 255])), without_fallback).is_err());
+    }
+    
+    #[test]
+    fn variance_threshold_tiles_only_busy_half() {
+        // 左半平坦灰色，右半逐像素黑白交替
+        let base = RgbaImage::from_fn(40, 20, |x, y| match x < 20 {
+            true => image::Rgba([128, 128, 128, 255]),
+            false => image::Rgba(if (x + y) % 2 == 0 { [0, 0, 0, 255] } else { [255, 255, 255, 255] }),
+        });
+        let config = WatermarkConfig {
+            tile: Some(true),
+            tile_variance_threshold: Some(0.01),
+            ..image_config(&solid(4, 4, [255, 0, 0, 255]))
+        };
+        let busy = placements(&base, &config);
+        assert!(!busy.is_empty() && busy.iter().all(|p| p.x >= 20));
+        
+        let config = WatermarkConfig { tile_variance_polarity: Some("below".to_string()), ..config };
+        let flat = placements(&base, &config);
+        assert!(!flat.is_empty() && flat.iter().all(|p| p.x + p.width <= 20));
+    }
+    
+    #[test]
+    fn suggested_filename_uses_output_extension() {
+        let base = png_bytes(&solid(20, 20, [255, 255, 255, 255]));
+        for format in ["png", "jpeg", "webp"] {
+            let config = WatermarkConfig {
+                source_name: Some("uploads/photo.jpg".to_string()),
+                output_format: Some(format.to_string()),
+                ..image_config(&solid(4, 4, [255, 0, 0, 255]))
+            };
+            let result = process_watermark(&base, &config).unwrap();
+            assert_eq!(result.suggested_filename, format!("photo_watermarked.{}", format));
+            assert_eq!(image::guess_format(&result.bytes).unwrap().extensions_str()[0], if format == "jpeg" { "jpg" } else { format });
+        }
+    }
+    
+    #[test]
+    fn color_key_removes_jpeg_background() {
+        let logo = image::RgbImage::from_fn(16, 16, |x, y| {
+            image::Rgb(if (4..12).contains(&x) && (4..12).contains(&y) { [0, 0, 0] } else { [255, 255, 255] })
+        });
+        let mut jpeg = Vec::new();
+        DynamicImage::ImageRgb8(logo).write_to(&mut Cursor::new(&mut jpeg), image::ImageOutputFormat::Jpeg(90)).unwrap();
+        let config = WatermarkConfig {
+            watermark_bytes: Some(jpeg),
+            color_key: Some([255, 255, 255]),
+            color_key_tolerance: Some(40),
+            ..image_config(&solid(1, 1, [0, 0, 0, 0]))
+        };
+        let watermark = load_and_prepare_watermark(&config).unwrap();
+        assert_eq!(watermark.get_pixel(0, 0)[3], 0);
+        assert_eq!(watermark.get_pixel(8, 8)[3], 255);
+    }
+    
+    #[test]
+    fn corner_opacities_interpolate_bilinearly() {
+        // 5x5 水印：四角分别为 [0, 1, 0.5, 1]，中心为四者的平均值 0.625
+        let config = WatermarkConfig {
+            corner_opacities: Some([0.0, 1.0, 0.5, 1.0]),
+            ..image_config(&solid(5, 5, [255, 0, 0, 255]))
+        };
+        let watermark = load_and_prepare_watermark(&config).unwrap();
+        let alpha = |x, y| watermark.get_pixel(x, y)[3];
+        assert_eq!([alpha(0, 0), alpha(4, 0), alpha(0, 4), alpha(4, 4)], [0, 255, 128, 255]);
+        assert_eq!(alpha(2, 2), (255.0f32 * 0.625).round() as u8);
+    }
+    
+    #[test]
+    fn custom_output_encoder_receives_watermarked_pixels() {
+        // 把图片编码为 "宽 高 左上角像素" 的文本
+        struct DebugEncoder;
+        impl OutputEncoder for DebugEncoder {
+            fn encode(&self, img: &RgbaImage) -> Result<Vec<u8>, WatermarkError> {
+                Ok(format!("{} {} {:?}", img.width(), img.height(), img.get_pixel(0, 0).0).into_bytes())
+            }
+        }
+        let config = image_config(&solid(4, 4, [255, 0, 0, 255]));
+        let bytes = watermark_image_with_encoder(&png_bytes(&solid(20, 10, [255, 255, 255, 255])), &config, &DebugEncoder).unwrap();
+        assert_eq!(String::from_utf8(bytes).unwrap(), "20 10 [255, 0, 0, 255]");
+    }
+    
+    #[test]
+    fn coverage_percent_matches_measured_coverage() {
+        for target in [10.0, 25.0] {
+            let config = WatermarkConfig { coverage_percent: Some(target), ..image_config(&solid(6, 4, [255, 0, 0, 255])) };
+            let out = render(&solid(120, 90, [255, 255, 255, 255]), config);
+            let covered = out.pixels().filter(|p| p[1] == 0).count() as f32 / (120.0 * 90.0) * 100.0;
+            assert!((covered - target).abs() <= target * 0.2, "target {}%, measured {}%", target, covered);
+        }
+    }
+    
+    #[test]
+    fn query_strings_parse_into_config() {
+        let config = parse_query_config("?transparency=0.3&rotate=-15&anchor=bottom-right&tile=1&color_key=255,0,10&source_name=my%20photo.png", "abc").unwrap();
+        assert_eq!(config.transparency, Some(0.3));
+        assert_eq!(config.rotate, Some(-15.0));
+        assert_eq!(config.position.as_deref(), Some("bottom-right"));
+        assert_eq!(config.tile, Some(true));
+        assert_eq!(config.color_key, Some([255, 0, 10]));
+        assert_eq!(config.source_name.as_deref(), Some("my photo.png"));
+        assert_eq!(config.image_data.as_deref(), Some("abc"));
+        // 使用锚点且未给出偏移量时偏移为 0
+        assert_eq!((config.x_offset, config.y_offset), (None, None));
+        
+        for bad in ["transparency=abc", "tile=maybe", "color_key=1,2", "unknown=1", "source_name=%ZZ"] {
+            assert!(parse_query_config(bad, "abc").is_err(), "{} was accepted", bad);
+        }
+    }
+    
+    #[cfg(feature = "sdf-text")]
+    #[test]
+    fn sdf_resize_keeps_edges_sharp_at_any_size() {
+        // 硬边圆形作为字形；每行最多两个边缘，每个边缘的半透明过渡不超过 2 像素
+        let glyph = RgbaImage::from_fn(20, 20, |x, y| {
+            let inside = (x as f32 - 9.5).powi(2) + (y as f32 - 9.5).powi(2) <= 64.0;
+            image::Rgba([255, 255, 255, if inside { 255 } else { 0 }])
+        });
+        for size in [40, 120] {
+            let scaled = sdf_resize(&glyph, size, size);
+            let widest = scaled.rows().map(|row| row.filter(|p| p[3] > 0 && p[3] < 255).count()).max().unwrap();
+            assert!(widest <= 4, "{}px: {} partially transparent pixels in one row", size, widest);
+            // 对照：Lanczos 放大的边缘随倍数变宽
+            let smooth = image::imageops::resize(&glyph, size, size, image::imageops::FilterType::Lanczos3);
+            assert!(smooth.rows().map(|row| row.filter(|p| p[3] > 0 && p[3] < 255).count()).max().unwrap() > widest);
+        }
+    }
+    
+    #[test]
+    fn diff_output_is_black_except_at_watermark() {
+        let base = RgbaImage::from_fn(20, 20, |x, y| image::Rgba([(x * 10) as u8, (y * 10) as u8, 90, 255]));
+        let noop = WatermarkConfig { diff_output: Some(true), ..image_config(&solid(4, 4, [0, 0, 0, 0])) };
+        assert!(render(&base, noop).pixels().all(|p| p.0 == [0, 0, 0, 255]));
+        
+        let config = WatermarkConfig { diff_output: Some(true), x_offset: Some(8), y_offset: Some(6), ..image_config(&solid(4, 4, [255, 0, 0, 255])) };
+        let diff = render(&base, config);
+        for (x, y, p) in diff.enumerate_pixels() {
+            let inside = (8..12).contains(&x) && (6..10).contains(&y);
+            assert_eq!(p.0[..3] != [0, 0, 0], inside, "({}, {})", x, y);
+        }
+    }
+    
+    #[test]
+    fn chroma_444_keeps_colored_edges_sharper_than_420() {
+        // 红蓝交界落在奇数列，4:2:0 抽样会把两侧颜色混在一起
+        let base = RgbaImage::from_fn(32, 32, |x, _| image::Rgba(if x < 15 { [220, 0, 0, 255] } else { [0, 0, 220, 255] }));
+        let edge_error = |subsampling: &str| {
+            let config = WatermarkConfig {
+                output_format: Some("jpeg".to_string()),
+                quality: Some(95),
+                jpeg_chroma_subsampling: Some(subsampling.to_string()),
+                ..image_config(&solid(1, 1, [0, 0, 0, 0]))
+            };
+            let out = render(&base, config);
+            (0..32u32).flat_map(|y| (13..17u32).map(move |x| (x, y)))
+                .map(|(x, y)| (0..3).map(|c| out.get_pixel(x, y)[c].abs_diff(base.get_pixel(x, y)[c]) as u32).sum::<u32>())
+                .sum::<u32>()
+        };
+        assert!(edge_error("4:4:4") < edge_error("4:2:0"));
+    }
+    
+    #[test]
+    fn preloaded_watermarks_are_referenced_by_key_until_cleared() {
+        preload_watermark("logo", &STANDARD.encode(png_bytes(&solid(4, 4, [0, 255, 0, 255])))).unwrap();
+        assert!(preload_watermark("broken", "not base64!").is_err());
+        
+        let config = WatermarkConfig { watermark_bytes: None, watermark_key: Some("logo".to_string()), ..image_config(&solid(1, 1, [0, 0, 0, 0])) };
+        let base = solid(20, 20, [255, 255, 255, 255]);
+        assert_eq!(render(&base, config.clone()).get_pixel(1, 1).0, [0, 255, 0, 255]);
+        
+        clear_watermarks();
+        assert!(add_watermark_native(&png_bytes(&base), config).is_err());
     }
     
-    let angle_rad = angle_degrees * std::f32::consts::PI / 180.0;
-    let cos_r = angle_rad.cos();
-    let sin_r = angle_rad.sin();
-    
-    let (width, height) = img.dimensions();
-    let center_x = width as f32 / 2.0;
-    let center_y = height as f32 / 2.0;
+    #[test]
+    fn autocrop_trims_transparent_borders() {
+        let mut base = solid(100, 100, [0, 0, 0, 0]);
+        image::imageops::replace(&mut base, &solid(10, 10, [0, 0, 255, 255]), 40, 40);
+        let config = WatermarkConfig {
+            autocrop: Some(true),
+            position: Some("bottom-right".to_string()),
+            ..image_config(&solid(5, 5, [255, 0, 0, 255]))
+        };
+        let out = render(&base, config);
+        assert_eq!(out.dimensions(), (60, 60));
+        assert_eq!((out.get_pixel(0, 0).0, out.get_pixel(59, 59).0), ([0, 0, 255, 255], [255, 0, 0, 255]));
+    }
     
-    // 计算旋转后的新尺寸
-    let new_width = (width as f32 * cos_r.abs() + height as f32 * sin_r.abs()).ceil() as u32;
-    let new_height = (width as f32 * sin_r.abs() + height as f32 * cos_r.abs()).ceil() as u32;
+    #[test]
+    fn border_watermark_stays_in_the_band() {
+        let config = WatermarkConfig {
+            border_watermark: Some(BorderConfig { thickness: 8, color: None, spacing: None }),
+            ..image_config(&solid(4, 4, [255, 0, 0, 255]))
+        };
+        let out = render(&solid(60, 60, [255, 255, 255, 255]), config);
+        let in_band = |x: u32, y: u32| x < 8 || y < 8 || x >= 52 || y >= 52;
+        let inked: Vec<(u32, u32)> = out.enumerate_pixels().filter(|(_, _, p)| p.0 != [255, 255, 255, 255]).map(|(x, y, _)| (x, y)).collect();
+        assert!(!inked.is_empty());
+        assert!(inked.iter().all(|&(x, y)| in_band(x, y)));
+        // 四条边上都有水印
+        assert!(inked.iter().any(|&(_, y)| y < 8) && inked.iter().any(|&(_, y)| y >= 52));
+        assert!(inked.iter().any(|&(x, _)| x < 8) && inked.iter().any(|&(x, _)| x >= 52));
+    }
     
-    let mut result = RgbaImage::new(new_width, new_height);
-    let new_center_x = new_width as f32 / 2.0;
-    let new_center_y = new_height as f32 / 2.0;
+    #[test]
+    fn simple_wrapper_matches_full_config() {
+        let base = png_bytes(&solid(30, 20, [255, 255, 255, 255]));
+        let logo = STANDARD.encode(png_bytes(&solid(6, 4, [255, 0, 0, 255])));
+        let simple = add_watermark_simple(&base, &logo, 0.4, Some("top-right".to_string())).unwrap();
+        let full = WatermarkConfig {
+            watermark_type: WatermarkType::Image,
+            image_data: Some(logo),
+            transparency: Some(0.4),
+            position: Some("top-right".to_string()),
+            x_offset: Some(0),
+            y_offset: Some(0),
+            ..WatermarkConfig::default()
+        };
+        assert_eq!(simple, add_watermark_native(&base, full).unwrap());
+        assert_ne!(decode(&simple).get_pixel(29, 0).0, [255, 255, 255, 255]);
+    }
     
-    // 转换为 RGBA8 以便便快速访问
-    let img_rgba = img.to_rgba8();
-    let img_data = img_rgba.as_ref();
-    let result_data = result.as_mut();
+    #[test]
+    fn very_wide_watermark_keeps_at_least_one_pixel_height() {
+        let config = WatermarkConfig { width: Some(5), ..image_config(&solid(1000, 10, [255, 0, 0, 255])) };
+        assert_eq!(load_and_prepare_watermark(&config).unwrap().dimensions(), (5, 1));
+        // 四舍五入而不是截断：1000x10 缩放到宽 150 得到高 2（1.5 舍入），而不是 1
+        let config = WatermarkConfig { width: Some(150), ..config };
+        assert_eq!(load_and_prepare_watermark(&config).unwrap().height(), 2);
+    }
     
-    let width_usize = width as usize;
-    let height_usize = height as usize;
-    let new_width_usize = new_width as usize;
+    #[test]
+    fn force_rgb_writes_png_without_alpha() {
+        let base = png_bytes(&solid(20, 20, [0, 0, 0, 0]));
+        let config = WatermarkConfig {
+            force_rgb: Some(true),
+            background_color: Some("#00FF00".to_string()),
+            ..image_config(&solid(4, 4, [255, 0, 0, 128]))
+        };
+        let out = image::load_from_memory(&add_watermark_native(&base, config).unwrap()).unwrap();
+        assert!(!out.color().has_alpha());
+        assert_eq!(out.to_rgb8().get_pixel(10, 10).0, [0, 255, 0]);
+    }
     
-    // 按行处理每个像素
-    for y in 0..new_height {
-        let y_f32 = y as f32;
-        let rel_y = y_f32 - new_center_y;
+    #[test]
+    fn fast_mode_uses_nearest_neighbor_and_skips_slow_steps() {
+        // 黑白棋盘格放大后只出现原有的两种颜色，说明使用了最近邻
+        let checker = RgbaImage::from_fn(8, 8, |x, y| image::Rgba(if (x + y) % 2 == 0 { [0, 0, 0, 255] } else { [255, 255, 255, 255] }));
+        let config = WatermarkConfig { fast: Some(true), width: Some(27), rotate: Some(30.0), ..image_config(&checker) };
+        let watermark = load_and_prepare_watermark(&config).unwrap();
+        assert!(watermark.pixels().all(|p| p[3] == 0 || p.0 == [0, 0, 0, 255] || p.0 == [255, 255, 255, 255]));
         
-        for x in 0..new_width {
-            let x_f32 = x as f32;
-            let rel_x = x_f32 - new_center_x;
-            
-            // 逆旋转到原图坐标
-            let orig_x = rel_x * cos_r + rel_y * sin_r + center_x;
-            let orig_y = -rel_x * sin_r + rel_y * cos_r + center_y;
-            
-            // 边界检查
-            if orig_x >= 0.0 && orig_x < width as f32 - 1.0 &&
-               orig_y >= 0.0 && orig_y < height as f32 - 1.0 {
-                // 使用双线性插值
-                let pixel = bilinear_interpolate(img_data, width_usize, height_usize, orig_x, orig_y);
-                let target_idx = (y as usize * new_width_usize + x as usize) * 4;
-                
-                result_data[target_idx] = pixel[0];
-                result_data[target_idx + 1] = pixel[1];
-                result_data[target_idx + 2] = pixel[2];
-                result_data[target_idx + 3] = pixel[3];
+        // 快速模式跳过阴影模糊和高质量旋转：结果与不模糊、不超采样的配置完全相同
+        let base = png_bytes(&solid(60, 60, [128, 128, 128, 255]));
+        let fast = WatermarkConfig {
+            fast: Some(true),
+            width: Some(20),
+            rotate: Some(30.0),
+            rotation_quality: Some("high".to_string()),
+            shadow: Some(true),
+            shadow_blur: Some(12.0),
+            ..image_config(&checker)
+        };
+        let cheap = WatermarkConfig { rotation_quality: None, shadow_blur: Some(0.0), ..fast.clone() };
+        assert_eq!(add_watermark_native(&base, fast).unwrap(), add_watermark_native(&base, cheap).unwrap());
+    }
+    
+    #[test]
+    fn clip_polygon_limits_watermark_to_triangle() {
+        let config = WatermarkConfig {
+            clip_polygon: Some(vec![[0, 0], [40, 0], [0, 40]]),
+            ..image_config(&solid(40, 40, [255, 0, 0, 255]))
+        };
+        let out = render(&solid(40, 40, [255, 255, 255, 255]), config);
+        for (x, y, p) in out.enumerate_pixels() {
+            // 远离斜边（抗锯齿区域）的像素必须完全在内或完全在外
+            match x + y {
+                sum if sum <= 36 => assert_eq!(p.0, [255, 0, 0, 255], "({}, {})", x, y),
+                sum if sum >= 42 => assert_eq!(p.0, [255, 255, 255, 255], "({}, {})", x, y),
+                _ => {}
             }
         }
     }
     
-    DynamicImage::ImageRgba8(result)
-}
-
-// 叠加图片（直接操作 RGBA8，带透明度参数，SIMD 优化版本）
-fn overlay_image_rgba_with_transparency(
-    target: &mut RgbaImage,
-    overlay: &RgbaImage,
-    x: u32,
-    y: u32,
-    transparency: f32
-) {
-    // web_sys::console::log_1(&format!("开始叠加图片，位置: ({}, {}), 透明度: {}", x, y, transparency).into());
-    
-    let (target_width, target_height) = target.dimensions();
-    let (overlay_width, overlay_height) = overlay.dimensions();
+    #[test]
+    fn svg_overlay_matches_raster_rotation_and_position() {
+        let config = WatermarkConfig {
+            rotate: Some(30.0),
+            position: Some("bottom-right".to_string()),
+            ..image_config(&solid(20, 10, [255, 0, 0, 255]))
+        };
+        let placement = placements(&solid(100, 80, [255, 255, 255, 255]), &config)[0];
+        let svg = render_watermark_svg(100, 80, &config).unwrap();
+        let rotation = format!("transform=\"rotate(30 {} {})\"", placement.width as f32 / 2.0, placement.height as f32 / 2.0);
+        assert!(svg.contains(&rotation), "{}", rotation);
+        assert!(svg.contains(&format!("<use href=\"#fast-watermark\" x=\"{}\" y=\"{}\"/>", placement.x, placement.y)));
+    }
     
-    // web_sys::console::log_1(&format!("目标图片尺寸: {}x{}, 水印图片尺寸: {}x{}",
-    //     target_width, target_height, overlay_width, overlay_height).into());
+    #[test]
+    fn alternate_watermark_tiles_in_checkerboard() {
+        let config = WatermarkConfig {
+            tile: Some(true),
+            tile_alternate_image_data: Some(STANDARD.encode(png_bytes(&solid(4, 4, [0, 0, 255, 255])))),
+            ..image_config(&solid(4, 4, [255, 0, 0, 255]))
+        };
+        let out = render(&solid(16, 16, [255, 255, 255, 255]), config);
+        for (row, col) in [(0, 0), (0, 1), (1, 0), (1, 1), (3, 2)] {
+            let expected = if (row + col) % 2 == 0 { [255, 0, 0, 255] } else { [0, 0, 255, 255] };
+            assert_eq!(out.get_pixel(col * 4 + 1, row * 4 + 1).0, expected, "tile ({}, {})", row, col);
+        }
+    }
     
-    // 预计算透明度因子
-    let transparency_factor = transparency;
+    #[test]
+    fn rotated_tile_grid_spacing_stays_regular() {
+        let watermark = solid(30, 12, [255, 0, 0, 255]);
+        let config = WatermarkConfig { tile: Some(true), tile_angle: Some(17.0), ..image_config(&watermark) };
+        let positions = tile_grid(&config, &watermark, 3000, 3000).unwrap();
+        let lookup: std::collections::HashMap<(i64, i64), (i64, i64)> = positions.iter().map(|&(row, col, x, y)| ((row, col), (x, y))).collect();
+        // 相邻列之间的步长只因取整相差至多 1 像素，大网格的远端也不会累积漂移
+        let steps: Vec<(i64, i64)> = positions.iter()
+            .filter_map(|&(row, col, x, y)| lookup.get(&(row, col + 1)).map(|&(nx, ny)| (nx - x, ny - y)))
+            .collect();
+        assert!(steps.len() > 1000);
+        for axis in [|s: &(i64, i64)| s.0, |s: &(i64, i64)| s.1] {
+            let (min, max) = (steps.iter().map(axis).min().unwrap(), steps.iter().map(axis).max().unwrap());
+            assert!(max - min <= 1, "step range {}..={}", min, max);
+        }
+    }
     
-    // 获取像素数据切片
-    let target_data = target.as_mut();
-    let overlay_data = overlay.as_ref();
+    #[test]
+    fn ensure_visible_boosts_faint_watermark() {
+        let base = solid(20, 20, [200, 200, 200, 255]);
+        let faint = WatermarkConfig { transparency: Some(0.05), ..image_config(&solid(4, 4, [255, 255, 255, 255])) };
+        let delta = |config: WatermarkConfig| render(&base, config).get_pixel(1, 1)[0] as f32 - 200.0;
+        assert!(delta(faint.clone()) < MIN_VISIBLE_DELTA);
+        let boosted = delta(WatermarkConfig { ensure_visible: Some(true), ..faint.clone() });
+        assert!(boosted >= MIN_VISIBLE_DELTA - 1.0, "boosted delta {}", boosted);
+        // 上限限制提升幅度
+        let capped = delta(WatermarkConfig { ensure_visible: Some(true), ensure_visible_max: Some(0.1), ..faint });
+        assert!(capped <= (255.0 - 200.0) * 0.1 + 1.0);
+    }
     
-    // 计算边界
-    let start_x = x as usize;
-    let start_y = y as usize;
-    let end_x = (start_x + overlay_width as usize).min(target_width as usize);
-    let end_y = (start_y + overlay_height as usize).min(target_height as usize);
+    #[cfg(feature = "quantize")]
+    #[test]
+    fn quantized_png_fits_budget_and_stays_close() {
+        let base = RgbaImage::from_fn(64, 64, |x, y| image::Rgba([(x * 4) as u8, (y * 4) as u8, ((x + y) * 2) as u8, 255]));
+        let config = image_config(&solid(1, 1, [0, 0, 0, 0]));
+        let full = add_watermark_native(&png_bytes(&base), config.clone()).unwrap();
+        let budget = full.len() as u32 / 2;
+        let quantized = add_watermark_native(&png_bytes(&base), WatermarkConfig { max_output_bytes: Some(budget), ..config }).unwrap();
+        assert!(quantized.len() as u32 <= budget);
+        
+        let decoded = decode(&quantized);
+        let squared: f64 = base.pixels().zip(decoded.pixels())
+            .flat_map(|(a, b)| (0..3).map(move |c| (a[c] as f64 - b[c] as f64).powi(2)))
+            .sum();
+        let mse = squared / (64.0 * 64.0 * 3.0);
+        // 调色板量化允许少量色带：均方误差不超过 200（约 14 级）
+        assert!(mse < 200.0, "mse {}", mse);
+    }
     
-    // SIMD 优化的像素混合
-    // web_sys::console::log_1(&format!("开始像素混合，处理区域: ({}, {}) 到 ({}, {})",
-    //     start_x, start_y, end_x, end_y).into());
+    #[test]
+    fn interpolate_config_midpoint_of_each_numeric_field() {
+        let a = WatermarkConfig {
+            transparency: Some(0.2),
+            rotate: Some(-10.0),
+            x_offset: Some(0),
+            y_offset: Some(-20),
+            width: Some(100),
+            height: Some(40),
+            coverage_percent: Some(10.0),
+            corner_opacities: Some([0.0, 0.0, 1.0, 1.0]),
+            position: Some("top-left".to_string()),
+            ..WatermarkConfig::default()
+        };
+        let b = WatermarkConfig {
+            transparency: Some(0.8),
+            rotate: Some(30.0),
+            x_offset: Some(50),
+            y_offset: Some(20),
+            width: Some(200),
+            height: Some(60),
+            coverage_percent: Some(30.0),
+            corner_opacities: Some([1.0, 0.5, 1.0, 0.0]),
+            position: Some("center".to_string()),
+            ..WatermarkConfig::default()
+        };
+        let mid = interpolate_config(&a, &b, 0.5);
+        assert_eq!((mid.transparency, mid.rotate, mid.coverage_percent), (Some(0.5), Some(10.0), Some(20.0)));
+        assert_eq!((mid.x_offset, mid.y_offset, mid.width, mid.height), (Some(25), Some(0), Some(150), Some(50)));
+        assert_eq!(mid.corner_opacities, Some([0.5, 0.25, 1.0, 0.5]));
+        // 非数值字段：t < 0.5 取 a，否则取 b
+        assert_eq!(mid.position.as_deref(), Some("center"));
+        assert_eq!(interpolate_config(&a, &b, 0.25).position.as_deref(), Some("top-left"));
+        // 超出 [0, 1] 的 t 截断到端点，不外推
+        assert_eq!(format!("{:?}", interpolate_config(&a, &b, 1.5)), format!("{:?}", interpolate_config(&a, &b, 1.0)));
+        assert_eq!(format!("{:?}", interpolate_config(&a, &b, -2.0)), format!("{:?}", interpolate_config(&a, &b, 0.0)));
+        assert_eq!(interpolate_config(&a, &b, 1.5).width, Some(200));
+    }
     
-    // 添加像素级别的调试信息
-    // let mut debug_pixel_count = 0;
-    // let max_debug_pixels = 10; // 最多调试10个像素
+    #[test]
+    fn self_watermark_changes_pixels_by_bounded_amount() {
+        let base = RgbaImage::from_fn(64, 48, |x, y| image::Rgba([(x * 4) as u8, (y * 5) as u8, 255 - (x * 3) as u8, 255]));
+        let config = WatermarkConfig {
+            watermark_type: WatermarkType::Image,
+            self_watermark: Some(SelfWatermarkConfig { strength: Some(0.1), scale: Some(0.25) }),
+            transparency: Some(1.0),
+            ..WatermarkConfig::default()
+        };
+        let out = render(&base, config);
+        let diffs: Vec<u8> = base.pixels().zip(out.pixels()).flat_map(|(a, b)| (0..3).map(move |c| a[c].abs_diff(b[c]))).collect();
+        assert!(diffs.iter().any(|&d| d > 0));
+        assert!(diffs.iter().all(|&d| d as f32 <= 255.0 * 0.1 + 1.0), "max diff {}", diffs.iter().max().unwrap());
+    }
     
-    for oy in 0..(end_y - start_y) {
-        let overlay_row_start = oy * overlay_width as usize * 4;
-        let target_row_start = (start_y + oy) * target_width as usize * 4 + start_x * 4;
+    #[test]
+    fn zero_size_images_fail_cleanly() {
+        // 手工构造宽度为 0 的 PNG（编码器本身拒绝写出这种图片）
+        let mut header = Vec::new();
+        header.extend_from_slice(&0u32.to_be_bytes());
+        header.extend_from_slice(&1u32.to_be_bytes());
+        header.extend_from_slice(&[8, 6, 0, 0, 0]);
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        write_png_chunk(&mut png, b"IHDR", &header);
+        write_png_chunk(&mut png, b"IDAT", &miniz_oxide::deflate::compress_to_vec_zlib(&[0], 6));
+        write_png_chunk(&mut png, b"IEND", &[]);
+        assert!(add_watermark_native(&png, image_config(&solid(4, 4, [255, 0, 0, 255]))).is_err());
         
-        // 处理每一行，每次处理 1 个像素（4 字节）
-        let mut ox = 0;
-        
-        while ox < (end_x - start_x) {
-            let overlay_idx = overlay_row_start + ox * 4;
-            let target_idx = target_row_start + ox * 4;
-             
-            // 加载像素值（RGBA）
-            let overlay_r = overlay_data[overlay_idx] as f32;
-            let overlay_g = overlay_data[overlay_idx + 1] as f32;
-            let overlay_b = overlay_data[overlay_idx + 2] as f32;
-            let overlay_a = overlay_data[overlay_idx + 3] as f32;
-             
-            let target_r = target_data[target_idx] as f32;
-            let target_g = target_data[target_idx + 1] as f32;
-            let target_b = target_data[target_idx + 2] as f32;
-            let target_a = target_data[target_idx + 3] as f32;
-             
-            // 正确的透明度计算：基于overlay的alpha通道
-            let overlay_alpha = overlay_a / 255.0 * transparency_factor;
-            let inv_alpha = 1.0 - overlay_alpha;
-             
-            // 只混合RGB通道，alpha通道单独处理
-            let result_r = target_r * inv_alpha + overlay_r * overlay_alpha;
-            let result_g = target_g * inv_alpha + overlay_g * overlay_alpha;
-            let result_b = target_b * inv_alpha + overlay_b * overlay_alpha;
-            let result_a = target_a + overlay_alpha * (255.0 - target_a) / 255.0;
-             
-            // 存储结果
-            target_data[target_idx] = result_r as u8;
-            target_data[target_idx + 1] = result_g as u8;
-            target_data[target_idx + 2] = result_b as u8;
-            target_data[target_idx + 3] = result_a as u8;
-             
-            // 调试前几个像素
-            // if debug_pixel_count < max_debug_pixels {
-            //     web_sys::console::log_1(&format!("像素{}: 目标=({},{},{},{}) 水印=({},{},{},{}) 透明度={} 结果=({},{},{},{})",
-            //         debug_pixel_count,
-            //         target_r as u8, target_g as u8, target_b as u8, target_a as u8,
-            //         overlay_r as u8, overlay_g as u8, overlay_b as u8, overlay_a as u8,
-            //         transparency_factor,
-            //         result_r as u8, result_g as u8, result_b as u8, result_a as u8
-            //     ).into());
-            //     debug_pixel_count += 1;
-            // }
-             
-            ox += 1;
-        }
-        
-        // 处理剩余的像素（非 SIMD 部分）
-        while ox < (end_x - start_x) {
-            let overlay_idx = overlay_row_start + ox * 4;
-            let target_idx = target_row_start + ox * 4;
-            
-            let overlay_pixel = &overlay_data[overlay_idx..overlay_idx + 4];
-            let target_pixel = &mut target_data[target_idx..target_idx + 4];
-            
-            // 单像素混合
-            let alpha = overlay_pixel[3] as f32 / 255.0 * transparency_factor;
-            if alpha > 0.0 {
-                let inv_alpha = 1.0 - alpha;
-                target_pixel[0] = (target_pixel[0] as f32 * inv_alpha + overlay_pixel[0] as f32 * alpha) as u8;
-                target_pixel[1] = (target_pixel[1] as f32 * inv_alpha + overlay_pixel[1] as f32 * alpha) as u8;
-                target_pixel[2] = (target_pixel[2] as f32 * inv_alpha + overlay_pixel[2] as f32 * alpha) as u8;
-                target_pixel[3] = (target_pixel[3] as f32 * inv_alpha + overlay_pixel[3] as f32 * alpha) as u8;
-            }
-            
-            ox += 1;
+        for (width, height) in [(0, 0), (1, 0), (0, 1)] {
+            let err = check_image_dimensions(&DynamicImage::new_rgba8(width, height)).unwrap_err();
+            assert!(err.contains("zero width or height"), "{}", err);
         }
     }
-}
-
-// 叠加图片（带透明度，兼容旧接口）
-fn overlay_image_with_transparency(target: &mut DynamicImage, overlay: &RgbaImage, x: u32, y: u32, transparency: f32) {
-    let mut target_rgba = target.to_rgba8();
-    overlay_image_rgba_with_transparency(&mut target_rgba, overlay, x, y, transparency);
-    *target = DynamicImage::ImageRgba8(target_rgba);
-}
-
-// 应用水印（统一的实现，消除重复代码）
-fn apply_watermark(
-    img: &mut DynamicImage,
-    config: &WatermarkConfig,
-) -> Result<(), String> {
-    // web_sys::console::log_1(&format!("开始应用水印").into());
     
-    // 验证配置
-    validate_config(config)?;
+    #[test]
+    fn text_gradient_spans_top_to_bottom() {
+        let config = WatermarkConfig {
+            watermark_type: WatermarkType::Text,
+            text_gradient: Some(GradientConfig { colors: vec!["#FF0000".to_string(), "#0000FF".to_string()], direction: None }),
+            ..image_config(&solid(6, 11, [255, 255, 255, 255]))
+        };
+        let watermark = load_and_prepare_watermark(&config).unwrap();
+        assert_eq!(watermark.get_pixel(3, 0).0, [255, 0, 0, 255]);
+        assert_eq!(watermark.get_pixel(3, 10).0, [0, 0, 255, 255]);
+        assert_eq!(watermark.get_pixel(3, 5).0, [128, 0, 128, 255]);
+    }
     
-    // 加载并准备水印图片
-    let watermark_rgba = load_and_prepare_watermark(config)?;
+    #[test]
+    fn tile_ensure_full_adds_a_fully_visible_watermark() {
+        // 间距 22 的网格从 (14, 14) 开始，20x20 图片上每个 12x12 水印都被边缘裁切
+        let config = WatermarkConfig {
+            tile: Some(true),
+            x_offset: Some(14),
+            y_offset: Some(14),
+            tile_spacing_x: Some(22),
+            tile_spacing_y: Some(22),
+            ..image_config(&solid(12, 12, [255, 0, 0, 255]))
+        };
+        let base = solid(20, 20, [255, 255, 255, 255]);
+        let full = |placements: Vec<WatermarkPlacement>| placements.iter().filter(|p| (p.width, p.height) == (12, 12)).count();
+        let clipped = placements(&base, &config);
+        assert!(!clipped.is_empty());
+        assert_eq!(full(clipped), 0);
+        assert_eq!(full(placements(&base, &WatermarkConfig { tile_ensure_full: Some(true), ..config })), 1);
+    }
     
-    // 获取参数
-    let transparency = config.transparency.unwrap_or(0.5);
-    let x_offset = config.x_offset.unwrap_or(10);
-    let y_offset = config.y_offset.unwrap_or(10);
-    let tile = config.tile.unwrap_or(false);
+    #[test]
+    fn filename_patterns_parse_and_reject_malformed_names() {
+        let config = config_from_filename("batch/logo_t50_r30.png", "{name}_t{transparency}_r{rotate}").unwrap();
+        assert_eq!((config.transparency, config.rotate), (Some(0.5), Some(30.0)));
+        
+        let config = config_from_filename("shot-bottom-right-x-12.jpg", "shot-{position}-x{x_offset}").unwrap();
+        assert_eq!((config.position.as_deref(), config.x_offset), (Some("bottom-right"), Some(-12)));
+        
+        for (filename, pattern) in [
+            ("logo_t50.png", "{name}_t{transparency}_r{rotate}"),
+            ("logo_tabc_r30.png", "{name}_t{transparency}_r{rotate}"),
+            ("logo_t50_r30_extra.png", "{name}_t{transparency}_r{rotate}"),
+            ("logo_t50.png", "{name}_t{transparency"),
+            ("logo50.png", "{name}{transparency}"),
+            ("logo_q5.png", "{name}_q{quality}"),
+        ] {
+            assert!(config_from_filename(filename, pattern).is_err(), "{} / {} was accepted", filename, pattern);
+        }
+    }
     
-    // web_sys::console::log_1(&format!("水印参数: 透明度={}, X偏移={}, Y偏移={}, 平铺={}",
-    //     transparency, x_offset, y_offset, tile).into());
+    #[test]
+    fn z_index_decides_which_layer_is_on_top() {
+        let base = png_bytes(&solid(10, 10, [255, 255, 255, 255]));
+        let top_pixel = |red_z: i32, blue_z: i32| {
+            let layers = [
+                WatermarkConfig { z_index: Some(red_z), ..image_config(&solid(4, 4, [255, 0, 0, 255])) },
+                WatermarkConfig { z_index: Some(blue_z), ..image_config(&solid(4, 4, [0, 0, 255, 255])) },
+            ];
+            render_watermark_layers(&base, &layers).unwrap().to_rgba8().get_pixel(1, 1).0
+        };
+        assert_eq!(top_pixel(0, 1), [0, 0, 255, 255]);
+        assert_eq!(top_pixel(1, 0), [255, 0, 0, 255]);
+        // 层级相同时保持数组顺序
+        assert_eq!(top_pixel(0, 0), [0, 0, 255, 255]);
+    }
     
-    let (img_width, img_height) = img.dimensions();
-    let (wm_width, wm_height) = watermark_rgba.dimensions();
+    #[test]
+    fn streaming_writer_matches_in_memory_png() {
+        let base = png_bytes(&RgbaImage::from_fn(30, 20, |x, y| image::Rgba([(x * 8) as u8, (y * 12) as u8, 77, 255])));
+        let config = WatermarkConfig { transparency: Some(0.6), ..image_config(&solid(6, 6, [255, 0, 0, 255])) };
+        let mut streamed = Cursor::new(Vec::new());
+        watermark_to_writer(&base, &config, &mut streamed).unwrap();
+        assert_eq!(streamed.into_inner(), watermark_image(&base, &config).unwrap());
+    }
     
-    // web_sys::console::log_1(&format!("原始图片尺寸: {}x{}, 水印尺寸: {}x{}",
-    //     img_width, img_height, wm_width, wm_height).into());
+    #[test]
+    fn opacity_map_ramp_scales_watermark_strength() {
+        let ramp = image::GrayImage::from_fn(21, 1, |x, _| image::Luma([(x * 255 / 20) as u8]));
+        let mut ramp_png = Vec::new();
+        DynamicImage::ImageLuma8(ramp).write_to(&mut Cursor::new(&mut ramp_png), image::ImageOutputFormat::Png).unwrap();
+        let config = WatermarkConfig {
+            opacity_map_data: Some(STANDARD.encode(ramp_png)),
+            ..image_config(&solid(21, 4, [255, 0, 0, 255]))
+        };
+        let out = render(&solid(21, 4, [255, 255, 255, 255]), config);
+        // 白底上的红色水印：绿色通道越低说明该处不透明度越高
+        let green: Vec<u8> = (0..21).map(|x| out.get_pixel(x, 2)[1]).collect();
+        assert!(green.windows(2).all(|pair| pair[1] < pair[0]), "{:?}", green);
+        assert!(green[0] >= 250 && green[20] <= 5, "{:?}", green);
+    }
     
-    if tile {
-        // 平铺水印 - 优化版本：只转换一次目标图片
-        let spacing_x = wm_width + x_offset.abs() as u32;
-        let spacing_y = wm_height + y_offset.abs() as u32;
+    #[test]
+    fn negative_offset_start_and_css_modes() {
+        let base = solid(40, 30, [255, 255, 255, 255]);
+        let config = WatermarkConfig { x_offset: Some(-5), y_offset: Some(-5), ..image_config(&solid(6, 4, [255, 0, 0, 255])) };
+        // start：水印左上角距右/下边缘 5 像素，超出部分被裁切
+        let start = placements(&base, &config)[0];
+        assert_eq!((start.x, start.y, start.width, start.height), (35, 25, 5, 4));
+        // css：水印右/下边缘距右/下边缘 5 像素
+        let css = placements(&base, &WatermarkConfig { negative_offset_mode: Some("css".to_string()), ..config })[0];
+        assert_eq!((css.x, css.y, css.width, css.height), (29, 21, 6, 4));
+    }
+    
+    #[test]
+    fn premultiply_output_scales_rgb_by_alpha() {
+        let base = solid(10, 10, [0, 0, 0, 0]);
+        let config = image_config(&solid(4, 4, [200, 100, 50, 128]));
+        let straight = render(&base, config.clone()).get_pixel(1, 1).0;
+        let premultiplied = render(&base, WatermarkConfig { premultiply_output: Some(true), ..config }).get_pixel(1, 1).0;
+        assert_eq!(straight, [200, 100, 50, 128]);
+        for c in 0..3 {
+            let expected = (straight[c] as f32 * 128.0 / 255.0).round() as u8;
+            assert!(premultiplied[c].abs_diff(expected) <= 1, "{:?}", premultiplied);
+        }
+        assert_eq!(premultiplied[3], 128);
+    }
+    
+    #[cfg(feature = "hdr")]
+    #[test]
+    fn hdr_input_is_tone_mapped_to_ldr() {
+        // 亮度远超 1.0 的像素经色调映射后仍可区分，而不是全部截断为 255
+        let radiance = [0.1f32, 1.0, 10.0, 50.0];
+        let pixels: Vec<image::Rgb<f32>> = radiance.iter().flat_map(|&v| [image::Rgb([v, v, v]); 2]).collect();
+        let mut hdr = Vec::new();
+        image::codecs::hdr::HdrEncoder::new(&mut hdr).encode(&pixels, 8, 1).unwrap();
         
-        // 计算起始位置（考虑偏移量）
-        let start_x = if x_offset >= 0 {
-            x_offset as u32
-        } else {
-            0
+        let out = add_watermark_native(&hdr, image_config(&solid(1, 1, [0, 0, 0, 0]))).unwrap();
+        assert_eq!(image::guess_format(&out).unwrap(), image::ImageFormat::Png);
+        let levels: Vec<u8> = (0..4).map(|i| decode(&out).get_pixel(i * 2, 0)[0]).collect();
+        assert!(levels.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", levels);
+    }
+    
+    #[test]
+    fn transform_order_changes_flip_and_rotate_result() {
+        let mut watermark = solid(12, 6, [0, 0, 255, 255]);
+        image::imageops::replace(&mut watermark, &solid(3, 3, [255, 0, 0, 255]), 0, 0);
+        let prepared = |watermark: &RgbaImage, flip: Option<&str>, order: [&str; 2]| {
+            let config = WatermarkConfig {
+                flip: flip.map(str::to_string),
+                rotate: Some(90.0),
+                transform_order: Some(order.iter().map(|step| step.to_string()).collect()),
+                ..image_config(watermark)
+            };
+            load_and_prepare_watermark(&config).unwrap()
         };
+        let flip_first = prepared(&watermark, Some("horizontal"), ["flip", "rotate"]);
+        let rotate_first = prepared(&watermark, Some("horizontal"), ["rotate", "flip"]);
+        assert_ne!(flip_first, rotate_first);
+        // 分别等于：旋转预先翻转的水印；翻转旋转后的水印
+        assert_eq!(flip_first, prepared(&image::imageops::flip_horizontal(&watermark), None, ["flip", "rotate"]));
+        assert_eq!(rotate_first, image::imageops::flip_horizontal(&prepared(&watermark, None, ["flip", "rotate"])));
+    }
+    
+    #[test]
+    fn subpixel_nearest_versus_bilinear() {
+        let config = WatermarkConfig { subpixel_offset: Some([0.5, 0.25]), ..image_config(&solid(4, 4, [255, 0, 0, 255])) };
+        let bilinear = load_and_prepare_watermark(&config).unwrap();
+        let nearest = load_and_prepare_watermark(&WatermarkConfig { overlay_sampling: Some("nearest".to_string()), ..config }).unwrap();
+        assert_eq!((bilinear.dimensions(), nearest.dimensions()), ((5, 5), (5, 5)));
+        // 双线性：左边缘列按 0.5 覆盖率半透明；最近邻：只有完全透明或完全不透明的像素
+        assert_eq!(bilinear.get_pixel(0, 2)[3], 128);
+        assert!(nearest.pixels().all(|p| p[3] == 0 || p[3] == 255));
+        assert_eq!(nearest.pixels().filter(|p| p[3] == 255).count(), 16);
+    }
+    
+    #[test]
+    fn exif_orientation_is_applied_and_reset_to_normal() {
+        // 方向 6：存储为横图，需顺时针旋转 90 度显示为竖图；左上角的红块摆正后位于右上角
+        let mut stored = solid(40, 20, [255, 255, 255, 255]);
+        image::imageops::replace(&mut stored, &solid(6, 6, [255, 0, 0, 255]), 0, 0);
+        let source = jpeg_with_orientation(&stored, 6);
+        let config = WatermarkConfig { preserve_exif: Some(true), ..image_config(&solid(1, 1, [0, 0, 0, 0])) };
+        let out = add_watermark_native(&source, config).unwrap();
         
-        let start_y = if y_offset >= 0 {
-            y_offset as u32
-        } else {
-            0
+        let upright = decode(&out);
+        assert_eq!(upright.dimensions(), (20, 40));
+        assert!(upright.get_pixel(17, 2)[1] < 64 && upright.get_pixel(2, 2)[1] > 192);
+        let segment = jpeg_exif_segment(&out).expect("EXIF kept");
+        assert_eq!(exif_orientation(&out[segment]).map(|(value, _)| value), Some(1));
+    }
+    
+    #[test]
+    fn output_formats_are_all_present_and_decode_alike() {
+        let base = png_bytes(&RgbaImage::from_fn(24, 16, |x, y| image::Rgba([(x * 10) as u8, (y * 15) as u8, 60, 255])));
+        let config = WatermarkConfig {
+            output_formats: Some(["png", "webp", "bmp", "jpeg"].map(String::from).to_vec()),
+            ..image_config(&solid(6, 6, [255, 0, 0, 255]))
         };
+        let results = render_output_formats(&base, &config).unwrap();
+        let formats: Vec<&str> = results.iter().map(|result| result.format.as_str()).collect();
+        assert_eq!(formats, ["png", "webp", "bmp", "jpeg"]);
         
-        // 只转换一次目标图片为 RGBA8
-        let mut target_rgba = img.to_rgba8();
-        
-        for y in (start_y..img_height).step_by(spacing_y as usize) {
-            for x in (start_x..img_width).step_by(spacing_x as usize) {
-                overlay_image_rgba_with_transparency(&mut target_rgba, &watermark_rgba, x, y, transparency);
+        // 无损格式逐像素一致，JPEG 只需尺寸一致
+        let reference = decode(&results[0].bytes);
+        for result in &results[1..3] {
+            assert_eq!(decode(&result.bytes), reference, "{}", result.format);
+        }
+        assert_eq!(decode(&results[3].bytes).dimensions(), reference.dimensions());
+        assert_eq!(image::guess_format(&results[3].bytes).unwrap(), image::ImageFormat::Jpeg);
+    }
+    
+    #[test]
+    fn label_chip_encloses_text_with_padding() {
+        // 30x20 的画布中只有 12x5 的文字像素
+        let mut text = solid(30, 20, [0, 0, 0, 0]);
+        image::imageops::replace(&mut text, &solid(12, 5, [255, 255, 255, 255]), 7, 9);
+        let config = WatermarkConfig {
+            watermark_type: WatermarkType::Text,
+            label_background: Some(LabelBgConfig { color: Some("#0000FF".to_string()), gradient: None, padding: Some(3), corner_radius: None, opacity: None }),
+            ..image_config(&text)
+        };
+        let chip = load_and_prepare_watermark(&config).unwrap();
+        assert_eq!(chip.dimensions(), (12 + 3 * 2, 5 + 3 * 2));
+        assert_eq!(chip.get_pixel(0, 0).0, [0, 0, 255, 255]);
+        assert_eq!(chip.get_pixel(3, 3).0, [255, 255, 255, 255]);
+        assert_eq!(chip.get_pixel(14, 7).0, [255, 255, 255, 255]);
+        assert_eq!(chip.get_pixel(15, 8).0, [0, 0, 255, 255]);
+    }
+    
+    #[test]
+    fn transparency_sweep_strength_follows_each_level() {
+        let levels = [0.2, 0.5, 0.8];
+        let config = WatermarkConfig { transparency_sweep: Some(levels.to_vec()), ..image_config(&solid(4, 4, [0, 0, 0, 255])) };
+        let outputs = render_transparency_sweep(&png_bytes(&solid(10, 10, [255, 255, 255, 255])), &config).unwrap();
+        assert_eq!(outputs.len(), levels.len());
+        for ((img, _), level) in outputs.iter().zip(levels) {
+            // 白底上的黑色水印：亮度按不透明度线性降低
+            let expected = 255.0 * (1.0 - level);
+            assert!((img.to_rgba8().get_pixel(1, 1)[0] as f32 - expected).abs() <= 1.0, "level {}", level);
+        }
+    }
+    
+    #[test]
+    fn subject_tile_stays_inside_subject_bounds() {
+        let mut base = solid(60, 40, [0, 0, 0, 0]);
+        image::imageops::replace(&mut base, &solid(20, 20, [0, 255, 0, 255]), 20, 10);
+        let config = WatermarkConfig { subject_tile: Some(true), ..image_config(&solid(4, 4, [255, 0, 0, 255])) };
+        let placed = placements(&base, &config);
+        assert!(!placed.is_empty());
+        assert!(placed.iter().all(|p| p.x >= 20 && p.y >= 10 && p.x + p.width <= 40 && p.y + p.height <= 30), "{:?}", placed);
+        // 主体外的透明区域保持不变
+        let out = render(&base, config);
+        assert!(out.enumerate_pixels().all(|(x, y, p)| (20..40).contains(&x) && (10..30).contains(&y) || p[3] == 0));
+    }
+    
+    #[test]
+    fn rgba_buffer_len_rejects_offsets_past_the_address_space() {
+        // 60000x4000 的全景图约 960MB，在 32 位和 64 位下都能寻址
+        assert_eq!(rgba_buffer_len(60_000, 4_000), Some(960_000_000));
+        // 2^34 字节超出 32 位 usize，在 64 位下仍然有效
+        assert_eq!(rgba_buffer_len(1 << 16, 1 << 16), usize::try_from(1u64 << 34).ok());
+        // 连 u64 都会溢出时返回 None，而不是回绕
+        assert_eq!(rgba_buffer_len(u32::MAX, u32::MAX), None);
+    }
+    
+    #[test]
+    fn output_format_round_trips_and_jpeg_flattens_on_white() {
+        let base = solid(16, 16, [0, 0, 0, 0]);
+        for (format, expected) in [
+            ("png", image::ImageFormat::Png),
+            ("jpeg", image::ImageFormat::Jpeg),
+            ("webp", image::ImageFormat::WebP),
+        ] {
+            let config = WatermarkConfig { output_format: Some(format.to_string()), ..image_config(&solid(4, 4, [0, 0, 0, 0])) };
+            let bytes = add_watermark_native(&png_bytes(&base), config).unwrap();
+            assert_eq!(image::guess_format(&bytes).unwrap(), expected, "{}", format);
+            if format == "jpeg" {
+                // 透明区域压平到白色背景上，而不是变黑
+                assert!(decode(&bytes).pixels().all(|p| p[0] > 250 && p[1] > 250 && p[2] > 250 && p[3] == 255));
             }
         }
-        
-        // 转换回 DynamicImage
-        *img = DynamicImage::ImageRgba8(target_rgba);
-    } else {
-        // 单个水印
-        let x = if x_offset >= 0 {
-            x_offset as u32
-        } else {
-            (img_width as i32 + x_offset).max(0) as u32
+    }
+    
+    #[test]
+    fn lower_jpeg_quality_gives_smaller_output() {
+        // 带纹理的底图，纯色图在不同质量下体积几乎相同
+        let base = RgbaImage::from_fn(64, 64, |x, y| image::Rgba([(x * 4) as u8, (y * 4) as u8, ((x * y) % 256) as u8, 255]));
+        let encode = |quality| {
+            let config = WatermarkConfig {
+                output_format: Some("jpeg".to_string()),
+                quality: Some(quality),
+                ..image_config(&solid(8, 8, [255, 0, 0, 255]))
+            };
+            add_watermark_native(&png_bytes(&base), config).unwrap().len()
         };
+        assert!(encode(50) < encode(95));
+    }
+    
+    #[test]
+    fn semi_transparent_red_over_transparent_canvas_stays_red() {
+        let mut canvas = solid(8, 8, [0, 0, 0, 0]);
+        overlay_image_rgba_with_transparency(&mut canvas, &solid(4, 4, [255, 0, 0, 128]), 2, 2, 1.0, BlendMode::Normal);
+        let p = canvas.get_pixel(3, 3);
+        // 颜色不应被透明底图的黑色 RGB 拉暗
+        assert_eq!(&p.0[..3], &[255, 0, 0]);
+        assert!((127..=129).contains(&p[3]));
+        assert_eq!(canvas.get_pixel(0, 0).0, [0, 0, 0, 0]);
+    }
+    
+    #[test]
+    fn jpeg_input_keeps_jpeg_output_unless_overridden() {
+        let jpeg = encode_bytes(&DynamicImage::ImageRgba8(solid(16, 16, [40, 80, 120, 255])), "jpeg", &WatermarkConfig::default()).unwrap();
+        let config = image_config(&solid(4, 4, [255, 0, 0, 128]));
+        let bytes = add_watermark_native(&jpeg, config.clone()).unwrap();
+        assert_eq!(image::guess_format(&bytes).unwrap(), image::ImageFormat::Jpeg);
         
-        let y = if y_offset >= 0 {
-            y_offset as u32
-        } else {
-            (img_height as i32 + y_offset).max(0) as u32
+        let config = WatermarkConfig { output_format: Some("png".to_string()), ..config };
+        let bytes = add_watermark_native(&jpeg, config).unwrap();
+        assert_eq!(image::guess_format(&bytes).unwrap(), image::ImageFormat::Png);
+    }
+    
+    #[test]
+    fn stepped_and_direct_processing_are_byte_identical() {
+        let base = RgbaImage::from_fn(32, 24, |x, y| image::Rgba([(x * 8) as u8, (y * 10) as u8, 90, 255]));
+        let config = WatermarkConfig {
+            x_offset: Some(5),
+            y_offset: Some(7),
+            transparency: Some(0.6),
+            ..image_config(&solid(8, 8, [255, 255, 0, 200]))
         };
+        for format in ["png", "jpeg"] {
+            let config = WatermarkConfig { output_format: Some(format.to_string()), ..config.clone() };
+            let stepped = block_on(process_watermark_in_steps(&png_bytes(&base), &config, || std::future::ready(()))).unwrap();
+            assert_eq!(stepped.bytes, add_watermark_native(&png_bytes(&base), config).unwrap(), "{}", format);
+        }
+    }
+    
+    #[test]
+    fn horizontal_gradient_fades_alpha_across_each_row() {
+        let config = WatermarkConfig { gradient: Some("horizontal".to_string()), ..image_config(&solid(8, 2, [0, 0, 0, 255])) };
+        let watermark = load_and_prepare_watermark(&config).unwrap();
+        for y in 0..2 {
+            let alphas: Vec<u8> = (0..8).map(|x| watermark.get_pixel(x, y)[3]).collect();
+            assert!(alphas.windows(2).all(|w| w[0] > w[1]), "{:?}", alphas);
+            assert_eq!((alphas[0], alphas[7]), (255, 0));
+        }
+    }
+    
+    #[test]
+    fn red_tint_recolors_opaque_pixels_and_keeps_transparency() {
+        // 左半边不透明黑色，右半边完全透明
+        let logo = RgbaImage::from_fn(8, 4, |x, _| if x < 4 { image::Rgba([0, 0, 0, 255]) } else { image::Rgba([0, 0, 0, 0]) });
+        let config = WatermarkConfig { tint_color: Some("#FF0000".to_string()), ..image_config(&logo) };
+        let watermark = load_and_prepare_watermark(&config).unwrap();
+        for (x, _, p) in watermark.enumerate_pixels() {
+            if x < 4 {
+                assert_eq!(p.0, [255, 0, 0, 255]);
+            } else {
+                assert_eq!(p[3], 0);
+            }
+        }
+        assert!(validate_config(&WatermarkConfig { tint_color: Some("#GG0000".to_string()), ..config }).is_err());
+    }
+    
+    #[test]
+    fn height_only_resize_derives_width_from_aspect_ratio() {
+        let config = WatermarkConfig { height: Some(20), ..image_config(&solid(40, 10, [255, 0, 0, 255])) };
+        assert_eq!(load_and_prepare_watermark(&config).unwrap().dimensions(), (80, 20));
         
-        overlay_image_with_transparency(img, &watermark_rgba, x, y, transparency);
+        let width_only = WatermarkConfig { width: Some(20), height: None, ..config.clone() };
+        assert_eq!(load_and_prepare_watermark(&width_only).unwrap().dimensions(), (20, 5));
+        assert_eq!(target_size(&WatermarkConfig::default(), 40, 10).unwrap(), None);
+        assert!(target_size(&config, 40, 0).is_err());
     }
     
-    Ok(())
-}
-
-// 添加文字水印（现在使用客户端渲染的图片）
-fn add_text_watermark(
-    img: &mut DynamicImage,
-    config: &WatermarkConfig,
-) -> Result<(), String> {
-    // 检查是否有图片数据（客户端渲染的文字图片）
-    if config.image_data.is_none() {
-        return Err("Text watermark requires image_data parameter (rendered by client)".to_string());
+    #[test]
+    fn grayscale_watermark_has_equal_channels() {
+        let logo = RgbaImage::from_fn(8, 8, |x, y| image::Rgba([(x * 30) as u8, (y * 30) as u8, 200, if x == 0 { 0 } else { 255 }]));
+        let config = WatermarkConfig { grayscale: Some(true), ..image_config(&logo) };
+        let watermark = load_and_prepare_watermark(&config).unwrap();
+        assert!(watermark.pixels().filter(|p| p[3] == 255).all(|p| p[0] == p[1] && p[1] == p[2]));
+        // alpha 保持不变
+        assert!(watermark.pixels().zip(logo.pixels()).all(|(a, b)| a[3] == b[3]));
     }
     
-    apply_watermark(img, config)
-}
-
-// 添加图片水印
-fn add_image_watermark(
-    img: &mut DynamicImage,
-    config: &WatermarkConfig,
-) -> Result<(), String> {
-    apply_watermark(img, config)
-}
-
-// WASM导出函数：添加水印
-#[wasm_bindgen]
-pub fn add_watermark(
-    image_data: &[u8],
-    config_js: JsValue,
-) -> Result<Vec<u8>, JsValue> {
-    // 检查输入数据
-    if image_data.is_empty() {
-        return Err(JsValue::from_str("Image data is empty"));
+    #[test]
+    fn small_rotation_keeps_the_outer_pixel_ring() {
+        let square = DynamicImage::ImageRgba8(solid(20, 20, [0, 0, 255, 255]));
+        let rotated = rotate_image(&square, 1.0, false).to_rgba8();
+        // 最后一行、一列不再被边界检查丢弃：旋转前后不透明像素数量一致
+        let opaque: Vec<_> = rotated.pixels().filter(|p| p[3] > 0).collect();
+        assert_eq!(opaque.len(), 400);
+        assert!(opaque.iter().all(|p| p.0 == [0, 0, 255, 255]));
     }
     
-    // 解析配置
-    let config: WatermarkConfig = serde_wasm_bindgen::from_value(config_js)
-        .map_err(|e| JsValue::from_str(&format!("Failed to parse config: {}", e)))?;
+    #[test]
+    fn high_quality_rotation_antialiases_edges() {
+        // 纯色方块的斜边：双线性旋转只有全透明/全不透明两级，超采样后出现过渡像素
+        let square = DynamicImage::ImageRgba8(solid(60, 60, [0, 0, 0, 255]));
+        let edge_pixels = |quality: &str| {
+            let config = WatermarkConfig { rotation_quality: Some(quality.to_string()), ..WatermarkConfig::default() };
+            rotate_watermark(&square, 10.0, &config).to_rgba8().pixels().filter(|p| p[3] > 0 && p[3] < 255).count()
+        };
+        assert!(edge_pixels("high") > edge_pixels("fast") + 100);
+    }
     
-    // 加载图片
-    let mut img = image::load_from_memory(image_data)
-        .map_err(|e| JsValue::from_str(&format!("Failed to load image: {}", e)))?;
+    #[test]
+    fn bottom_right_watermark_lands_bottom_right_of_upright_photo() {
+        let source = jpeg_with_orientation(&solid(40, 20, [255, 255, 255, 255]), 6);
+        let config = WatermarkConfig { position: Some("bottom-right".to_string()), ..image_config(&solid(6, 6, [255, 0, 0, 255])) };
+        let is_red = |p: &image::Rgba<u8>| p[0] > 192 && p[1] < 64;
+        
+        let upright = decode(&add_watermark_native(&source, config.clone()).unwrap());
+        assert_eq!(upright.dimensions(), (20, 40));
+        assert!(is_red(upright.get_pixel(17, 37)) && !is_red(upright.get_pixel(2, 2)));
+        
+        let config = WatermarkConfig { respect_exif_orientation: Some(false), ..config };
+        let stored = decode(&add_watermark_native(&source, config).unwrap());
+        assert_eq!(stored.dimensions(), (40, 20));
+        assert!(is_red(stored.get_pixel(37, 17)));
+    }
     
-    // 根据类型添加水印
-    match config.watermark_type.as_str() {
-        "text" => {
-            add_text_watermark(&mut img, &config)
-                .map_err(|e| JsValue::from_str(&format!("Failed to add text watermark: {}", e)))?;
-        }
-        "image" => {
-            add_image_watermark(&mut img, &config)
-                .map_err(|e| JsValue::from_str(&format!("Failed to add image watermark: {}", e)))?;
-        }
-        _ => {
-            return Err(JsValue::from_str(&format!(
-                "Invalid watermark type '{}'. Use 'text' or 'image'", 
-                config.watermark_type
-            )));
-        }
+    #[test]
+    fn tile_margins_set_the_gap_between_tiles() {
+        let watermark = solid(10, 10, [255, 0, 0, 255]);
+        let config = WatermarkConfig {
+            tile: Some(true),
+            tile_margin_x: Some(5),
+            tile_margin_y: Some(10),
+            ..image_config(&watermark)
+        };
+        // 横向间距 15（x = 0, 15, ..., 90），纵向间距 20（y = 0, 20, ..., 80）
+        let positions = tile_grid(&config, &watermark, 100, 100).unwrap();
+        assert_eq!(positions.len(), 7 * 5);
+        assert!(positions.iter().all(|&(_, _, x, y)| x % 15 == 0 && y % 20 == 0));
     }
     
-    // 编码为PNG（预分配缓冲区以减少重新分配）
-    let (width, height) = img.dimensions();
-    // 预估 PNG 编码后的大小：width * height * 4 (RGBA) + 头部开销
-    let estimated_size = (width * height * 4) as usize + 1024;
-    let mut buffer = Vec::with_capacity(estimated_size);
-    img.write_to(&mut Cursor::new(&mut buffer), image::ImageFormat::Png)
-        .map_err(|e| JsValue::from_str(&format!("Failed to encode image: {}", e)))?;
+    #[test]
+    fn transparent_input_flattens_onto_background_color_jpeg() {
+        let config = WatermarkConfig {
+            output_format: Some("jpeg".to_string()),
+            background_color: Some("#808080".to_string()),
+            ..image_config(&solid(1, 1, [0, 0, 0, 0]))
+        };
+        let out = decode(&add_watermark_native(&png_bytes(&solid(16, 16, [255, 0, 0, 0])), config).unwrap());
+        assert!(out.pixels().all(|p| p.0[..3].iter().all(|&c| (126..=130).contains(&c)) && p[3] == 255));
+    }
     
-    Ok(buffer)
-}
-
-// WASM导出函数：批量添加水印
-#[wasm_bindgen]
-pub async fn add_watermark_async(
-    image_data: &[u8],
-    config_js: JsValue,
-) -> Result<Vec<u8>, JsValue> {
-    // 使用wasm-bindgen-futures来支持异步操作
-    // 注意：当前实现仍然是同步的，但提供了异步接口以便未来扩展
-    // 重新调用同步版本的add_watermark
-    let config: WatermarkConfig = serde_wasm_bindgen::from_value(config_js)
-        .map_err(|e| JsValue::from_str(&format!("Failed to parse config: {}", e)))?;
+    #[test]
+    fn auto_contrast_flips_color_across_the_boundary() {
+        let base = RgbaImage::from_fn(60, 20, |x, _| image::Rgba(if x < 30 { [0, 0, 0, 255] } else { [255, 255, 255, 255] }));
+        let config = WatermarkConfig { auto_contrast: Some(true), ..image_config(&solid(60, 20, [128, 128, 128, 255])) };
+        let out = render(&base, config);
+        // 黑色一侧远离边界处为白色，白色一侧为黑色
+        assert_eq!(out.get_pixel(5, 10).0, [255, 255, 255, 255]);
+        assert_eq!(out.get_pixel(54, 10).0, [0, 0, 0, 255]);
+    }
     
-    // 加载图片
-    let mut img = image::load_from_memory(image_data)
-        .map_err(|e| JsValue::from_str(&format!("Failed to load image: {}", e)))?;
+    #[test]
+    fn pixel_limit_counts_past_u32_without_wrapping() {
+        // 100000 x 100000 在 u32 下回绕为 1410065408，按 u64 计算为 100 亿
+        let err = check_pixel_limit(100_000, 100_000, &WatermarkConfig::default()).unwrap_err();
+        assert!(err.contains("10000000000 pixels"), "{}", err);
+        let unlimited = WatermarkConfig { max_pixels: Some(u64::MAX), ..WatermarkConfig::default() };
+        assert!(check_pixel_limit(100_000, 100_000, &unlimited).is_ok());
+        
+        // 解码后立即检查，超出上限时返回错误
+        let config = WatermarkConfig { max_pixels: Some(100), ..image_config(&solid(2, 2, [0, 0, 0, 255])) };
+        assert!(add_watermark_native(&png_bytes(&solid(20, 20, [255, 255, 255, 255])), config).is_err());
+    }
     
-    // 根据类型添加水印
-    match config.watermark_type.as_str() {
-        "text" => {
-            add_text_watermark(&mut img, &config)
-                .map_err(|e| JsValue::from_str(&format!("Failed to add text watermark: {}", e)))?;
-        }
-        "image" => {
-            add_image_watermark(&mut img, &config)
-                .map_err(|e| JsValue::from_str(&format!("Failed to add image watermark: {}", e)))?;
-        }
-        _ => {
-            return Err(JsValue::from_str(&format!(
-                "Invalid watermark type '{}'. Use 'text' or 'image'",
-                config.watermark_type
-            )));
+    #[test]
+    fn multiply_of_half_gray_over_half_gray_is_quarter_gray() {
+        let blend = |mode: BlendMode| {
+            let mut canvas = solid(4, 4, [128, 128, 128, 255]);
+            overlay_image_rgba_with_transparency(&mut canvas, &solid(4, 4, [128, 128, 128, 255]), 0, 0, 1.0, mode);
+            canvas.get_pixel(1, 1).0
+        };
+        // 0.5 * 0.5 = 0.25，即 128 * 128 / 255 ≈ 64
+        assert!(blend(BlendMode::Multiply)[..3].iter().all(|&c| (63..=65).contains(&c)));
+        // 滤色：1 - 0.5 * 0.5 = 0.75
+        assert!(blend(BlendMode::Screen)[..3].iter().all(|&c| (190..=193).contains(&c)));
+        assert_eq!(blend(BlendMode::Normal), [128, 128, 128, 255]);
+    }
+    
+    #[test]
+    fn negative_tile_offset_starts_left_of_the_image() {
+        let watermark = solid(10, 10, [255, 0, 0, 255]);
+        let config = WatermarkConfig { tile: Some(true), x_offset: Some(-20), ..image_config(&watermark) };
+        // 偏移量兼作空隙：间距 10 + 20 = 30，从 x = -20 开始
+        let mut xs: Vec<i64> = tile_grid(&config, &watermark, 100, 10).unwrap().iter().map(|p| p.2).collect();
+        xs.dedup();
+        assert_eq!(xs, [-20, 10, 40, 70]);
+        
+        let out = render(&solid(100, 10, [255, 255, 255, 255]), config);
+        assert_eq!(out.get_pixel(0, 5).0, [255, 255, 255, 255]);
+        assert_eq!(out.get_pixel(10, 5).0, [255, 0, 0, 255]);
+    }
+    
+    #[test]
+    fn circle_mask_clears_corners_and_keeps_center() {
+        let config = WatermarkConfig { mask: Some("circle".to_string()), ..image_config(&solid(40, 40, [0, 128, 255, 255])) };
+        let watermark = load_and_prepare_watermark(&config).unwrap();
+        for (x, y) in [(0, 0), (39, 0), (0, 39), (39, 39), (3, 3)] {
+            assert_eq!(watermark.get_pixel(x, y)[3], 0, "({}, {})", x, y);
         }
+        assert_eq!(watermark.get_pixel(20, 20).0, [0, 128, 255, 255]);
+        // 圆周上有羽化的过渡像素
+        assert!(watermark.pixels().any(|p| p[3] > 0 && p[3] < 255));
     }
     
-    // 编码为PNG（预分配缓冲区以减少重新分配）
-    let (width, height) = img.dimensions();
-    // 预估 PNG 编码后的大小：width * height * 4 (RGBA) + 头部开销
-    let estimated_size = (width * height * 4) as usize + 1024;
-    let mut buffer = Vec::with_capacity(estimated_size);
-    img.write_to(&mut Cursor::new(&mut buffer), image::ImageFormat::Png)
-        .map_err(|e| JsValue::from_str(&format!("Failed to encode image: {}", e)))?;
+    #[test]
+    fn jitter_is_reproducible_per_seed() {
+        let base = solid(120, 120, [255, 255, 255, 255]);
+        let config = |seed| WatermarkConfig {
+            tile: Some(true),
+            x_offset: Some(10),
+            y_offset: Some(10),
+            jitter: Some(0.5),
+            seed: Some(seed),
+            ..image_config(&solid(10, 10, [255, 0, 0, 255]))
+        };
+        assert_eq!(render(&base, config(7)), render(&base, config(7)));
+        assert_ne!(render(&base, config(7)), render(&base, config(8)));
+    }
     
-    Ok(buffer)
-}
-
-// 初始化函数
-#[wasm_bindgen(start)]
-pub fn init() {
-    #[cfg(feature = "console_error_panic_hook")]
-    set_once();
+    #[test]
+    fn opaque_png_output_drops_the_alpha_channel() {
+        let base = RgbaImage::from_fn(64, 64, |x, y| image::Rgba([(x * 4) as u8, (y * 4) as u8, ((x ^ y) * 4) as u8, 255]));
+        let bytes = add_watermark_native(&png_bytes(&base), image_config(&solid(8, 8, [255, 0, 0, 128]))).unwrap();
+        assert_eq!(image::load_from_memory(&bytes).unwrap().color(), image::ColorType::Rgb8);
+        // 与强制 RGBA 编码的同一结果相比体积更小
+        assert!(bytes.len() < png_bytes(&decode(&bytes)).len());
+        
+        // 仍有透明像素时保留 alpha
+        let transparent = add_watermark_native(&png_bytes(&solid(16, 16, [0, 0, 0, 0])), image_config(&solid(4, 4, [255, 0, 0, 255]))).unwrap();
+        assert_eq!(image::load_from_memory(&transparent).unwrap().color(), image::ColorType::Rgba8);
+    }
+    
+    #[test]
+    fn edge_matte_lightens_dark_fringes_only() {
+        // 透明底图上的抗锯齿黑边（alpha 64），中间叠加不透明红色水印
+        let base = RgbaImage::from_fn(8, 8, |x, y| image::Rgba(if x == 0 || y == 0 { [0, 0, 0, 64] } else { [0, 0, 0, 0] }));
+        let config = WatermarkConfig {
+            edge_matte_color: Some("#FFFFFF".to_string()),
+            x_offset: Some(2),
+            y_offset: Some(2),
+            ..image_config(&solid(4, 4, [255, 0, 0, 255]))
+        };
+        let out = render(&base, config);
+        // t = 0.5 * (1 - 64/255)，RGB 从 0 提升到约 96，alpha 不变
+        let fringe = out.get_pixel(0, 3);
+        assert!(fringe.0[..3].iter().all(|&c| (94..=98).contains(&c)) && fringe[3] == 64);
+        assert_eq!(out.get_pixel(3, 3).0, [255, 0, 0, 255]);
+        assert_eq!(out.get_pixel(7, 7)[3], 0);
+    }
+    
+    #[test]
+    fn animated_gif_without_animated_flag_uses_first_frame() {
+        let gif = animated_gif(&[[255, 0, 0, 255], [0, 0, 255, 255], [0, 255, 0, 255]], None);
+        let config = WatermarkConfig { x_offset: Some(4), y_offset: Some(4), ..image_config(&solid(4, 4, [255, 255, 255, 255])) };
+        let frames = gif_frames(&add_watermark_native(&gif, config).unwrap());
+        assert_eq!(frames.len(), 1);
+        let frame = frames[0].buffer();
+        assert_eq!(frame.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(frame.get_pixel(5, 5).0, [255, 255, 255, 255]);
+    }
+    
+    #[test]
+    fn drop_shadow_keeps_the_watermark_in_place() {
+        let base = RgbaImage::from_fn(40, 40, |x, y| image::Rgba([(x * 6) as u8, (y * 6) as u8, 200, 255]));
+        let logo = RgbaImage::from_fn(6, 6, |x, y| image::Rgba([255, (x * 40) as u8, (y * 40) as u8, 255]));
+        for (position, offset) in [(None, 10), (Some("bottom-right"), 0)] {
+            let plain = WatermarkConfig {
+                position: position.map(str::to_string),
+                x_offset: Some(offset),
+                y_offset: Some(offset),
+                ..image_config(&logo)
+            };
+            let shadowed = WatermarkConfig {
+                shadow: Some(true),
+                shadow_blur: Some(2.0),
+                shadow_offset_x: Some(-3),
+                shadow_offset_y: Some(-2),
+                ..plain.clone()
+            };
+            let rect = placements(&base, &plain)[0];
+            assert_eq!(placements(&base, &shadowed), [rect]);
+            let (a, b) = (render(&base, plain), render(&base, shadowed));
+            for (x, y) in [(rect.x, rect.y), (rect.x + 5, rect.y + 5)] {
+                assert_eq!(a.get_pixel(x, y), b.get_pixel(x, y));
+            }
+            // 阴影向左上方超出水印区域
+            assert_ne!(a.get_pixel(rect.x - 2, rect.y - 1), b.get_pixel(rect.x - 2, rect.y - 1));
+        }
+    }
 }
\ No newline at end of file
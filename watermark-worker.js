@@ -24,7 +24,7 @@ self.onmessage = async function(e) {
                 self.postMessage({ type: 'error', error: 'Unknown message type' });
         }
     } catch (error) {
-        self.postMessage({ type: 'error', error: error.message });
+        self.postMessage({ type: 'error', error: error.message, error_code: error.error_code });
     }
 };
 
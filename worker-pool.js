@@ -61,7 +61,11 @@ class WorkerPool {
             task.resolve(e.data.result);
         } else if (e.data.type === 'error') {
             // 任务失败
-            task.reject(new Error(e.data.error));
+            const error = new Error(e.data.error);
+            if (e.data.error_code !== undefined) {
+                error.error_code = e.data.error_code;
+            }
+            task.reject(error);
         }
 
         // 标记Worker为空闲